@@ -75,6 +75,8 @@ fn main() {
                                     "Counter tool to count things",
                                     build_ui);
 
+    let _ = rt.store().register_schema(libimagcounter::counter::header_schema()).map_err_trace();
+
     rt.cli()
         .subcommand_name()
         .map_or_else(|| {
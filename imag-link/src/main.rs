@@ -55,10 +55,12 @@ use libimagstore::store::FileLockEntry;
 use libimagstore::store::Store;
 use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
 use libimagentrylink::external::ExternalLinker;
+use libimagentrylink::internal::Relation;
 use libimagutil::warn_result::*;
 use libimagutil::warn_exit::warn_exit;
 use libimagutil::info_result::*;
 use clap::ArgMatches;
+use toml::Value;
 use url::Url;
 
 mod ui;
@@ -77,6 +79,8 @@ fn main() {
             match name {
                 "internal" => handle_internal_linking(&rt),
                 "external" => handle_external_linking(&rt),
+                "check"    => handle_check(&rt),
+                "graph"    => handle_graph(&rt),
                 _ => warn_exit("No commandline call", 1)
             }
         });
@@ -94,18 +98,24 @@ fn handle_internal_linking(rt: &Runtime) {
             debug!("Listing for '{}'", entry);
             match get_entry_by_name(rt, entry) {
                 Ok(Some(e)) => {
-                    e.get_internal_links()
+                    e.get_related_links()
                         .map(|links| {
                             let i = links
+                                .into_iter()
                                 .filter_map(|l| {
-                                    l.to_str()
+                                    l.link()
+                                        .to_str()
                                         .map_warn_err(|e| format!("Failed to convert StoreId to string: {:?}", e))
                                         .ok()
+                                        .map(|s| (s, l.relation().map(|r| r.name().clone())))
                                 })
                                 .enumerate();
 
-                            for (i, link) in i {
-                                println!("{: <3}: {}", i, link);
+                            for (i, (link, relation)) in i {
+                                match relation {
+                                    Some(r) => println!("{: <3}: {} ({})", i, link, r),
+                                    None    => println!("{: <3}: {}", i, link),
+                                }
                             }
                         })
                         .map_err_trace()
@@ -145,8 +155,13 @@ fn handle_internal_linking(rt: &Runtime) {
 
         match cmd.subcommand_name() {
             Some("add") => {
+                let relation = get_relation(rt);
                 for mut to_entry in to {
-                    if let Err(e) = to_entry.add_internal_link(&mut from) {
+                    let res = match relation {
+                        Some(ref r) => from.add_related_link(&mut to_entry, r.clone()),
+                        None        => to_entry.add_internal_link(&mut from),
+                    };
+                    if let Err(e) = res {
                         trace_error_exit(&e, 1);
                     }
                 }
@@ -205,6 +220,38 @@ fn get_to_entries<'a>(rt: &'a Runtime) -> Option<Vec<FileLockEntry<'a>>> {
         })
 }
 
+fn get_relation(rt: &Runtime) -> Option<Relation> {
+    let scmd = rt.cli()
+        .subcommand_matches("internal")
+        .unwrap() // safe, we know there is an "internal" subcommand"
+        .subcommand_matches("add")
+        .unwrap(); // safe, we know there is an "add" subcommand
+
+    scmd.value_of("relation")
+        .map(|name| {
+            let mut relation = Relation::new(name);
+
+            if let Some(inverse) = scmd.value_of("inverse") {
+                relation = relation.with_inverse(inverse);
+            }
+
+            for attr in scmd.values_of("attribute").into_iter().flat_map(|v| v) {
+                match attr.find('=') {
+                    Some(i) => {
+                        let value = Value::String(String::from(&attr[(i + 1)..]));
+                        relation  = relation.with_attribute(&attr[..i], value);
+                    },
+                    None => {
+                        let msg = format!("Attribute is not of the form KEY=VALUE: {}", attr);
+                        warn_exit(&msg, 1)
+                    },
+                }
+            }
+
+            relation
+        })
+}
+
 fn get_entry_by_name<'a>(rt: &'a Runtime, name: &str) -> Result<Option<FileLockEntry<'a>>, StoreError> {
     use std::path::PathBuf;
     use libimagstore::storeid::StoreId;
@@ -309,3 +356,93 @@ fn list_links_for_entry(store: &Store, entry: &mut FileLockEntry) {
         .ok();
 }
 
+fn handle_check(rt: &Runtime) {
+    use libimagentrylink::check::{check_links, repair};
+
+    let scmd     = rt.cli().subcommand_matches("check").unwrap();
+    let problems = check_links(rt.store()).map_err_trace_exit(1).unwrap(); // safe by above call
+
+    if problems.is_empty() {
+        info!("No problems found");
+        return;
+    }
+
+    let mut unresolved = 0;
+    for problem in problems.iter() {
+        println!("{}", problem);
+
+        if scmd.is_present("repair") {
+            match repair(rt.store(), problem) {
+                Ok(_)  => info!("Repaired"),
+                Err(e) => {
+                    trace_error(&e);
+                    unresolved += 1;
+                },
+            }
+        } else {
+            unresolved += 1;
+        }
+    }
+
+    if unresolved != 0 {
+        warn_exit(&format!("{} of {} problems unresolved", unresolved, problems.len()), 1);
+    }
+}
+
+fn handle_graph(rt: &Runtime) {
+    use std::path::PathBuf;
+    use libimagentrylink::graph::LinkGraph;
+    use libimagstore::storeid::StoreId;
+
+    let scmd  = rt.cli().subcommand_matches("graph").unwrap();
+    let graph = LinkGraph::from_store(rt.store()).map_err_trace_exit(1).unwrap(); // safe by above call
+
+    let to_id = |s: &str| StoreId::new_baseless(PathBuf::from(s)).map_err_trace_exit(1).unwrap();
+
+    if let Some(mut ids) = scmd.values_of("path") {
+        let from = to_id(ids.next().unwrap()); // safe, clap ensures two values
+        let to   = to_id(ids.next().unwrap());
+
+        match graph.shortest_path(&from, &to) {
+            Some(path) => for id in path {
+                println!("{}", id);
+            },
+            None => warn_exit(&format!("'{}' and '{}' are not connected", from, to), 1),
+        }
+    } else if scmd.is_present("components") {
+        for (i, component) in graph.connected_components().into_iter().enumerate() {
+            let ids = component.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+            println!("{: <3}: {}", i, ids.join(", "));
+        }
+    } else if scmd.is_present("orphans") {
+        for id in graph.orphans() {
+            println!("{}", id);
+        }
+    } else {
+        let graph = match scmd.value_of("id") {
+            Some(id) => {
+                let depth = scmd.value_of("depth").map(|d| {
+                    d.parse::<usize>()
+                        .unwrap_or_else(|_| warn_exit(&format!("Not a number: {}", d), 1))
+                });
+
+                let ids = graph.bfs(&to_id(id), depth)
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>();
+
+                if ids.is_empty() {
+                    warn_exit(&format!("Entry not found: {}", id), 1);
+                }
+
+                graph.subgraph(&ids)
+            },
+            None => graph,
+        };
+
+        match scmd.value_of("format") {
+            Some("graphml") => print!("{}", graph.to_graphml()),
+            _               => print!("{}", graph.to_dot()),
+        }
+    }
+}
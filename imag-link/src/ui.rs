@@ -42,6 +42,30 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                                      .multiple(true)
                                      .help("Link to this entries")
                                      .value_name("ENTRIES"))
+                                .arg(Arg::with_name("relation")
+                                     .long("relation")
+                                     .short("r")
+                                     .takes_value(true)
+                                     .required(false)
+                                     .help("The relation of the link, as seen from the 'from' entry")
+                                     .value_name("RELATION"))
+                                .arg(Arg::with_name("inverse")
+                                     .long("inverse")
+                                     .short("i")
+                                     .takes_value(true)
+                                     .required(false)
+                                     .requires("relation")
+                                     .help("The relation of the link, as seen from the 'to' entries. Defaults to the relation")
+                                     .value_name("RELATION"))
+                                .arg(Arg::with_name("attribute")
+                                     .long("attribute")
+                                     .short("a")
+                                     .takes_value(true)
+                                     .required(false)
+                                     .multiple(true)
+                                     .requires("relation")
+                                     .help("Attributes of the relation")
+                                     .value_name("KEY=VALUE"))
                                 )
 
                     .subcommand(SubCommand::with_name("remove")
@@ -119,4 +143,73 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                            .required(true))
 
                     )
+        .subcommand(SubCommand::with_name("check")
+                    .about("Check the links of all entries for consistency")
+                    .version("0.1")
+
+                    .arg(Arg::with_name("repair")
+                         .long("repair")
+                         .short("r")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Repair the problems which are found"))
+                    )
+        .subcommand(SubCommand::with_name("graph")
+                    .about("Print the graph of the internal links")
+                    .version("0.1")
+
+                    .arg(Arg::with_name("id")
+                         .long("id")
+                         .short("i")
+                         .takes_value(true)
+                         .required(false)
+                         .help("Only print the entries which are reachable from this entry")
+                         .value_name("ENTRY"))
+
+                    .arg(Arg::with_name("depth")
+                         .long("depth")
+                         .short("d")
+                         .takes_value(true)
+                         .required(false)
+                         .requires("id")
+                         .help("Only follow this many links from the entry")
+                         .value_name("N"))
+
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .short("f")
+                         .takes_value(true)
+                         .required(false)
+                         .possible_values(&["dot", "graphml"])
+                         .default_value("dot")
+                         .help("The output format")
+                         .value_name("FORMAT"))
+
+                    .arg(Arg::with_name("path")
+                         .long("path")
+                         .short("p")
+                         .takes_value(true)
+                         .required(false)
+                         .number_of_values(2)
+                         .help("Print the shortest path between two entries")
+                         .value_name("ENTRY"))
+
+                    .arg(Arg::with_name("components")
+                         .long("components")
+                         .short("c")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Print the groups of entries which are linked with each other"))
+
+                    .arg(Arg::with_name("orphans")
+                         .long("orphans")
+                         .short("o")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Print the entries which are not linked at all"))
+
+                    .group(ArgGroup::with_name("graph-query-group")
+                           .args(&["path", "components", "orphans"])
+                           .required(false))
+                    )
 }
@@ -0,0 +1,262 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+extern crate clap;
+#[macro_use] extern crate log;
+extern crate semver;
+#[macro_use] extern crate version;
+
+extern crate libimagstore;
+extern crate libimagrt;
+extern crate libimagmail;
+extern crate libimagref;
+extern crate libimagerror;
+extern crate libimagentrylist;
+extern crate libimagentrylink;
+extern crate libimagentrytag;
+extern crate libimagutil;
+
+use std::path::PathBuf;
+
+use libimagmail::import::{ImportReport, Importer};
+use libimagmail::mail::Mail;
+use libimagmail::result::Result;
+use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
+use libimagrt::runtime::Runtime;
+use libimagrt::setup::generate_runtime_setup;
+use libimagutil::warn_exit::warn_exit;
+
+mod ui;
+
+use ui::build_ui;
+
+fn main() {
+    let rt = generate_runtime_setup("imag-mail",
+                                    &version!()[..],
+                                    "Mail collection tool",
+                                    build_ui);
+
+    if let Err(e) = rt.store().register_schema(libimagref::reference::header_schema()) {
+        trace_error(&e);
+    }
+
+    rt.cli()
+        .subcommand_name()
+        .map(|name| {
+            debug!("Call {}", name);
+            match name {
+                "import"         => import_mail(&rt),
+                "import-maildir" => import_maildir(&rt),
+                "import-mbox"    => import_mbox(&rt),
+                "list"           => list(&rt),
+                "show"           => show(&rt),
+                "tag"            => tag(&rt),
+                "link"           => link(&rt),
+                _                => debug!("Unknown command") // More error handling
+            }
+        });
+}
+
+fn import_mail(rt: &Runtime) {
+    use libimagentrytag::tagable::Tagable;
+    use libimagentrytag::ui::get_add_tags;
+
+    let scmd = rt.cli().subcommand_matches("import").unwrap();
+    let path = scmd.value_of("path").unwrap(); // enforced by clap
+
+    let mut mail = Mail::import_from_path(rt.store(), path).map_err_trace_exit(1).unwrap();
+
+    if let Some(tags) = get_add_tags(scmd) {
+        for tag in tags {
+            if let Err(e) = mail.add_tag(tag) {
+                trace_error(&e);
+            }
+        }
+    }
+
+    match mail.get_hash() {
+        Ok(hash) => println!("{}", hash),
+        Err(e)   => trace_error(&e),
+    }
+}
+
+fn import_maildir(rt: &Runtime) {
+    let path = rt.cli().subcommand_matches("import-maildir").unwrap().value_of("path").unwrap();
+
+    let report = Importer::new(rt.store())
+        .and_then(|mut importer| importer.import_maildir(path))
+        .map_err_trace_exit(1)
+        .unwrap();
+
+    print_report(&report);
+}
+
+fn import_mbox(rt: &Runtime) {
+    let scmd   = rt.cli().subcommand_matches("import-mbox").unwrap();
+    let path   = scmd.value_of("path").unwrap(); // enforced by clap
+    let target = scmd.value_of("target").unwrap(); // enforced by clap
+
+    let report = Importer::new(rt.store())
+        .and_then(|mut importer| importer.import_mbox(path, target))
+        .map_err_trace_exit(1)
+        .unwrap();
+
+    print_report(&report);
+}
+
+fn print_report(report: &ImportReport) {
+    println!("Imported: {}", report.imported());
+    println!("Skipped:  {}", report.skipped());
+    println!("Failed:   {}", report.failed());
+}
+
+fn list(rt: &Runtime) {
+    use std::collections::HashMap;
+
+    use libimagentrylist::lister::Lister;
+    use libimagentrylist::listers::table::TableLister;
+    use libimagstore::store::FileLockEntry;
+
+    let mails = Mail::all(rt.store(), None).map_err_trace_exit(1).unwrap().collect::<Vec<_>>();
+
+    let mut rows = HashMap::new();
+    for mail in mails.iter() {
+        let hash = mail.get_hash().map_err_trace().unwrap_or(String::from("<unknown>"));
+        let row  = vec![
+            hash,
+            field_or_empty(mail.get_from()),
+            field_or_empty(mail.get_subject()),
+            field_or_empty(mail.get_date()),
+        ];
+        rows.insert(mail.get_location().clone(), row);
+    }
+
+    let header = vec!["Hash", "From", "Subject", "Date"].into_iter().map(String::from).collect();
+
+    TableLister::new(|fle: &FileLockEntry| rows.get(fle.get_location()).cloned().unwrap_or(vec![]))
+        .with_header(header)
+        .with_idx(false)
+        .list(mails.into_iter().map(|mail| -> FileLockEntry { mail.into() }))
+        .map_err_trace()
+        .ok();
+}
+
+fn show(rt: &Runtime) {
+    let hash = rt.cli().subcommand_matches("show").unwrap().value_of("hash").unwrap();
+    let mail = get_mail(rt, hash);
+
+    for &(name, ref value) in [
+        ("From",        mail.get_from()),
+        ("To",          mail.get_to()),
+        ("Date",        mail.get_date()),
+        ("Subject",     mail.get_subject()),
+        ("Message-ID",  mail.get_message_id()),
+        ("In-Reply-To", mail.get_in_reply_to()),
+    ].iter() {
+        match *value {
+            Ok(Some(ref value)) => println!("{}: {}", name, value),
+            Ok(None)            => (),
+            Err(ref e)          => trace_error(e),
+        }
+    }
+
+    match mail.get_body() {
+        Ok(body) => println!("\n{}", body),
+        Err(e)   => trace_error(&e),
+    }
+}
+
+fn tag(rt: &Runtime) {
+    use libimagentrytag::exec::exec_cli_for_entry;
+
+    let scmd     = rt.cli().subcommand_matches("tag").unwrap();
+    let hash     = scmd.value_of("hash").unwrap(); // enforced by clap
+    let mut mail = get_mail(rt, hash);
+
+    if let Err(e) = exec_cli_for_entry(scmd, &mut mail) {
+        trace_error_exit(&e, 1);
+    }
+}
+
+fn link(rt: &Runtime) {
+    use libimagentrylink::internal::InternalLinker;
+    use libimagstore::storeid::StoreId;
+
+    let scmd     = rt.cli().subcommand_matches("link").unwrap();
+    let hash     = scmd.value_of("hash").unwrap(); // enforced by clap
+    let mut mail = get_mail(rt, hash);
+
+    let mut targets = vec![];
+    for name in scmd.values_of("to").map(|v| v.collect::<Vec<_>>()).unwrap_or(vec![]) {
+        let id = StoreId::new(Some(rt.store().path().clone()), PathBuf::from(name))
+            .map_err_trace_exit(1)
+            .unwrap();
+
+        match rt.store().get(id) {
+            Ok(Some(entry)) => targets.push(entry),
+            Ok(None)        => warn_exit(&format!("Entry not found: {}", name), 1),
+            Err(e)          => trace_error_exit(&e, 1),
+        }
+    }
+
+    if scmd.is_present("thread") {
+        match mail.get_parent(rt.store()) {
+            Ok(Some(parent)) => targets.push(parent.into()),
+            Ok(None)         => warn!("The mail is not a reply to a mail in the store"),
+            Err(e)           => trace_error_exit(&e, 1),
+        }
+    }
+
+    if targets.is_empty() {
+        warn_exit("Nothing to link to, use --to or --thread", 1);
+    }
+
+    for mut target in targets {
+        if let Err(e) = mail.add_internal_link(&mut target) {
+            trace_error_exit(&e, 1);
+        }
+    }
+}
+
+fn get_mail<'a>(rt: &'a Runtime, hash: &str) -> Mail<'a> {
+    match Mail::open(rt.store(), hash) {
+        Ok(Some(mail)) => mail,
+        Ok(None)       => warn_exit(&format!("No mail with hash '{}'", hash), 1),
+        Err(e)         => trace_error_exit(&e, 1),
+    }
+}
+
+fn field_or_empty(field: Result<Option<String>>) -> String {
+    field.map_err_trace().ok().and_then(|f| f).unwrap_or(String::new())
+}
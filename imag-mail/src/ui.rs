@@ -0,0 +1,124 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use clap::{Arg, App, SubCommand};
+
+use libimagentrytag::ui::{tag_add_arg, tag_remove_arg};
+use libimagutil::cli_validators::{is_directory, is_file};
+
+pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app
+        .subcommand(SubCommand::with_name("import")
+                    .about("Import a mail into the store")
+                    .version("0.1")
+                    .arg(Arg::with_name("path")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The path of the mail file")
+                         .validator(is_file)
+                         .value_name("PATH"))
+                    .arg(tag_add_arg())
+                    )
+
+        .subcommand(SubCommand::with_name("import-maildir")
+                    .about("Import all mails of a Maildir, skipping the ones already imported")
+                    .version("0.1")
+                    .arg(Arg::with_name("path")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The path of the Maildir")
+                         .validator(is_directory)
+                         .value_name("PATH"))
+                    )
+
+        .subcommand(SubCommand::with_name("import-mbox")
+                    .about("Import all mails of a mbox file, skipping the ones already imported")
+                    .version("0.1")
+                    .arg(Arg::with_name("path")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The path of the mbox file")
+                         .validator(is_file)
+                         .value_name("PATH"))
+                    .arg(Arg::with_name("target")
+                         .long("target")
+                         .short("t")
+                         .takes_value(true)
+                         .required(true)
+                         .help("The directory the mails are written to, one file per mail")
+                         .value_name("DIR"))
+                    )
+
+        .subcommand(SubCommand::with_name("list")
+                    .about("List the mails in the store")
+                    .version("0.1")
+                    )
+
+        .subcommand(SubCommand::with_name("show")
+                    .about("Show a mail")
+                    .version("0.1")
+                    .arg(Arg::with_name("hash")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The hash of the mail, as printed by 'list'")
+                         .value_name("HASH"))
+                    )
+
+        .subcommand(SubCommand::with_name("tag")
+                    .about("Add or remove tags of a mail")
+                    .version("0.1")
+                    .arg(Arg::with_name("hash")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The hash of the mail, as printed by 'list'")
+                         .value_name("HASH"))
+                    .arg(tag_add_arg())
+                    .arg(tag_remove_arg())
+                    )
+
+        .subcommand(SubCommand::with_name("link")
+                    .about("Link a mail to entries in the store")
+                    .version("0.1")
+                    .arg(Arg::with_name("hash")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .help("The hash of the mail, as printed by 'list'")
+                         .value_name("HASH"))
+                    .arg(Arg::with_name("to")
+                         .long("to")
+                         .short("t")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .help("Link the mail to these entries")
+                         .value_name("ENTRY"))
+                    .arg(Arg::with_name("thread")
+                         .long("thread")
+                         .short("T")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Link the mail to the mail it is a reply to"))
+                    )
+}
@@ -22,19 +22,23 @@ extern crate clap;
 extern crate semver;
 #[macro_use] extern crate version;
 extern crate itertools;
+extern crate filters;
 
 extern crate libimagnotes;
 extern crate libimagrt;
 extern crate libimagentryedit;
 extern crate libimagentrytag;
+extern crate libimagentryfilter;
 extern crate libimagerror;
 extern crate libimagutil;
 
 use std::process::exit;
 
+use filters::filter::Filter;
 use itertools::Itertools;
 
 use libimagentryedit::edit::Edit;
+use libimagentryfilter::cli::filter_from_cli;
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
 use libimagnotes::note::Note;
@@ -51,6 +55,8 @@ fn main() {
                                     "Note taking helper",
                                     build_ui);
 
+    let _ = rt.store().register_schema(libimagnotes::note::header_schema()).map_err_trace();
+
     rt.cli()
         .subcommand_name()
         .map(|name| {
@@ -112,10 +118,16 @@ fn edit_entry(rt: &Runtime, name: String) -> bool {
 fn list(rt: &Runtime) {
     use std::cmp::Ordering;
 
+    let filter = rt.cli()
+        .subcommand_matches("list")
+        .map(|scmd| filter_from_cli(scmd).map_err_trace_exit(1).unwrap()) // safe by above call
+        .and_then(|f| f);
+
     Note::all_notes(rt.store())
         .map_err_trace_exit(1)
         .map(|iter| {
             let notes = iter.filter_map(|note| note.map_err_trace().ok())
+                .filter(|note| filter.as_ref().map(|f| f.filter(note)).unwrap_or(true))
                 .sorted_by(|note_a, note_b| {
                     if let (Ok(a), Ok(b)) = (note_a.get_name(), note_b.get_name()) {
                         return a.cmp(&b)
@@ -36,6 +36,8 @@
 #[macro_use] extern crate version;
 extern crate semver;
 extern crate clap;
+extern crate inotify;
+extern crate toml;
 
 extern crate libimagstore;
 extern crate libimagrt;
@@ -46,13 +48,19 @@ extern crate libimaginteraction;
 extern crate libimagutil;
 
 mod ui;
+mod watch;
+
 use ui::build_ui;
+use watch::watch;
 
 use std::path::PathBuf;
 
+use toml::Value;
+
 use libimagref::reference::Ref;
 use libimagref::flags::RefFlags;
-use libimagerror::trace::trace_error;
+use libimagref::hashers::NamedHasher;
+use libimagerror::trace::{trace_error, trace_error_exit};
 use libimagrt::setup::generate_runtime_setup;
 use libimagrt::runtime::Runtime;
 
@@ -61,6 +69,11 @@ fn main() {
                                     &version!()[..],
                                     "Reference files outside of the store",
                                     build_ui);
+
+    if let Err(e) = rt.store().register_schema(libimagref::reference::header_schema()) {
+        trace_error(&e);
+    }
+
     rt.cli()
         .subcommand_name()
         .map(|name| {
@@ -69,6 +82,8 @@ fn main() {
                 "add"    => add(&rt),
                 "remove" => remove(&rt),
                 "list"   => list(&rt),
+                "repair" => repair(&rt),
+                "watch"  => watch(&rt),
                 _        => {
                     debug!("Unknown command"); // More error handling
                 },
@@ -77,14 +92,27 @@ fn main() {
 }
 
 fn add(rt: &Runtime) {
+    use std::process::exit;
+
     let cmd  = rt.cli().subcommand_matches("add").unwrap();
     let path = cmd.value_of("path").map(PathBuf::from).unwrap(); // saved by clap
 
+    let hasher = cmd.value_of("hasher")
+        .map(String::from)
+        .or_else(|| get_config_string(rt, "ref.hasher"))
+        .map(|name| {
+            NamedHasher::from_name(&name).unwrap_or_else(|| {
+                warn!("Unknown hasher: {}", name);
+                exit(1)
+            })
+        })
+        .unwrap_or(NamedHasher::Default);
+
     let flags = RefFlags::default()
-        .with_content_hashing(cmd.is_present("track-content"))
+        .with_content_hashing(cmd.is_present("track-content") || cmd.is_present("hasher"))
         .with_permission_tracking(cmd.is_present("track-permissions"));
 
-    match Ref::create(rt.store(), path, flags) {
+    match Ref::create_with_hasher(rt.store(), path, flags, hasher) {
         Ok(r) => {
             debug!("Reference created: {:?}", r);
             info!("Ok");
@@ -151,3 +179,112 @@ fn list(rt: &Runtime) {
         .ok();
 }
 
+fn repair(rt: &Runtime) {
+    use libimaginteraction::ask::ask_bool;
+    use libimagref::hasher::Hasher;
+    use libimagref::repair::{HashIndex, find_dead_refs};
+    use libimagref::repair::repair as repair_ref;
+    use libimagutil::warn_exit::warn_exit;
+
+    let cmd         = rt.cli().subcommand_matches("repair").unwrap();
+    let interactive = cmd.is_present("interactive");
+    let dry_run     = cmd.is_present("dry-run");
+
+    let roots = match cmd.values_of("root") {
+        Some(roots) => roots.map(PathBuf::from).collect(),
+        None        => get_search_roots(rt),
+    };
+
+    if roots.is_empty() {
+        warn_exit("No search roots given and none configured in 'ref.search_roots'", 1);
+    }
+
+    // check for dead refs before walking the search roots, which is expensive
+    let dead = match find_dead_refs(rt.store(), &HashIndex::default()) {
+        Ok(dead) => dead,
+        Err(e)   => trace_error_exit(&e, 1),
+    };
+
+    if dead.is_empty() {
+        info!("No dead references");
+        return;
+    }
+
+    // only hash the files with the hashers the dead refs were created with
+    let mut hashers = dead.iter().filter_map(|d| d.hasher()).collect::<Vec<_>>();
+    hashers.sort_by_key(|h| h.hash_name());
+    hashers.dedup();
+
+    info!("Searching files below {:?}", roots);
+    let index = HashIndex::build(&roots, &hashers);
+    debug!("Indexed {} distinct hashes", index.len());
+
+    let dead = match find_dead_refs(rt.store(), &index) {
+        Ok(dead) => dead,
+        Err(e)   => trace_error_exit(&e, 1),
+    };
+
+    let mut unresolved = vec![];
+    for d in dead.iter() {
+        let found = match d.found() {
+            Some(found) => found,
+            None => {
+                unresolved.push(d);
+                continue;
+            },
+        };
+
+        println!("{}: {} -> {}", d.id(), d.path().display(), found.display());
+
+        if dry_run || (interactive && !ask_bool("Repair this reference?", Some(true))) {
+            continue;
+        }
+
+        if let Err(e) = repair_ref(rt.store(), d) {
+            trace_error(&e);
+            unresolved.push(d);
+        }
+    }
+
+    if !unresolved.is_empty() {
+        println!("Could not resolve {} of {} dead references:", unresolved.len(), dead.len());
+        for d in unresolved {
+            println!("{}: {}", d.id(), d.path().display());
+        }
+    }
+}
+
+/// Get the directories `imag-ref repair` searches for moved files, from the configuration
+///
+/// ```toml
+/// [ref]
+/// search_roots = [ "/home/user/documents" ]
+/// ```
+fn get_search_roots(rt: &Runtime) -> Vec<PathBuf> {
+    rt.config()
+        .and_then(|config| config.config().lookup("ref.search_roots").cloned())
+        .map(|roots| match roots {
+            Value::Array(roots) => roots.into_iter()
+                .filter_map(|root| match root {
+                    Value::String(s) => Some(PathBuf::from(s)),
+                    _ => {
+                        warn!("Search root is not a String: {:?}", root);
+                        None
+                    },
+                })
+                .collect(),
+            _ => {
+                warn!("'ref.search_roots' is not an Array");
+                vec![]
+            },
+        })
+        .unwrap_or(vec![])
+}
+
+fn get_config_string(rt: &Runtime, key: &str) -> Option<String> {
+    rt.config()
+        .and_then(|config| match config.config().lookup(key) {
+            Some(&Value::String(ref s)) => Some(s.clone()),
+            _ => None,
+        })
+}
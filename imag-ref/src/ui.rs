@@ -40,6 +40,14 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .takes_value(false)
                          .required(false)
                          .help("Hash the content for the reference"))
+                    .arg(Arg::with_name("hasher")
+                         .long("hasher")
+                         .short("H")
+                         .takes_value(true)
+                         .required(false)
+                         .possible_values(&["default", "sha256", "blake2b", "sampling"])
+                         .help("Hash the content for the reference with this hasher. 'sampling' only hashes the start and the end of the file, for very large files")
+                         .value_name("HASHER"))
                     .arg(Arg::with_name("track-permissions")
                          .long("permission-tracking")
                          .short("P")
@@ -90,4 +98,39 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .help("Check whether the permissions of the referenced file changed"))
 
                     )
+
+        .subcommand(SubCommand::with_name("repair")
+                    .about("Find the files of dead references and point the references to them")
+                    .version("0.1")
+
+                    .arg(Arg::with_name("root")
+                         .long("root")
+                         .short("r")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .help("Search for the files below this directory. Overrides the search roots from the configuration")
+                         .validator(is_existing_path)
+                         .value_name("PATH"))
+
+                    .arg(Arg::with_name("interactive")
+                         .long("interactive")
+                         .short("i")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Ask before repairing each reference"))
+
+                    .arg(Arg::with_name("dry-run")
+                         .long("dry-run")
+                         .short("n")
+                         .takes_value(false)
+                         .required(false)
+                         .help("Only print what would be repaired"))
+
+                    )
+
+        .subcommand(SubCommand::with_name("watch")
+                    .about("Watch the referenced files and keep the references up to date")
+                    .version("0.1")
+                    )
 }
@@ -0,0 +1,355 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use inotify::INotify;
+use inotify::ffi::{IN_ATTRIB, IN_CLOSE_WRITE, IN_MOVED_FROM, IN_MOVED_TO};
+
+use libimagref::reference::Ref;
+use libimagrt::runtime::Runtime;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagerror::trace::{trace_error, trace_error_exit};
+
+/// How long the MOVED_FROM half of a rename waits for its MOVED_TO half
+///
+/// Both events of a rename are usually read in the same batch, but they may be split over two
+/// reads.
+const MOVE_TIMEOUT_SECS: u64 = 2;
+
+/// What happened to a watched file
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    Modified(PathBuf),
+    MovedFrom(u32, PathBuf),
+    MovedTo(u32, PathBuf),
+}
+
+/// What has to be done to a ref because of a `Change`
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Update(StoreId),
+    Move(StoreId, PathBuf),
+}
+
+/// A ref pointing to a watched file
+#[derive(Debug, Clone)]
+struct WatchedRef {
+    id: StoreId,
+
+    /// Whether the ref is flagged `often_moving`, which means that the content of the file is
+    /// hashed
+    often_moving: bool,
+}
+
+/// The refs of the watched files and the renames which were not completed yet
+struct State {
+    refs: BTreeMap<PathBuf, Vec<WatchedRef>>,
+    moved: BTreeMap<u32, (PathBuf, Instant)>,
+    timeout: Duration,
+}
+
+/// Watch the directories of all referenced files and keep the refs up to date
+///
+/// If a referenced file is changed, refs flagged `often_moving` are updated with the new content
+/// hash and permissions. Refs without a content hash are left alone, so they do not get one.
+/// If a referenced file is renamed or moved to another watched directory, the ref is pointed to
+/// the new path. Runs until it is killed.
+pub fn watch(rt: &Runtime) {
+    let refs = collect_refs(rt.store());
+    info!("Watching {} referenced files", refs.len());
+
+    let mut inotify = INotify::init().unwrap_or_else(|e| {
+        error!("Cannot initialize inotify: {:?}", e);
+        exit(1)
+    });
+
+    let mut dirs = BTreeMap::new();
+    let parents  = refs.keys().filter_map(|p| p.parent().map(PathBuf::from)).collect::<Vec<_>>();
+    for dir in parents {
+        add_watch(&mut inotify, &mut dirs, dir);
+    }
+
+    let mut state = State::new(refs, Duration::from_secs(MOVE_TIMEOUT_SECS));
+    loop {
+        let changes = match inotify.wait_for_events() {
+            Err(e) => {
+                error!("Cannot read inotify events: {:?}", e);
+                exit(1)
+            },
+            Ok(events) => events.iter()
+                .filter_map(|event| {
+                    dirs.get(&event.wd)
+                        .map(|dir| dir.join(&event.name))
+                        .and_then(|path| change_of(event.mask, event.cookie, path))
+                })
+                .collect::<Vec<_>>(),
+        };
+
+        let now = Instant::now();
+        for change in changes {
+            for action in state.apply(change, now) {
+                perform(rt.store(), action);
+            }
+        }
+
+        for (path, refs) in state.expire(now) {
+            for r in refs {
+                if r.often_moving {
+                    warn!("{:?} was moved out of the watched directories, {:?} can be repaired \
+                          with 'imag-ref repair'", path, r.id);
+                } else {
+                    warn!("{:?} was moved out of the watched directories, {:?} is dangling now",
+                          path, r.id);
+                }
+            }
+        }
+    }
+}
+
+/// Map the mask of an inotify event to a `Change`
+fn change_of(mask: u32, cookie: u32, path: PathBuf) -> Option<Change> {
+    if mask & IN_MOVED_FROM != 0 {
+        Some(Change::MovedFrom(cookie, path))
+    } else if mask & IN_MOVED_TO != 0 {
+        Some(Change::MovedTo(cookie, path))
+    } else if mask & (IN_CLOSE_WRITE | IN_ATTRIB) != 0 {
+        Some(Change::Modified(path))
+    } else {
+        None
+    }
+}
+
+impl State {
+
+    fn new(refs: BTreeMap<PathBuf, Vec<WatchedRef>>, timeout: Duration) -> State {
+        State {
+            refs: refs,
+            moved: BTreeMap::new(),
+            timeout: timeout,
+        }
+    }
+
+    /// Get the actions for a change, remembering the first half of a rename
+    fn apply(&mut self, change: Change, now: Instant) -> Vec<Action> {
+        match change {
+            Change::Modified(path) => self.refs
+                .get(&path)
+                .map(|refs| {
+                    refs.iter()
+                        .filter(|r| r.often_moving)
+                        .map(|r| Action::Update(r.id.clone()))
+                        .collect()
+                })
+                .unwrap_or(vec![]),
+
+            Change::MovedFrom(cookie, path) => {
+                if self.refs.contains_key(&path) {
+                    self.moved.insert(cookie, (path, now));
+                }
+                vec![]
+            },
+
+            Change::MovedTo(cookie, path) => {
+                let old = match self.moved.remove(&cookie) {
+                    Some((old, _)) => old,
+                    None           => return vec![],
+                };
+
+                let refs    = self.refs.remove(&old).unwrap_or(vec![]);
+                let actions = refs.iter()
+                    .map(|r| Action::Move(r.id.clone(), path.clone()))
+                    .collect::<Vec<_>>();
+                self.refs.insert(path, refs);
+                actions
+            },
+        }
+    }
+
+    /// Forget the renames which did not complete within the timeout
+    ///
+    /// The files were moved out of the watched directories and cannot be followed. Their old path
+    /// and the refs pointing to it are returned.
+    fn expire(&mut self, now: Instant) -> Vec<(PathBuf, Vec<WatchedRef>)> {
+        let timeout = self.timeout;
+        let expired = self.moved
+            .iter()
+            .filter(|&(_, &(_, at))| now.duration_since(at) >= timeout)
+            .map(|(cookie, _)| *cookie)
+            .collect::<Vec<_>>();
+
+        let mut gone = vec![];
+        for cookie in expired {
+            if let Some((path, _)) = self.moved.remove(&cookie) {
+                let refs = self.refs.remove(&path).unwrap_or(vec![]);
+                gone.push((path, refs));
+            }
+        }
+        gone
+    }
+
+}
+
+fn perform(store: &Store, action: Action) {
+    let res = match action {
+        Action::Update(id) => {
+            debug!("Updating {:?}", id);
+            Ref::get(store, id).and_then(|mut r| r.update_ref())
+        },
+        Action::Move(id, path) => {
+            info!("Pointing {:?} to {:?}", id, path);
+            Ref::get(store, id).and_then(|mut r| r.set_fs_file(path))
+        },
+    };
+
+    if let Err(e) = res {
+        trace_error(&e);
+    }
+}
+
+/// Get the paths of all referenced files, with the refs pointing to them
+fn collect_refs(store: &Store) -> BTreeMap<PathBuf, Vec<WatchedRef>> {
+    let ids = store.retrieve_for_module("ref").unwrap_or_else(|e| trace_error_exit(&e, 1));
+
+    let mut refs = BTreeMap::new();
+    for id in ids {
+        let res = Ref::get(store, id.clone())
+            .and_then(|r| r.fs_file().map(|path| (path, r.get_stored_hash().is_ok())));
+
+        match res {
+            Ok((path, often_moving)) => {
+                let r = WatchedRef { id: id, often_moving: often_moving };
+                refs.entry(path).or_insert_with(Vec::new).push(r)
+            },
+            Err(e) => trace_error(&e),
+        }
+    }
+    refs
+}
+
+fn add_watch(inotify: &mut INotify, dirs: &mut BTreeMap<i32, PathBuf>, dir: PathBuf) {
+    if dirs.values().any(|d| *d == dir) {
+        return;
+    }
+
+    // IN_MODIFY is not watched, it is sent for every chunk written and each would cause a rehash
+    let mask = IN_CLOSE_WRITE | IN_ATTRIB | IN_MOVED_FROM | IN_MOVED_TO;
+    match inotify.add_watch(&dir, mask) {
+        Ok(wd) => {
+            debug!("Watching {:?}", dir);
+            dirs.insert(wd, dir);
+        },
+        Err(e) => warn!("Cannot watch {:?}: {:?}", dir, e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use inotify::ffi::{IN_ATTRIB, IN_CLOSE_WRITE, IN_MODIFY, IN_MOVED_FROM, IN_MOVED_TO};
+
+    use libimagstore::storeid::StoreId;
+
+    use super::{Action, Change, State, WatchedRef, change_of};
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    fn state() -> State {
+        let mut refs = BTreeMap::new();
+        refs.insert(PathBuf::from("/a/file"), vec![
+            WatchedRef { id: id("ref/hashed"), often_moving: true },
+            WatchedRef { id: id("ref/plain"), often_moving: false },
+        ]);
+        State::new(refs, Duration::from_secs(2))
+    }
+
+    #[test]
+    fn test_change_of() {
+        let p = PathBuf::from("/a/file");
+        assert_eq!(change_of(IN_CLOSE_WRITE, 0, p.clone()), Some(Change::Modified(p.clone())));
+        assert_eq!(change_of(IN_ATTRIB, 0, p.clone()), Some(Change::Modified(p.clone())));
+        assert_eq!(change_of(IN_MOVED_FROM, 3, p.clone()), Some(Change::MovedFrom(3, p.clone())));
+        assert_eq!(change_of(IN_MOVED_TO, 3, p.clone()), Some(Change::MovedTo(3, p.clone())));
+        assert_eq!(change_of(IN_MODIFY, 0, p), None);
+    }
+
+    #[test]
+    fn test_modification_updates_only_hashed_refs() {
+        let mut state = state();
+        let actions   = state.apply(Change::Modified(PathBuf::from("/a/file")), Instant::now());
+        assert_eq!(actions, vec![Action::Update(id("ref/hashed"))]);
+
+        let actions = state.apply(Change::Modified(PathBuf::from("/a/other")), Instant::now());
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn test_rename_over_two_batches() {
+        let mut state = state();
+        let now       = Instant::now();
+
+        let actions = state.apply(Change::MovedFrom(7, PathBuf::from("/a/file")), now);
+        assert!(actions.is_empty());
+        assert!(state.expire(now).is_empty());
+
+        let actions = state.apply(Change::MovedTo(7, PathBuf::from("/b/file")), now);
+        assert_eq!(actions, vec![
+            Action::Move(id("ref/hashed"), PathBuf::from("/b/file")),
+            Action::Move(id("ref/plain"), PathBuf::from("/b/file")),
+        ]);
+
+        // the refs are known under the new path now
+        let actions = state.apply(Change::Modified(PathBuf::from("/b/file")), now);
+        assert_eq!(actions, vec![Action::Update(id("ref/hashed"))]);
+    }
+
+    #[test]
+    fn test_unmatched_rename_expires() {
+        let mut state = state();
+        let now       = Instant::now();
+
+        state.apply(Change::MovedFrom(7, PathBuf::from("/a/file")), now);
+        let gone = state.expire(now + Duration::from_secs(2));
+        assert_eq!(gone.len(), 1);
+        assert_eq!(gone[0].0, PathBuf::from("/a/file"));
+        assert_eq!(gone[0].1.len(), 2);
+
+        let actions = state.apply(Change::MovedTo(7, PathBuf::from("/b/file")), now);
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn test_rename_of_unwatched_file_is_ignored() {
+        let mut state = state();
+        let now       = Instant::now();
+
+        state.apply(Change::MovedFrom(7, PathBuf::from("/a/unknown")), now);
+        let actions = state.apply(Change::MovedTo(7, PathBuf::from("/b/unknown")), now);
+        assert!(actions.is_empty());
+        assert!(state.expire(now + Duration::from_secs(10)).is_empty());
+    }
+}
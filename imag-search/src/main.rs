@@ -0,0 +1,98 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+extern crate clap;
+#[macro_use] extern crate log;
+extern crate semver;
+#[macro_use] extern crate version;
+
+extern crate libimagrt;
+extern crate libimagsearch;
+extern crate libimagerror;
+
+use std::process::exit;
+use std::str::FromStr;
+
+use libimagrt::setup::generate_runtime_setup;
+use libimagsearch::index::SearchIndex;
+use libimagsearch::query::Query;
+use libimagerror::trace::MapErrTrace;
+
+mod ui;
+
+use ui::build_ui;
+
+fn main() {
+    let rt = generate_runtime_setup("imag-search",
+                                    &version!()[..],
+                                    "Full-text search over the entries of the store",
+                                    build_ui);
+
+    let query = rt.cli()
+        .values_of("query")
+        .unwrap() // enforced by clap
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let mut query = Query::parse(&query).map_err_trace_exit(1).unwrap(); // safe by above call
+    if let Some(module) = rt.cli().value_of("module") {
+        query = query.with_module(module);
+    }
+
+    let limit = match rt.cli().value_of("limit").map(usize::from_str) {
+        None         => None,
+        Some(Ok(n))  => Some(n),
+        Some(Err(e)) => {
+            error!("Not a number: {}", e);
+            exit(1);
+        },
+    };
+
+    let with_header = rt.cli().is_present("header");
+    let print_score = rt.cli().is_present("score");
+
+    let index = SearchIndex::build(rt.store(), query.module().map(|m| &m[..]), with_header)
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    let results = index.search(&query);
+    debug!("Found {} results", results.len());
+
+    for result in results.into_iter().take(limit.unwrap_or(::std::usize::MAX)) {
+        if print_score {
+            println!("{:.4} {}", result.score(), result.id());
+        } else {
+            println!("{}", result.id());
+        }
+    }
+}
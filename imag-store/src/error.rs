@@ -20,7 +20,10 @@
 generate_error_module!(
     generate_error_types!(StoreError, StoreErrorKind,
         BackendError      => "Backend Error",
-        NoCommandlineCall => "No commandline call"
+        NoCommandlineCall => "No commandline call",
+        ArchiveReadError  => "Error reading archive",
+        ArchiveWriteError => "Error writing archive",
+        ArchiveFormatError => "Malformed archive line"
     );
 );
 
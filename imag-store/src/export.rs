@@ -0,0 +1,90 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::io::stdout;
+use std::result::Result as RResult;
+
+use serde_json;
+use serde_json::Value as JsonValue;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::store::Entry;
+use libimagerror::trace::MapErrTrace;
+use libimagutil::warn_exit::warn_exit;
+
+use error::StoreError;
+use error::StoreErrorKind;
+
+type Result<T> = RResult<T, StoreError>;
+
+/// Export the store (or one module of it) into an archive
+///
+/// The archive is a JSON-lines file, where each line is an object with the keys `id` and `entry`.
+/// The entry is written in the format of the store files, so the header values keep their TOML
+/// types, which JSON cannot represent (datetimes, for example).
+pub fn export(rt: &Runtime) {
+    let scmd = rt.cli()
+        .subcommand_matches("export")
+        .unwrap_or_else(|| warn_exit("No subcommand 'export'. Will exit now", 1));
+
+    let entries = rt.store()
+        .snapshot(scmd.value_of("module"))
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    let result = match scmd.value_of("output") {
+        None | Some("-") => write_archive(&entries, &mut stdout()),
+        Some(path) => {
+            debug!("Writing archive to {:?}", path);
+            File::create(path)
+                .map_err(|e| StoreError::new(StoreErrorKind::ArchiveWriteError, Some(Box::new(e))))
+                .and_then(|mut file| write_archive(&entries, &mut file))
+        },
+    };
+
+    let n = result.map_err_trace_exit(1).unwrap(); // safe by above call
+    info!("Exported {} entries", n);
+}
+
+fn write_archive(entries: &[Entry], out: &mut Write) -> Result<usize> {
+    for entry in entries {
+        let line = try!(entry_to_json(entry));
+        try!(writeln!(out, "{}", line)
+             .map_err(|e| StoreError::new(StoreErrorKind::ArchiveWriteError, Some(Box::new(e)))));
+    }
+    Ok(entries.len())
+}
+
+fn entry_to_json(entry: &Entry) -> Result<String> {
+    let id = match entry.get_location().local().to_str() {
+        Some(id) => String::from(id),
+        None     => return Err(StoreError::new(StoreErrorKind::ArchiveWriteError, None)),
+    };
+    debug!("Exporting {:?}", id);
+
+    let mut map = BTreeMap::new();
+    map.insert(String::from("id"), JsonValue::String(id));
+    map.insert(String::from("entry"), JsonValue::String(entry.to_str()));
+
+    serde_json::to_string(&JsonValue::Object(map))
+        .map_err(|e| StoreError::new(StoreErrorKind::ArchiveWriteError, Some(Box::new(e))))
+}
@@ -19,18 +19,24 @@
 
 use std::path::PathBuf;
 
+use clap::ArgMatches;
+use filters::filter::Filter;
+
 use libimagrt::runtime::Runtime;
-use libimagerror::trace::{trace_error, trace_error_exit};
+use libimagerror::trace::{MapErrTrace, trace_error, trace_error_exit};
+use libimagentryfilter::builtin::header::find_in_store;
+use libimagstore::store::Entry;
 use libimagstore::storeid::StoreId;
+use libimagutil::warn_exit::warn_exit;
 
-use retrieve::print_entry;
+use retrieve::{build_header_filters, print_entry};
 
 pub fn get(rt: &Runtime) {
     rt.cli()
         .subcommand_matches("get")
         .map(|scmd| {
-            scmd.value_of("id")
-                .map(|id| {
+            match scmd.value_of("id") {
+                Some(id) => {
                     let path = PathBuf::from(id);
                     let path = match StoreId::new(Some(rt.store().path().clone()), path) {
                         Err(e) => trace_error_exit(&e, 1),
@@ -39,11 +45,32 @@ pub fn get(rt: &Runtime) {
                     debug!("path = {:?}", path);
 
                     match rt.store().get(path) {
-                        Ok(Some(entry)) => print_entry(rt, scmd, entry),
+                        Ok(Some(entry)) => print_entry(scmd, entry),
                         Ok(None)        => info!("No entry found"),
                         Err(e)          => trace_error(&e),
                     }
-                })
+                },
+                None => match scmd.subcommand_matches("filter-header") {
+                    Some(filter_scmd) => get_filtered(rt, scmd, filter_scmd),
+                    None              => warn_exit("Either --id or filter-header is required", 1),
+                },
+            }
         });
 }
 
+/// Print all entries which match the header filters
+///
+/// If the header index is enabled, only the matching entries are read.
+fn get_filtered(rt: &Runtime, scmd: &ArgMatches, filter_scmd: &ArgMatches) {
+    let filters = build_header_filters(filter_scmd);
+    let all     = |entry: &Entry| filters.iter().all(|f| f.filter(entry));
+
+    let ids = find_in_store(rt.store(), None, &all).map_err_trace_exit(1).unwrap();
+    for id in ids {
+        match rt.store().get(id) {
+            Ok(Some(entry)) => print_entry(scmd, entry),
+            Ok(None)        => debug!("Entry vanished"),
+            Err(e)          => trace_error(&e),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+use libimagstorestdhook::vcs::git::history::{history as entry_history, entry_at_revision};
+use libimagstorestdhook::vcs::git::history::{diff_headers, diff_content};
+use libimagstorestdhook::vcs::git::history::{HeaderChange, LineChange};
+use libimagerror::trace::MapErrTrace;
+use libimagutil::warn_exit::warn_exit;
+
+/// List the revisions which touched an entry or show the changes of the entry since a revision
+pub fn history(rt: &Runtime) {
+    let scmd = rt.cli()
+        .subcommand_matches("history")
+        .unwrap_or_else(|| warn_exit("No subcommand 'history'. Will exit now", 1));
+
+    let id = scmd.value_of("id").unwrap(); // enforced by clap
+    let id = StoreId::new_baseless(PathBuf::from(id))
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    match scmd.value_of("diff") {
+        None => {
+            let revisions = entry_history(rt.store().path(), &id)
+                .map_err_trace_exit(1)
+                .unwrap(); // safe by above call
+
+            for revision in revisions {
+                let time = NaiveDateTime::from_timestamp(revision.time(), 0);
+                println!("{} {} {} {}",
+                         &revision.id()[..8],
+                         time.format("%Y-%m-%d %H:%M:%S"),
+                         revision.author(),
+                         revision.summary());
+            }
+        },
+
+        Some(rev) => {
+            let old = entry_at_revision(rt.store().path(), &id, rev)
+                .map_err_trace_exit(1)
+                .unwrap(); // safe by above call
+
+            let new = match scmd.value_of("to") {
+                Some(to) => entry_at_revision(rt.store().path(), &id, to)
+                    .map_err_trace_exit(1)
+                    .unwrap(), // safe by above call
+                None => rt.store()
+                    .retrieve_copy(id.clone())
+                    .map_err_trace_exit(1)
+                    .unwrap(), // safe by above call
+            };
+
+            print_diff(&old, &new);
+        },
+    }
+}
+
+fn print_diff(old: &Entry, new: &Entry) {
+    for change in diff_headers(old, new) {
+        match change {
+            HeaderChange::Added(key, value)        => println!("+ {} = {}", key, value),
+            HeaderChange::Removed(key, value)      => println!("- {} = {}", key, value),
+            HeaderChange::Changed(key, old, new)   => println!("~ {} = {} -> {}", key, old, new),
+        }
+    }
+
+    println!("---");
+
+    for line in diff_content(old, new) {
+        match line {
+            LineChange::Unchanged(line) => println!(" {}", line),
+            LineChange::Added(line)     => println!("+{}", line),
+            LineChange::Removed(line)   => println!("-{}", line),
+        }
+    }
+}
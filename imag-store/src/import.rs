@@ -0,0 +1,100 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::stdin;
+use std::path::PathBuf;
+use std::result::Result as RResult;
+
+use serde_json;
+use serde_json::Value as JsonValue;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+use libimagerror::trace::MapErrTrace;
+use libimagutil::warn_exit::warn_exit;
+
+use error::StoreError;
+use error::StoreErrorKind;
+
+type Result<T> = RResult<T, StoreError>;
+
+/// Import an archive which was written by `imag-store export`
+///
+/// Each entry is created through the store, so hooks are executed for every imported entry.
+pub fn import(rt: &Runtime) {
+    let scmd = rt.cli()
+        .subcommand_matches("import")
+        .unwrap_or_else(|| warn_exit("No subcommand 'import'. Will exit now", 1));
+
+    let result = match scmd.value_of("input") {
+        None | Some("-") => {
+            let stdin = stdin();
+            let lock  = stdin.lock();
+            read_archive(rt, lock)
+        },
+        Some(path) => {
+            debug!("Reading archive from {:?}", path);
+            File::open(path)
+                .map_err(|e| StoreError::new(StoreErrorKind::ArchiveReadError, Some(Box::new(e))))
+                .and_then(|file| read_archive(rt, BufReader::new(file)))
+        },
+    };
+
+    let n = result.map_err_trace_exit(1).unwrap(); // safe by above call
+    info!("Imported {} entries", n);
+}
+
+fn read_archive<R: BufRead>(rt: &Runtime, input: R) -> Result<usize> {
+    let mut n = 0;
+    for line in input.lines() {
+        let line = try!(line
+            .map_err(|e| StoreError::new(StoreErrorKind::ArchiveReadError, Some(Box::new(e)))));
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = try!(entry_from_json(&line));
+        debug!("Importing {:?}", entry.get_location());
+        try!(rt.store()
+             .import_entry(entry)
+             .map_err(|e| StoreError::new(StoreErrorKind::BackendError, Some(Box::new(e)))));
+        n += 1;
+    }
+    Ok(n)
+}
+
+fn entry_from_json(line: &str) -> Result<Entry> {
+    let json : JsonValue = try!(serde_json::from_str(line)
+        .map_err(|e| StoreError::new(StoreErrorKind::ArchiveFormatError, Some(Box::new(e)))));
+
+    let format_error = || StoreError::new(StoreErrorKind::ArchiveFormatError, None);
+
+    let id = try!(json.find("id").and_then(|id| id.as_str()).ok_or_else(&format_error));
+    let id = try!(StoreId::new_baseless(PathBuf::from(id))
+        .map_err(|e| StoreError::new(StoreErrorKind::ArchiveFormatError, Some(Box::new(e)))));
+
+    let entry = try!(json.find("entry").and_then(|e| e.as_str()).ok_or_else(&format_error));
+    Entry::from_str(id, entry)
+        .map_err(|e| StoreError::new(StoreErrorKind::ArchiveFormatError, Some(Box::new(e))))
+}
@@ -0,0 +1,43 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use libimagrt::runtime::Runtime;
+use libimagerror::trace::MapErrTrace;
+use libimagutil::warn_exit::warn_exit;
+
+pub fn index(rt: &Runtime) {
+    rt.cli()
+        .subcommand_matches("index")
+        .map(|sub| {
+            match sub.subcommand_name() {
+                Some("rebuild") => rebuild(rt),
+                _ => warn_exit("No subcommand for 'index'. Will exit now", 1),
+            }
+        })
+        .unwrap_or_else(|| warn_exit("No subcommand 'index'. Will exit now", 1));
+}
+
+fn rebuild(rt: &Runtime) {
+    let n = rt.store()
+        .rebuild_header_index()
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    info!("Indexed {} entries", n);
+}
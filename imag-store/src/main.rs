@@ -32,14 +32,21 @@
     while_true,
 )]
 
+extern crate chrono;
 extern crate clap;
 #[macro_use] extern crate log;
+extern crate filters;
+extern crate regex;
 extern crate semver;
+extern crate serde_json;
 extern crate toml;
 #[macro_use] extern crate version;
 
+extern crate libimagentryfilter;
+extern crate libimagref;
 extern crate libimagrt;
 extern crate libimagstore;
+extern crate libimagstorestdhook;
 extern crate libimagutil;
 #[macro_use] extern crate libimagerror;
 
@@ -48,7 +55,13 @@ use libimagrt::setup::generate_runtime_setup;
 mod create;
 mod delete;
 mod error;
+mod export;
 mod get;
+mod history;
+mod import;
+mod index;
+mod migrate;
+mod restore;
 mod retrieve;
 mod ui;
 mod update;
@@ -57,7 +70,13 @@ mod util;
 
 use create::create;
 use delete::delete;
+use export::export;
 use get::get;
+use history::history;
+use import::import;
+use index::index;
+use migrate::migrate;
+use restore::restore;
 use retrieve::retrieve;
 use ui::build_ui;
 use update::update;
@@ -81,7 +100,13 @@ fn main() {
                 match name {
                     "create"   => create(&rt),
                     "delete"   => delete(&rt),
+                    "export"   => export(&rt),
                     "get"      => get(&rt),
+                    "history"  => history(&rt),
+                    "import"   => import(&rt),
+                    "index"    => index(&rt),
+                    "migrate"  => migrate(&rt),
+                    "restore"  => restore(&rt),
                     "retrieve" => retrieve(&rt),
                     "update"   => update(&rt),
                     "verify"   => verify(&rt),
@@ -0,0 +1,67 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use toml::Value;
+
+use libimagref::hashers::NamedHasher;
+use libimagref::migration::RehashMigration;
+use libimagrt::runtime::Runtime;
+use libimagerror::trace::MapErrTrace;
+use libimagutil::warn_exit::warn_exit;
+
+pub fn migrate(rt: &Runtime) {
+    let dry_run = rt.cli()
+        .subcommand_matches("migrate")
+        .map(|sub| sub.is_present("dry-run"))
+        .unwrap_or_else(|| warn_exit("No subcommand 'migrate'. Will exit now", 1));
+
+    let _ = rt.store()
+        .register_migration(Box::new(RehashMigration::new(get_ref_hasher(rt))))
+        .map_err_trace_exit(1);
+
+    let reports = rt.store()
+        .migrate(dry_run)
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    for report in reports.iter() {
+        println!("{}", report);
+    }
+
+    if dry_run {
+        info!("{} entries would be migrated", reports.len());
+    } else {
+        info!("{} entries migrated", reports.len());
+    }
+}
+
+/// Get the hasher refs are re-hashed with, from `ref.hasher` in the configuration
+fn get_ref_hasher(rt: &Runtime) -> NamedHasher {
+    let name = rt.config()
+        .and_then(|config| match config.config().lookup("ref.hasher") {
+            Some(&Value::String(ref s)) => Some(s.clone()),
+            _ => None,
+        });
+
+    match name {
+        Some(name) => NamedHasher::from_name(&name)
+            .unwrap_or_else(|| warn_exit(&format!("Unknown hasher in 'ref.hasher': {}", name), 1)),
+        None => NamedHasher::Default,
+    }
+}
@@ -0,0 +1,59 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::path::PathBuf;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::storeid::StoreId;
+use libimagstorestdhook::vcs::git::history::entry_at_revision;
+use libimagerror::trace::MapErrTrace;
+use libimagutil::warn_exit::warn_exit;
+
+/// Restore an entry as it was at a revision of the git history of the store
+///
+/// The entry is written through the store, so the hooks are executed as for every other update.
+pub fn restore(rt: &Runtime) {
+    let scmd = rt.cli()
+        .subcommand_matches("restore")
+        .unwrap_or_else(|| warn_exit("No subcommand 'restore'. Will exit now", 1));
+
+    let id  = scmd.value_of("id").unwrap(); // enforced by clap
+    let rev = scmd.value_of("rev").unwrap(); // enforced by clap
+    let id  = StoreId::new_baseless(PathBuf::from(id))
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    let old = entry_at_revision(rt.store().path(), &id, rev)
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    let mut entry = rt.store()
+        .retrieve(id)
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    *entry.get_header_mut()  = old.get_header().clone();
+    *entry.get_content_mut() = old.get_content().clone();
+
+    rt.store()
+        .update(entry)
+        .map_err_trace_exit(1)
+        .unwrap(); // safe by above call
+
+    info!("Restored entry from revision {}", rev);
+}
@@ -17,16 +17,28 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::borrow::Cow;
 use std::path::PathBuf;
+use std::process::exit;
 
 use clap::ArgMatches;
+use filters::filter::Filter;
+use regex::Regex;
+use serde_json::to_string as to_json_string;
 use toml::Value;
 
+use libimagentryfilter::builtin::header::field_eq::FieldEq;
+use libimagentryfilter::builtin::header::field_grep::FieldGrep;
+use libimagstore::store::Entry;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
 use libimagrt::runtime::Runtime;
 use libimagerror::trace::MapErrTrace;
 use libimagutil::debug_result::*;
+use libimagutil::key_value_split::IntoKeyValue;
+
+use util::header_to_json;
+use util::parse_value;
 
 pub fn retrieve(rt: &Runtime) {
     rt.cli()
@@ -42,7 +54,7 @@ pub fn retrieve(rt: &Runtime) {
                     rt.store()
                         // "id" must be present, enforced via clap spec
                         .retrieve(path)
-                        .map(|e| print_entry(rt, scmd, e))
+                        .map(|e| print_entry(scmd, e))
                         .map_dbg_str("No entry")
                         .map_dbg(|e| format!("{:?}", e))
                         .map_err_trace()
@@ -50,29 +62,34 @@ pub fn retrieve(rt: &Runtime) {
         });
 }
 
-pub fn print_entry(rt: &Runtime, scmd: &ArgMatches, e: FileLockEntry) {
+pub fn print_entry(scmd: &ArgMatches, e: FileLockEntry) {
+    if let Some(filter_scmd) = scmd.subcommand_matches("filter-header") {
+        debug!("Filtering...");
+        let filters = build_header_filters(filter_scmd);
+        if !filters.iter().all(|f| f.filter(&e)) {
+            info!("Entry does not match the filter");
+            return;
+        }
+    }
+
     if do_print_raw(scmd) {
         debug!("Printing raw content...");
         println!("{}", e.to_str());
-    } else if do_filter(scmd) {
-        debug!("Filtering...");
-        warn!("Filtering via header specs is currently now supported.");
-        warn!("Will fail now!");
-        unimplemented!()
     } else {
         debug!("Printing structured...");
-        if do_print_header(scmd) {
-            debug!("Printing header...");
-            if do_print_header_as_json(rt.cli()) {
-                debug!("Printing header as json...");
-                warn!("Printing as JSON currently not supported.");
-                warn!("Will fail now!");
-                unimplemented!()
-            } else {
-                debug!("Printing header as TOML...");
-                // We have to Value::Table() for Display
-                println!("{}", Value::Table(e.get_header().clone().into()))
+        if do_print_header_as_json(scmd) {
+            debug!("Printing header as json...");
+            match to_json_string(&header_to_json(e.get_header())) {
+                Ok(s)  => println!("{}", s),
+                Err(e) => {
+                    error!("Could not convert header to JSON: {:?}", e);
+                    exit(1);
+                },
             }
+        } else if do_print_header(scmd) {
+            debug!("Printing header as TOML...");
+            // We have to Value::Table() for Display
+            println!("{}", Value::Table(e.get_header().clone().into()))
         }
 
         if do_print_content(scmd) {
@@ -83,6 +100,43 @@ pub fn print_entry(rt: &Runtime, scmd: &ArgMatches, e: FileLockEntry) {
     }
 }
 
+/// Build the header filters from the arguments of the "filter-header" subcommand
+///
+/// Exits if the arguments cannot be parsed.
+pub fn build_header_filters(m: &ArgMatches) -> Vec<Box<Filter<Entry>>> {
+    let mut filters : Vec<Box<Filter<Entry>>> = vec![];
+
+    if let Some(spec) = m.value_of("header-field-where") {
+        let (path, value) = split_filter_spec(spec);
+        debug!("Filtering where {} = {}", path, value);
+        filters.push(Box::new(FieldEq::new(path, parse_value(Cow::Owned(value)))));
+    }
+
+    if let Some(spec) = m.value_of("header-field-grep") {
+        let (path, regex) = split_filter_spec(spec);
+        debug!("Filtering where {} matches {}", path, regex);
+        match Regex::new(&regex[..]) {
+            Ok(regex) => filters.push(Box::new(FieldGrep::new(path, regex))),
+            Err(e)    => {
+                error!("Invalid regex '{}': {}", regex, e);
+                exit(1);
+            },
+        }
+    }
+
+    filters
+}
+
+fn split_filter_spec(spec: &str) -> (String, String) {
+    match String::from(spec).into_kv() {
+        Some(kv) => kv.into(),
+        None     => {
+            error!("Filter must be of the form 'header.field=value', got '{}'", spec);
+            exit(1);
+        },
+    }
+}
+
 fn do_print_header(m: &ArgMatches) -> bool {
     m.is_present("header")
 }
@@ -127,8 +127,8 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .long("id")
                         .short("i")
                         .takes_value(true)
-                        .required(true)
-                        .help("Retrieve by Store Path, where root (/) is the store itself")
+                        .required(false)
+                        .help("Retrieve by Store Path, where root (/) is the store itself. Without it, all entries matching 'filter-header' are printed")
                         .value_name("PATH"))
                    .arg(Arg::with_name("content")
                         .long("content")
@@ -205,4 +205,101 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                    .about("Verify the store")
                    .version("0.1")
                    )
+
+       .subcommand(SubCommand::with_name("migrate")
+                   .about("Migrate entries which were written by an older version of imag")
+                   .version("0.1")
+                   .arg(Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .short("n")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Only print which entries would be migrated, do not write anything"))
+                   )
+
+       .subcommand(SubCommand::with_name("index")
+                   .about("Work with the header index of the store")
+                   .version("0.1")
+                   .subcommand(SubCommand::with_name("rebuild")
+                               .about("Rebuild the header index from the entries in the store")
+                               .version("0.1")
+                               )
+                   )
+
+       .subcommand(SubCommand::with_name("history")
+                   .about("List the revisions of an entry in the git history of the store")
+                   .version("0.1")
+                   .arg(Arg::with_name("id")
+                        .long("id")
+                        .short("i")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Show the history of the Store Entry with this path")
+                        .value_name("PATH"))
+                   .arg(Arg::with_name("diff")
+                        .long("diff")
+                        .short("d")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Show the changes of header and content since this revision")
+                        .value_name("REV"))
+                   .arg(Arg::with_name("to")
+                        .long("to")
+                        .short("t")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("diff")
+                        .help("Compare with this revision instead of the current entry")
+                        .value_name("REV"))
+                   )
+
+       .subcommand(SubCommand::with_name("restore")
+                   .about("Restore an entry as it was at a revision of the git history of the store")
+                   .version("0.1")
+                   .arg(Arg::with_name("id")
+                        .long("id")
+                        .short("i")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Restore the Store Entry with this path")
+                        .value_name("PATH"))
+                   .arg(Arg::with_name("rev")
+                        .long("rev")
+                        .short("r")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The revision to restore")
+                        .value_name("REV"))
+                   )
+
+       .subcommand(SubCommand::with_name("export")
+                   .about("Export the entries of the store into an archive (JSON lines)")
+                   .version("0.1")
+                   .arg(Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Write the archive to this file ('-' for stdout, default)")
+                        .value_name("FILE"))
+                   .arg(Arg::with_name("module")
+                        .long("module")
+                        .short("m")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Only export the entries of this module")
+                        .value_name("MODULE"))
+                   )
+
+       .subcommand(SubCommand::with_name("import")
+                   .about("Import the entries from an archive written by 'export'")
+                   .version("0.1")
+                   .arg(Arg::with_name("input")
+                        .long("input")
+                        .short("i")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Read the archive from this file ('-' for stdin, default)")
+                        .value_name("FILE"))
+                   )
 }
@@ -22,6 +22,7 @@ use std::collections::btree_map::{BTreeMap, Entry};
 use std::str::Split;
 
 use clap::ArgMatches;
+use serde_json::Value as JsonValue;
 use toml::Value;
 
 use libimagstore::store::EntryHeader;
@@ -86,7 +87,29 @@ fn insert_key_into<'a>(current: String,
     }
 }
 
-fn parse_value(value: Cow<str>) -> Value {
+/// Convert a TOML value to JSON
+///
+/// TOML datetimes are converted to strings, as JSON does not have a datetime type.
+pub fn toml_to_json(v: &Value) -> JsonValue {
+    match *v {
+        Value::String(ref s)   => JsonValue::String(s.clone()),
+        Value::Integer(i)      => JsonValue::I64(i),
+        Value::Float(f)        => JsonValue::F64(f),
+        Value::Boolean(b)      => JsonValue::Bool(b),
+        Value::Datetime(ref s) => JsonValue::String(s.clone()),
+        Value::Array(ref a)    => JsonValue::Array(a.iter().map(toml_to_json).collect()),
+        Value::Table(ref t)    => JsonValue::Object(t.iter()
+                                                     .map(|(k, v)| (k.clone(), toml_to_json(v)))
+                                                     .collect()),
+    }
+}
+
+/// Convert an entry header to JSON
+pub fn header_to_json(header: &EntryHeader) -> JsonValue {
+    toml_to_json(header.header())
+}
+
+pub fn parse_value(value: Cow<str>) -> Value {
     use std::str::FromStr;
 
     fn is_ary(v: &str) -> bool {
@@ -30,6 +30,7 @@ use libimagstore::storeid::StoreIdIterator;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
 use libimagstore::storeid::IntoStoreId;
+use libimagstore::schema::{FieldType, Schema};
 use libimagerror::into::IntoError;
 
 use module_path::ModuleEntryPath;
@@ -40,6 +41,14 @@ use error::error::MapErrInto;
 
 pub type CounterName = String;
 
+/// The header schema of counter entries, to be registered with `Store::register_schema()`
+pub fn header_schema() -> Schema {
+    Schema::new("counter")
+        .required("counter.name", FieldType::String)
+        .required("counter.value", FieldType::Integer)
+        .optional("counter.unit", FieldType::String)
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub struct CounterUnit(String);
 
@@ -36,15 +36,15 @@ impl Predicate for EqGt {
         match self.comp {
             Value::Integer(i) => {
                 match v {
-                    Value::Integer(j) => i > j,
-                    Value::Float(f) => (i as f64) > f,
+                    Value::Integer(j) => j > i,
+                    Value::Float(f) => f > (i as f64),
                     _ => false,
                 }
             },
             Value::Float(f) => {
                 match v {
-                    Value::Integer(i) => f > (i as f64),
-                    Value::Float(d) => f > d,
+                    Value::Integer(i) => (i as f64) > f,
+                    Value::Float(d) => d > f,
                     _ => false,
                 }
             },
@@ -54,7 +54,7 @@ impl Predicate for EqGt {
 
 }
 
-/// Check whether certain header field in a entry is equal to a value
+/// Check whether certain header field in a entry is greater than a value
 pub struct FieldGt {
     filter: FieldPredicate<EqGt>,
 }
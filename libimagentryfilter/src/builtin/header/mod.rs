@@ -27,3 +27,22 @@ pub mod field_lt;
 pub mod field_path;
 pub mod field_predicate;
 pub mod version;
+
+use filters::filter::Filter;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Result as StoreResult;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+/// Get the ids of the entries in `store` which match the header filter `filter`, optionally only
+/// the ones of the module `mod_name`
+///
+/// If the header index of the store is enabled, the filter is run on the indexed headers and the
+/// entries are not read.
+pub fn find_in_store<F>(store: &Store, mod_name: Option<&str>, filter: &F)
+    -> StoreResult<Vec<StoreId>>
+    where F: Filter<Entry> + ?Sized
+{
+    store.find_by_header(mod_name, |entry| filter.filter(entry))
+}
@@ -17,3 +17,40 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+
+//! Commandline helpers for filtering entries
+//!
+//! Binaries can add the `--filter` argument to their listing commands and build the filter from
+//! the query passed by the user:
+//!
+//! ```ignore
+//!  SubCommand::with_name("list").arg(filter_argument())
+//!
+//!  let filter = try!(filter_from_cli(matches));
+//!  entries.filter(|e| filter.as_ref().map(|f| f.filter(e)).unwrap_or(true))
+//! ```
+
+use clap::{Arg, ArgMatches};
+
+use query::Query;
+use result::Result;
+
+/// The `--filter` argument, which takes a query (see `libimagentryfilter::query`)
+pub fn filter_argument<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("filter")
+        .long("filter")
+        .takes_value(true)
+        .required(false)
+        .help("Only use entries matching this filter, for example 'header.counter.value > 10 and tag:work'")
+        .value_name("QUERY")
+}
+
+/// Build the filter from the `--filter` argument in `matches`
+///
+/// Returns `None` if the argument was not passed.
+pub fn filter_from_cli(matches: &ArgMatches) -> Result<Option<Query>> {
+    match matches.value_of("filter") {
+        Some(q) => Query::parse(q).map(Some),
+        None    => Ok(None),
+    }
+}
@@ -0,0 +1,167 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Splitting a filter query into tokens
+
+use libimagerror::into::IntoError;
+
+use error::FilterErrorKind as FEK;
+use result::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Neq,
+    Gt,
+    Lt,
+    Matches,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// A bare word, for example `header.counter.value`, `tag:work`, `and` or `10`
+    Word(String),
+
+    /// A "quoted string"
+    Str(String),
+
+    /// A /regex/
+    Regex(String),
+
+    Op(Op),
+    LParen,
+    RParen,
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"()\"=!<>~".contains(c)
+}
+
+/// Split `s` into tokens
+pub fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars : Vec<char> = s.chars().collect();
+    let mut tokens        = vec![];
+    let mut i             = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '(' => { tokens.push(Token::LParen); i += 1; },
+            ')' => { tokens.push(Token::RParen); i += 1; },
+            '~' => { tokens.push(Token::Op(Op::Matches)); i += 1; },
+            '>' => { tokens.push(Token::Op(Op::Gt)); i += 1; },
+            '<' => { tokens.push(Token::Op(Op::Lt)); i += 1; },
+            '=' => {
+                // both '=' and '==' are equality
+                i += if chars.get(i + 1) == Some(&'=') { 2 } else { 1 };
+                tokens.push(Token::Op(Op::Eq));
+            },
+            '!' => {
+                if chars.get(i + 1) != Some(&'=') {
+                    debug!("Expected '=' after '!' at position {}", i);
+                    return Err(FEK::UnexpectedToken.into_error());
+                }
+                tokens.push(Token::Op(Op::Neq));
+                i += 2;
+            },
+            '"' | '/' => {
+                let (s, next) = try!(delimited(&chars, i));
+                tokens.push(if c == '"' { Token::Str(s) } else { Token::Regex(s) });
+                i = next;
+            },
+            _ => {
+                let start = i;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().cloned().collect()));
+            },
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read a string delimited by the character at `start`, where the delimiter can be escaped with a
+/// backslash
+///
+/// Returns the string and the position after the closing delimiter.
+fn delimited(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let delim   = chars[start];
+    let mut s   = String::new();
+    let mut i   = start + 1;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if chars.get(i + 1) == Some(&delim) => {
+                s.push(delim);
+                i += 2;
+            },
+            c if c == delim => return Ok((s, i + 1)),
+            c => {
+                s.push(c);
+                i += 1;
+            },
+        }
+    }
+
+    if delim == '"' {
+        Err(FEK::UnterminatedString.into_error())
+    } else {
+        Err(FEK::UnterminatedRegex.into_error())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tokenize, Op, Token};
+
+    fn word(s: &str) -> Token {
+        Token::Word(String::from(s))
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("header.a.b > 10 and (tag:work or not content ~ /fo\\/o/)").unwrap();
+        assert_eq!(tokens, vec![
+            word("header.a.b"), Token::Op(Op::Gt), word("10"), word("and"),
+            Token::LParen, word("tag:work"), word("or"), word("not"), word("content"),
+            Token::Op(Op::Matches), Token::Regex(String::from("fo/o")), Token::RParen,
+        ]);
+    }
+
+    #[test]
+    fn test_tokenize_strings() {
+        let tokens = tokenize("header.name != \"a \\\"b\\\"\"").unwrap();
+        assert_eq!(tokens, vec![
+            word("header.name"), Token::Op(Op::Neq), Token::Str(String::from("a \"b\"")),
+        ]);
+
+        assert!(tokenize("header.name == \"foo").is_err());
+        assert!(tokenize("content ~ /foo").is_err());
+        assert!(tokenize("header.a ! 1").is_err());
+    }
+
+}
@@ -0,0 +1,151 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A query language for building filters
+//!
+//! Queries are built from the builtin filters, combined with `and`, `or`, `not` and parentheses.
+//! `and` binds stronger than `or`.
+//!
+//! ```text
+//! header.counter.value > 10 and tag:work and not content ~ /foo/
+//! ```
+//!
+//! The following conditions are available:
+//!
+//! * `header.<path> == <value>`, `!=`, `>`, `<`: Compare a header field with a value. Values can be
+//!   integers, floats, `true`, `false`, or strings (quoted if they contain whitespace or operators)
+//! * `header.<path> ~ /<regex>/`: Match a header field against a regex
+//! * `header.<path> exists`: Check whether the header field exists
+//! * `tag:<tag>`: Check whether the entry has a tag
+//! * `content ~ /<regex>/`: Match the content against a regex
+//! * `content.length > <n>`, `<`: Compare the length of the content
+//! * `version == <semver>`, `>`, `<`: Compare the version of the entry
+
+use filters::filter::Filter;
+
+use libimagstore::store::Entry;
+
+use result::Result;
+
+pub mod lexer;
+pub mod parser;
+
+/// A filter built from a query
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Condition(Box<Filter<Entry>>),
+}
+
+impl Query {
+
+    /// Parse a query string
+    pub fn parse(s: &str) -> Result<Query> {
+        lexer::tokenize(s).and_then(|tokens| parser::parse(tokens))
+    }
+
+}
+
+impl Filter<Entry> for Query {
+
+    fn filter(&self, e: &Entry) -> bool {
+        match *self {
+            Query::And(ref a, ref b) => a.filter(e) && b.filter(e),
+            Query::Or(ref a, ref b)  => a.filter(e) || b.filter(e),
+            Query::Not(ref a)        => !a.filter(e),
+            Query::Condition(ref f)  => f.filter(e),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use filters::filter::Filter;
+    use toml::Value;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::Query;
+
+    fn entry() -> Entry {
+        let id        = StoreId::new_baseless(PathBuf::from("test/entry")).unwrap();
+        let mut entry = Entry::new(id);
+        {
+            let header = entry.get_header_mut();
+            header.insert("counter", Value::Table(BTreeMap::new())).unwrap();
+            header.insert("counter.value", Value::Integer(12)).unwrap();
+            header.insert("counter.name", Value::String(String::from("my counter"))).unwrap();
+            header.insert("imag.tags", Value::Array(vec![Value::String(String::from("work"))])).unwrap();
+        }
+        *entry.get_content_mut() = String::from("some foo content");
+        entry
+    }
+
+    fn matches(q: &str) -> bool {
+        Query::parse(q).unwrap().filter(&entry())
+    }
+
+    #[test]
+    fn test_header_conditions() {
+        assert!(matches("header.counter.value > 10"));
+        assert!(!matches("header.counter.value < 10"));
+        assert!(matches("header.counter.value == 12"));
+        assert!(matches("header.counter.value != 13"));
+        assert!(matches("header.counter.name = \"my counter\""));
+        assert!(matches("header.counter.name ~ /^my/"));
+        assert!(matches("header.counter.name exists"));
+        assert!(!matches("header.counter.unit exists"));
+    }
+
+    #[test]
+    fn test_other_conditions() {
+        assert!(matches("tag:work"));
+        assert!(!matches("tag:home"));
+        assert!(matches("content ~ /foo/"));
+        assert!(matches("content.length > 3"));
+        assert!(!matches("content.length < 3"));
+    }
+
+    #[test]
+    fn test_combinations() {
+        assert!(matches("header.counter.value > 10 and tag:work and not content ~ /bar/"));
+        assert!(!matches("header.counter.value > 10 and not content ~ /foo/"));
+        assert!(matches("tag:home or tag:work and content ~ /foo/"));
+        assert!(!matches("(tag:home or tag:work) and content ~ /bar/"));
+        assert!(matches("NOT (tag:home OR content ~ /bar/)"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("header.a >").is_err());
+        assert!(Query::parse("tag:work and").is_err());
+        assert!(Query::parse("(tag:work").is_err());
+        assert!(Query::parse("tag:work tag:home").is_err());
+        assert!(Query::parse("content ~ /(/").is_err());
+        assert!(Query::parse("something").is_err());
+    }
+
+}
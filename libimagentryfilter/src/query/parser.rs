@@ -0,0 +1,235 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Building a `Query` from tokens
+
+use std::iter::Peekable;
+use std::str::FromStr;
+use std::vec::IntoIter;
+
+use filters::filter::Filter;
+use regex::Regex;
+use semver::Version;
+use toml::Value;
+
+use libimagerror::into::IntoError;
+use libimagstore::store::Entry;
+
+use builtin::content::grep::ContentGrep;
+use builtin::content::length::is_over::ContentLengthIsOver;
+use builtin::content::length::is_under::ContentLengthIsUnder;
+use builtin::header::field_eq::FieldEq;
+use builtin::header::field_exists::FieldExists;
+use builtin::header::field_grep::FieldGrep;
+use builtin::header::field_gt::FieldGt;
+use builtin::header::field_lt::FieldLt;
+use builtin::header::version::eq::VersionEq;
+use builtin::header::version::gt::VersionGt;
+use builtin::header::version::lt::VersionLt;
+use error::FilterErrorKind as FEK;
+use error::MapErrInto;
+use query::Query;
+use query::lexer::{Op, Token};
+use result::Result;
+use tags::HasTag;
+
+type Tokens = Peekable<IntoIter<Token>>;
+
+/// Parse a complete query from `tokens`
+pub fn parse(tokens: Vec<Token>) -> Result<Query> {
+    let mut tokens = tokens.into_iter().peekable();
+    let query      = try!(parse_or(&mut tokens));
+
+    match tokens.next() {
+        None    => Ok(query),
+        Some(t) => {
+            debug!("Unexpected token after end of query: {:?}", t);
+            Err(FEK::UnexpectedToken.into_error()).map_err_into(FEK::QueryParseError)
+        },
+    }
+}
+
+fn is_keyword(t: Option<&Token>, kw: &str) -> bool {
+    match t {
+        Some(&Token::Word(ref w)) => w.to_lowercase() == kw,
+        _                         => false,
+    }
+}
+
+fn parse_or(tokens: &mut Tokens) -> Result<Query> {
+    let mut query = try!(parse_and(tokens));
+    while is_keyword(tokens.peek(), "or") {
+        let _ = tokens.next();
+        query = Query::Or(Box::new(query), Box::new(try!(parse_and(tokens))));
+    }
+    Ok(query)
+}
+
+fn parse_and(tokens: &mut Tokens) -> Result<Query> {
+    let mut query = try!(parse_unary(tokens));
+    while is_keyword(tokens.peek(), "and") {
+        let _ = tokens.next();
+        query = Query::And(Box::new(query), Box::new(try!(parse_unary(tokens))));
+    }
+    Ok(query)
+}
+
+fn parse_unary(tokens: &mut Tokens) -> Result<Query> {
+    if is_keyword(tokens.peek(), "not") {
+        let _ = tokens.next();
+        return parse_unary(tokens).map(|q| Query::Not(Box::new(q)));
+    }
+
+    match tokens.next() {
+        Some(Token::LParen) => {
+            let query = try!(parse_or(tokens));
+            match tokens.next() {
+                Some(Token::RParen) => Ok(query),
+                Some(t)             => unexpected(t),
+                None                => unexpected_end(),
+            }
+        },
+        Some(Token::Word(w)) => parse_condition(w, tokens),
+        Some(t)              => unexpected(t),
+        None                 => unexpected_end(),
+    }
+}
+
+fn parse_condition(word: String, tokens: &mut Tokens) -> Result<Query> {
+    if word.starts_with("header.") {
+        let path = String::from(&word["header.".len()..]);
+
+        if is_keyword(tokens.peek(), "exists") {
+            let _ = tokens.next();
+            return Ok(condition(FieldExists::new(path)));
+        }
+
+        match try!(next_op(tokens)) {
+            Op::Eq      => next_value(tokens).map(|v| condition(FieldEq::new(path, v))),
+            Op::Neq     => next_value(tokens)
+                .map(|v| Query::Not(Box::new(condition(FieldEq::new(path, v))))),
+            Op::Gt      => next_value(tokens).map(|v| condition(FieldGt::new(path, v))),
+            Op::Lt      => next_value(tokens).map(|v| condition(FieldLt::new(path, v))),
+            Op::Matches => next_regex(tokens).map(|r| condition(FieldGrep::new(path, r))),
+        }
+    } else if word.starts_with("tag:") {
+        Ok(condition(HasTag::new(String::from(&word["tag:".len()..]))))
+    } else if word == "content" {
+        match try!(next_op(tokens)) {
+            Op::Matches => next_regex(tokens)
+                .and_then(|r| ContentGrep::new(r).map_err_into(FEK::InvalidRegex))
+                .map(condition),
+            op => unexpected(Token::Op(op)),
+        }
+    } else if word == "content.length" {
+        match try!(next_op(tokens)) {
+            Op::Gt => next_number(tokens).map(|n| condition(ContentLengthIsOver::new(n))),
+            Op::Lt => next_number(tokens).map(|n| condition(ContentLengthIsUnder::new(n))),
+            op     => unexpected(Token::Op(op)),
+        }
+    } else if word == "version" {
+        match try!(next_op(tokens)) {
+            Op::Eq => next_version(tokens).map(|v| condition(VersionEq::new(v))),
+            Op::Gt => next_version(tokens).map(|v| condition(VersionGt::new(v))),
+            Op::Lt => next_version(tokens).map(|v| condition(VersionLt::new(v))),
+            op     => unexpected(Token::Op(op)),
+        }
+    } else {
+        unexpected(Token::Word(word))
+    }
+}
+
+fn condition<F: Filter<Entry> + 'static>(f: F) -> Query {
+    Query::Condition(Box::new(f))
+}
+
+fn next_op(tokens: &mut Tokens) -> Result<Op> {
+    match tokens.next() {
+        Some(Token::Op(op)) => Ok(op),
+        Some(t)             => unexpected(t),
+        None                => unexpected_end(),
+    }
+}
+
+/// Get the next token as header value
+fn next_value(tokens: &mut Tokens) -> Result<Value> {
+    match tokens.next() {
+        Some(Token::Str(s))  => Ok(Value::String(s)),
+        Some(Token::Word(w)) => Ok(parse_value(w)),
+        Some(t)              => unexpected(t),
+        None                 => unexpected_end(),
+    }
+}
+
+fn parse_value(w: String) -> Value {
+    if w == "true" {
+        Value::Boolean(true)
+    } else if w == "false" {
+        Value::Boolean(false)
+    } else if let Ok(i) = i64::from_str(&w[..]) {
+        Value::Integer(i)
+    } else if let Ok(f) = f64::from_str(&w[..]) {
+        Value::Float(f)
+    } else {
+        Value::String(w)
+    }
+}
+
+/// Get the next token as regex. Strings and words are accepted as well.
+fn next_regex(tokens: &mut Tokens) -> Result<Regex> {
+    let s = match tokens.next() {
+        Some(Token::Regex(s)) | Some(Token::Str(s)) | Some(Token::Word(s)) => s,
+        Some(t) => return unexpected(t),
+        None    => return unexpected_end(),
+    };
+
+    Regex::new(&s[..])
+        .map_err_into(FEK::InvalidRegex)
+        .map_err_into(FEK::QueryParseError)
+}
+
+fn next_number(tokens: &mut Tokens) -> Result<usize> {
+    match tokens.next() {
+        Some(Token::Word(w)) => usize::from_str(&w[..])
+            .map_err_into(FEK::InvalidNumber)
+            .map_err_into(FEK::QueryParseError),
+        Some(t) => unexpected(t),
+        None    => unexpected_end(),
+    }
+}
+
+fn next_version(tokens: &mut Tokens) -> Result<Version> {
+    match tokens.next() {
+        Some(Token::Word(w)) | Some(Token::Str(w)) => Version::parse(&w[..])
+            .map_err(|_| FEK::InvalidVersion.into_error())
+            .map_err_into(FEK::QueryParseError),
+        Some(t) => unexpected(t),
+        None    => unexpected_end(),
+    }
+}
+
+fn unexpected<T>(t: Token) -> Result<T> {
+    debug!("Unexpected token in query: {:?}", t);
+    Err(FEK::UnexpectedToken.into_error()).map_err_into(FEK::QueryParseError)
+}
+
+fn unexpected_end<T>() -> Result<T> {
+    debug!("Unexpected end of query");
+    Err(FEK::UnexpectedEnd.into_error()).map_err_into(FEK::QueryParseError)
+}
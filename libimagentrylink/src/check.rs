@@ -0,0 +1,288 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Checking the links in the store for consistency
+//!
+//! Internal links are bidirectional: if an entry A links to an entry B, B links back to A. This
+//! module finds the places in the store where this does not hold anymore (for example because an
+//! entry was deleted or edited by hand) and can repair them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Error as FmtError};
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use error::LinkErrorKind as LEK;
+use error::MapErrInto;
+use external::is_external_link_storeid;
+use internal::InternalLinker;
+use internal::Link;
+use internal::set_relation;
+use internal::iter::IntoValues;
+use result::Result;
+
+use toml::Value;
+
+/// A problem with the links in the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkProblem {
+    /// `from` links to `to`, but `to` does not link back to `from`
+    OneDirectional { from: StoreId, to: StoreId },
+
+    /// `from` links to `to`, but there is no entry `to` in the store
+    Dangling { from: StoreId, to: StoreId },
+
+    /// The `links/external` entry is not linked from any entry and does not link to any entry
+    /// which exists
+    OrphanedExternal(StoreId),
+}
+
+impl Display for LinkProblem {
+
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        match *self {
+            LinkProblem::OneDirectional { ref from, ref to } => {
+                write!(fmt, "'{}' links to '{}', but there is no link back",
+                       from.local().display(), to.local().display())
+            },
+            LinkProblem::Dangling { ref from, ref to } => {
+                write!(fmt, "'{}' links to '{}', which does not exist",
+                       from.local().display(), to.local().display())
+            },
+            LinkProblem::OrphanedExternal(ref id) => {
+                write!(fmt, "'{}' is an external link which is not used by any entry",
+                       id.local().display())
+            },
+        }
+    }
+
+}
+
+/// Check the links of all entries in the store
+pub fn check_links(store: &Store) -> Result<Vec<LinkProblem>> {
+    let mut links = BTreeMap::new();
+
+    for id in try!(store.entries().map_err_into(LEK::StoreReadError)) {
+        let entry   = try!(store.retrieve_copy(id.clone()).map_err_into(LEK::StoreReadError));
+        let targets = try!(entry.get_internal_links())
+            .map(|link| link.without_base())
+            .collect::<Vec<Link>>();
+        links.insert(id.without_base(), targets);
+    }
+
+    Ok(find_problems(&links))
+}
+
+/// Find the problems in a map from entries to the entries they link to
+fn find_problems(links: &BTreeMap<StoreId, Vec<Link>>) -> Vec<LinkProblem> {
+    let linked : BTreeSet<&StoreId> = links.values().flat_map(|targets| targets.iter()).collect();
+    let mut problems = vec![];
+
+    for (id, targets) in links.iter() {
+        // An external link which still links to an entry has only lost its back link, which is
+        // reported as one-directional link below
+        let orphaned = is_external_link_storeid(id) &&
+            !linked.contains(id) &&
+            !targets.iter().any(|target| links.contains_key(target));
+
+        if orphaned {
+            problems.push(LinkProblem::OrphanedExternal(id.clone()));
+            continue;
+        }
+
+        for target in targets.iter().filter(|target| *target != id) {
+            match links.get(target) {
+                None => problems.push(LinkProblem::Dangling {
+                    from: id.clone(),
+                    to: target.clone(),
+                }),
+                Some(back) => if !back.contains(id) {
+                    problems.push(LinkProblem::OneDirectional {
+                        from: id.clone(),
+                        to: target.clone(),
+                    })
+                },
+            }
+        }
+    }
+
+    problems
+}
+
+/// Repair a problem found by `check_links()`
+///
+/// * One-directional links are completed by adding the missing back link
+/// * Dangling links are removed
+/// * Orphaned `links/external` entries are deleted
+pub fn repair(store: &Store, problem: &LinkProblem) -> Result<()> {
+    match *problem {
+        LinkProblem::OneDirectional { ref from, ref to } => {
+            debug!("Adding link back from {:?} to {:?}", to, from);
+            let mut from = try!(store.retrieve(from.clone()).map_err_into(LEK::StoreReadError));
+            let mut to   = try!(store.retrieve(to.clone()).map_err_into(LEK::StoreReadError));
+
+            try!(from.add_internal_link(&mut to));
+
+            try!(store.update(to).map_err_into(LEK::StoreWriteError));
+            store.update(from).map_err_into(LEK::StoreWriteError)
+        },
+
+        LinkProblem::Dangling { ref from, ref to } => {
+            debug!("Removing link from {:?} to {:?}", from, to);
+            let mut entry = try!(store.retrieve(from.clone()).map_err_into(LEK::StoreReadError));
+
+            try!(remove_dangling_link(&mut entry, to));
+            store.update(entry).map_err_into(LEK::StoreWriteError)
+        },
+
+        LinkProblem::OrphanedExternal(ref id) => {
+            debug!("Deleting orphaned external link {:?}", id);
+            let mut entry = try!(store.retrieve(id.clone()).map_err_into(LEK::StoreReadError));
+
+            // remove the links first, so hooks which deny the deletion of linked entries do not
+            // fail
+            try!(set_links(&mut entry, vec![].into_iter()));
+            try!(store.update(entry).map_err_into(LEK::StoreWriteError));
+
+            store.delete(id.clone()).map_err_into(LEK::StoreWriteError)
+        },
+    }
+}
+
+/// Remove the link from `entry` to `to`, which does not exist
+///
+/// This repairs a `LinkProblem::Dangling` without the store, so it can be used where the store is
+/// not available, for example in hooks.
+pub fn remove_dangling_link(entry: &mut Entry, to: &StoreId) -> Result<()> {
+    let to    = to.clone().without_base();
+    let links = try!(entry.get_internal_links())
+        .filter(|link| link.clone().without_base() != to);
+
+    try!(set_links(entry, links));
+    set_relation(entry.get_header_mut(), &to, None)
+}
+
+fn set_links<I: Iterator<Item = Link>>(entry: &mut Entry, links: I) -> Result<()> {
+    let links = try!(links.into_values().collect::<Result<Vec<Value>>>());
+    entry.get_header_mut()
+        .set("imag.links", Value::Array(links))
+        .map(|_| ())
+        .map_err_into(LEK::EntryHeaderWriteError)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+
+    use internal::InternalLinker;
+    use super::{check_links, repair, LinkProblem};
+
+    fn get_store() -> Store {
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    fn create_with_links(store: &Store, name: &str, links: &[&str]) {
+        let mut entry = store.create(id(name)).unwrap();
+        let links = links.iter().map(|l| Value::String(String::from(*l))).collect();
+        entry.get_header_mut().set("imag.links", Value::Array(links)).unwrap();
+    }
+
+    fn check_and_repair(store: &Store, expected: LinkProblem) {
+        let problems = check_links(store).unwrap();
+        assert_eq!(problems, vec![expected]);
+
+        for problem in problems.iter() {
+            assert!(repair(store, problem).is_ok());
+        }
+
+        assert!(check_links(store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_consistent_links() {
+        let store = get_store();
+        {
+            let mut a = store.create(id("a")).unwrap();
+            let mut b = store.create(id("b")).unwrap();
+            assert!(a.add_internal_link(&mut b).is_ok());
+        }
+
+        assert!(check_links(&store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_one_directional_link() {
+        let store = get_store();
+        create_with_links(&store, "a", &["b"]);
+        create_with_links(&store, "b", &[]);
+
+        check_and_repair(&store, LinkProblem::OneDirectional { from: id("a"), to: id("b") });
+
+        let b = store.retrieve(id("b")).unwrap();
+        assert_eq!(b.get_internal_links().unwrap().collect::<Vec<_>>(), vec![id("a")]);
+    }
+
+    #[test]
+    fn test_dangling_link() {
+        let store = get_store();
+        create_with_links(&store, "a", &["b"]);
+
+        check_and_repair(&store, LinkProblem::Dangling { from: id("a"), to: id("b") });
+
+        let a = store.retrieve(id("a")).unwrap();
+        assert_eq!(a.get_internal_links().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_orphaned_external_link() {
+        let store = get_store();
+        create_with_links(&store, "links/external/abc", &["a"]);
+
+        check_and_repair(&store, LinkProblem::OrphanedExternal(id("links/external/abc")));
+
+        assert!(store.get(id("links/external/abc")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_external_link_without_back_link() {
+        let store = get_store();
+        create_with_links(&store, "a", &[]);
+        create_with_links(&store, "links/external/abc", &["a"]);
+
+        check_and_repair(&store, LinkProblem::OneDirectional {
+            from: id("links/external/abc"),
+            to: id("a"),
+        });
+
+        let a = store.retrieve(id("a")).unwrap();
+        assert_eq!(a.get_internal_links().unwrap().collect::<Vec<_>>(),
+                   vec![id("links/external/abc")]);
+    }
+
+}
@@ -22,6 +22,7 @@ generate_error_module!(
         EntryHeaderReadError    => "Error while reading an entry header",
         EntryHeaderWriteError   => "Error while writing an entry header",
         ExistingLinkTypeWrong   => "Existing link entry has wrong type",
+        ExistingRelationTypeWrong => "Existing link relation has wrong type",
         LinkTargetDoesNotExist  => "Link target does not exist in the store",
         InternalConversionError => "Error while converting values internally",
         InvalidUri              => "URI is not valid",
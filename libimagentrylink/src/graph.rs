@@ -0,0 +1,451 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! The graph of the internal links in the store
+//!
+//! `InternalLinker::get_internal_links()` only answers which entries an entry links to. The
+//! `LinkGraph` holds the links of many entries, so it can be walked over several hops, searched
+//! for paths and components and exported in the Graphviz DOT format or as GraphML.
+//!
+//! Internal links are bidirectional, so the graph is undirected. Nodes are identified by the ids
+//! of the entries, without the store path.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use error::LinkErrorKind as LEK;
+use error::MapErrInto;
+use internal::InternalLinker;
+use result::Result;
+
+/// An undirected graph of internal links
+#[derive(Debug, Clone, Default)]
+pub struct LinkGraph {
+    edges: BTreeMap<StoreId, BTreeSet<StoreId>>,
+    relations: BTreeMap<(StoreId, StoreId), String>,
+}
+
+impl LinkGraph {
+
+    pub fn new() -> LinkGraph {
+        LinkGraph::default()
+    }
+
+    /// Build the graph of all entries in the store
+    pub fn from_store(store: &Store) -> Result<LinkGraph> {
+        let mut graph = LinkGraph::new();
+
+        for id in try!(store.entries().map_err_into(LEK::StoreReadError)) {
+            let entry = try!(store.retrieve_copy(id.clone()).map_err_into(LEK::StoreReadError));
+            let id    = id.without_base();
+
+            graph.add_node(id.clone());
+            for link in try!(entry.get_related_links()) {
+                let relation = link.relation().map(|r| r.name().clone());
+                graph.add_link(id.clone(), link.link().clone().without_base(), relation);
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Add an entry to the graph, without any links
+    pub fn add_node(&mut self, id: StoreId) {
+        self.edges.entry(id).or_insert_with(BTreeSet::new);
+    }
+
+    /// Add a link to the graph, with the relation of the link as seen from `from`
+    pub fn add_link(&mut self, from: StoreId, to: StoreId, relation: Option<String>) {
+        if from == to {
+            self.add_node(from);
+            return;
+        }
+
+        self.edges.entry(from.clone()).or_insert_with(BTreeSet::new).insert(to.clone());
+        self.edges.entry(to.clone()).or_insert_with(BTreeSet::new).insert(from.clone());
+
+        if let Some(relation) = relation {
+            self.relations.insert((from, to), relation);
+        }
+    }
+
+    /// Get all entries in the graph
+    pub fn nodes(&self) -> Vec<&StoreId> {
+        self.edges.keys().collect()
+    }
+
+    /// Get the entries `id` is linked with
+    pub fn neighbours(&self, id: &StoreId) -> Vec<&StoreId> {
+        self.edges.get(id).map(|n| n.iter().collect()).unwrap_or(vec![])
+    }
+
+    /// Get the relation of the link from `from` to `to`, if the link has one
+    pub fn relation(&self, from: &StoreId, to: &StoreId) -> Option<&String> {
+        self.relations.get(&(from.clone(), to.clone()))
+    }
+
+    /// Walk the graph breadth-first, starting at `start`
+    ///
+    /// Returns the reached entries together with their distance to `start`, which is included with
+    /// distance zero. If `max_hops` is given, entries which are further away are not walked to.
+    pub fn bfs(&self, start: &StoreId, max_hops: Option<usize>) -> Vec<(StoreId, usize)> {
+        let mut visited = BTreeSet::new();
+        let mut result  = vec![];
+        let mut queue   = VecDeque::new();
+
+        if !self.edges.contains_key(start) {
+            return result;
+        }
+
+        visited.insert(start.clone());
+        queue.push_back((start.clone(), 0));
+
+        while let Some((id, distance)) = queue.pop_front() {
+            if max_hops.map(|max| distance < max).unwrap_or(true) {
+                for next in self.neighbours(&id) {
+                    if visited.insert(next.clone()) {
+                        queue.push_back((next.clone(), distance + 1));
+                    }
+                }
+            }
+            result.push((id, distance));
+        }
+
+        result
+    }
+
+    /// Walk the graph depth-first, starting at `start`
+    ///
+    /// Returns the reached entries in the order they were first visited, `start` first. If
+    /// `max_hops` is given, the walk does not go deeper than that. An entry which is reached again
+    /// on a shorter path is walked from again, so all entries within `max_hops` are reached.
+    pub fn dfs(&self, start: &StoreId, max_hops: Option<usize>) -> Vec<StoreId> {
+        let mut depths : BTreeMap<StoreId, usize> = BTreeMap::new();
+        let mut result = vec![];
+        let mut stack  = vec![];
+
+        if !self.edges.contains_key(start) {
+            return result;
+        }
+
+        // Without max_hops, the depth does not matter, so every entry is walked from only once
+        let is_shorter = |depth: usize, best: Option<&usize>| match best {
+            Some(&best) => max_hops.is_some() && depth < best,
+            None        => true,
+        };
+
+        stack.push((start.clone(), 0));
+
+        while let Some((id, depth)) = stack.pop() {
+            if !is_shorter(depth, depths.get(&id)) {
+                continue;
+            }
+
+            if depths.insert(id.clone(), depth).is_none() {
+                result.push(id.clone());
+            }
+
+            if max_hops.map(|max| depth < max).unwrap_or(true) {
+                // push in reverse order, so the neighbours are visited in order
+                for next in self.neighbours(&id).into_iter().rev() {
+                    if is_shorter(depth + 1, depths.get(next)) {
+                        stack.push((next.clone(), depth + 1));
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Find the shortest path from `from` to `to`
+    ///
+    /// The path includes both `from` and `to`. Returns `None` if the entries are not connected.
+    pub fn shortest_path(&self, from: &StoreId, to: &StoreId) -> Option<Vec<StoreId>> {
+        let mut previous : BTreeMap<StoreId, StoreId> = BTreeMap::new();
+        let mut queue    = VecDeque::new();
+
+        if !self.edges.contains_key(from) || !self.edges.contains_key(to) {
+            return None;
+        }
+
+        queue.push_back(from.clone());
+
+        while let Some(id) = queue.pop_front() {
+            if id == *to {
+                let mut path    = vec![id.clone()];
+                let mut current = id;
+                loop {
+                    current = match previous.get(&current) {
+                        Some(prev) => prev.clone(),
+                        None       => break,
+                    };
+                    path.push(current.clone());
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for next in self.neighbours(&id) {
+                if next != from && !previous.contains_key(next) {
+                    previous.insert(next.clone(), id.clone());
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Get the connected components of the graph
+    ///
+    /// Each component is sorted, the components are sorted by their first entry.
+    pub fn connected_components(&self) -> Vec<Vec<StoreId>> {
+        let mut seen       = BTreeSet::new();
+        let mut components = vec![];
+
+        for id in self.edges.keys() {
+            if seen.contains(id) {
+                continue;
+            }
+
+            let mut component : Vec<StoreId> = self.bfs(id, None)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            component.sort();
+
+            for id in component.iter() {
+                seen.insert(id.clone());
+            }
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// Get the entries which are not linked with any other entry
+    pub fn orphans(&self) -> Vec<&StoreId> {
+        self.edges
+            .iter()
+            .filter(|&(_, links)| links.is_empty())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Get the part of the graph which only contains the entries `ids` and the links between them
+    pub fn subgraph(&self, ids: &[StoreId]) -> LinkGraph {
+        let ids : BTreeSet<&StoreId> = ids.iter().collect();
+        let mut graph = LinkGraph::new();
+
+        for id in ids.iter() {
+            graph.add_node((*id).clone());
+            for next in self.neighbours(id).into_iter().filter(|n| ids.contains(n)) {
+                let relation = self.relation(id, next).cloned();
+                graph.add_link((*id).clone(), next.clone(), relation);
+            }
+        }
+
+        graph
+    }
+
+    /// Export the graph in the Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("graph imag {\n");
+
+        for id in self.edges.keys() {
+            out.push_str(&format!("    \"{}\";\n", escape_dot(id)));
+        }
+
+        for (from, to) in self.edge_list() {
+            match self.label(from, to) {
+                Some(label) => out.push_str(&format!("    \"{}\" -- \"{}\" [label=\"{}\"];\n",
+                                                     escape_dot(from),
+                                                     escape_dot(to),
+                                                     label.replace("\"", "\\\""))),
+                None => out.push_str(&format!("    \"{}\" -- \"{}\";\n",
+                                              escape_dot(from),
+                                              escape_dot(to))),
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Export the graph as GraphML
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"relation\" for=\"edge\" attr.name=\"relation\" \
+                      attr.type=\"string\"/>\n");
+        out.push_str("  <graph id=\"imag\" edgedefault=\"undirected\">\n");
+
+        for id in self.edges.keys() {
+            out.push_str(&format!("    <node id=\"{}\"/>\n", escape_xml(&id.to_string())));
+        }
+
+        for (from, to) in self.edge_list() {
+            let edge = format!("    <edge source=\"{}\" target=\"{}\"",
+                               escape_xml(&from.to_string()),
+                               escape_xml(&to.to_string()));
+
+            match self.label(from, to) {
+                Some(label) => {
+                    out.push_str(&format!("{}>\n", edge));
+                    out.push_str(&format!("      <data key=\"relation\">{}</data>\n",
+                                          escape_xml(&label)));
+                    out.push_str("    </edge>\n");
+                },
+                None => out.push_str(&format!("{}/>\n", edge)),
+            }
+        }
+
+        out.push_str("  </graph>\n");
+        out.push_str("</graphml>\n");
+        out
+    }
+
+    /// Get each link once, as pair of the lower and the higher id
+    fn edge_list(&self) -> Vec<(&StoreId, &StoreId)> {
+        self.edges
+            .iter()
+            .flat_map(|(from, links)| {
+                links.iter().filter(move |to| from < *to).map(move |to| (from, to))
+            })
+            .collect()
+    }
+
+    /// Get the label of the link between `from` and `to`
+    ///
+    /// If the link has a relation on both sides and they differ, both are shown.
+    fn label(&self, from: &StoreId, to: &StoreId) -> Option<String> {
+        match (self.relation(from, to), self.relation(to, from)) {
+            (Some(a), Some(b)) if a == b => Some(a.clone()),
+            (Some(a), Some(b))           => Some(format!("{}/{}", a, b)),
+            (Some(a), None) | (None, Some(a)) => Some(a.clone()),
+            (None, None) => None,
+        }
+    }
+
+}
+
+fn escape_dot(id: &StoreId) -> String {
+    id.to_string().replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+        .replace("'", "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use libimagstore::storeid::StoreId;
+
+    use super::LinkGraph;
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    // a - b - c - d   e - f   g
+    fn get_graph() -> LinkGraph {
+        let mut graph = LinkGraph::new();
+        graph.add_link(id("a"), id("b"), None);
+        graph.add_link(id("b"), id("c"), Some(String::from("references")));
+        graph.add_link(id("c"), id("b"), Some(String::from("referenced-by")));
+        graph.add_link(id("c"), id("d"), None);
+        graph.add_link(id("e"), id("f"), None);
+        graph.add_node(id("g"));
+        graph
+    }
+
+    #[test]
+    fn test_bfs_with_max_hops() {
+        let graph = get_graph();
+
+        let reached = graph.bfs(&id("a"), Some(2));
+        assert_eq!(reached, vec![(id("a"), 0), (id("b"), 1), (id("c"), 2)]);
+
+        assert_eq!(graph.bfs(&id("a"), None).len(), 4);
+        assert_eq!(graph.dfs(&id("b"), None), vec![id("b"), id("a"), id("c"), id("d")]);
+    }
+
+    #[test]
+    fn test_dfs_with_max_hops_reaches_entries_on_shorter_paths() {
+        // a - b - c - d, a - c
+        let mut graph = LinkGraph::new();
+        graph.add_link(id("a"), id("b"), None);
+        graph.add_link(id("b"), id("c"), None);
+        graph.add_link(id("a"), id("c"), None);
+        graph.add_link(id("c"), id("d"), None);
+
+        // c is visited via b first, at depth 2, but is only one hop away from a
+        assert_eq!(graph.dfs(&id("a"), Some(2)), vec![id("a"), id("b"), id("c"), id("d")]);
+        assert_eq!(graph.dfs(&id("a"), Some(1)), vec![id("a"), id("b"), id("c")]);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let graph = get_graph();
+
+        assert_eq!(graph.shortest_path(&id("a"), &id("d")),
+                   Some(vec![id("a"), id("b"), id("c"), id("d")]));
+        assert_eq!(graph.shortest_path(&id("a"), &id("a")), Some(vec![id("a")]));
+        assert_eq!(graph.shortest_path(&id("a"), &id("e")), None);
+    }
+
+    #[test]
+    fn test_components_and_orphans() {
+        let graph = get_graph();
+
+        assert_eq!(graph.connected_components(), vec![
+            vec![id("a"), id("b"), id("c"), id("d")],
+            vec![id("e"), id("f")],
+            vec![id("g")],
+        ]);
+        assert_eq!(graph.orphans(), vec![&id("g")]);
+    }
+
+    #[test]
+    fn test_export() {
+        let graph = get_graph().subgraph(&[id("b"), id("c")]);
+
+        assert_eq!(graph.to_dot(), "graph imag {
+    \"b\";
+    \"c\";
+    \"b\" -- \"c\" [label=\"references/referenced-by\"];
+}
+");
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<node id=\"b\"/>"));
+        assert!(graphml.contains("<edge source=\"b\" target=\"c\">"));
+        assert!(graphml.contains("<data key=\"relation\">references/referenced-by</data>"));
+    }
+
+}
@@ -17,10 +17,14 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::collections::BTreeMap;
+
 use libimagstore::storeid::StoreId;
 use libimagstore::store::Entry;
 use libimagstore::store::EntryHeader;
 use libimagstore::store::Result as StoreResult;
+use libimagstore::error::StoreErrorKind as SEK;
+use libimagstore::linkupdate::LinkUpdater;
 use libimagerror::into::IntoError;
 
 use error::LinkErrorKind as LEK;
@@ -33,6 +37,123 @@ use toml::Value;
 
 pub type Link = StoreId;
 
+/// The relation of an internal link, for example "references" or "mentions"
+///
+/// Links are bidirectional, so the relation has an inverse name, which is used on the other side
+/// of the link (for example "referenced-by"). If no inverse name is given, the relation is named
+/// the same on both sides. The attributes are free-form and stored on both sides of the link.
+///
+/// Relations are stored in the `imag.relations` header of the entry, next to the plain
+/// `imag.links` array, so links without a relation look the same as they always did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    name: String,
+    inverse: String,
+    attributes: BTreeMap<String, Value>,
+}
+
+impl Relation {
+
+    pub fn new<S: Into<String>>(name: S) -> Relation {
+        let name = name.into();
+        Relation {
+            inverse: name.clone(),
+            name: name,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_inverse<S: Into<String>>(mut self, inverse: S) -> Relation {
+        self.inverse = inverse.into();
+        self
+    }
+
+    pub fn with_attribute<S: Into<String>>(mut self, key: S, value: Value) -> Relation {
+        self.attributes.insert(key.into(), value);
+        self
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn inverse(&self) -> &String {
+        &self.inverse
+    }
+
+    pub fn attributes(&self) -> &BTreeMap<String, Value> {
+        &self.attributes
+    }
+
+    /// Get the relation as it is seen from the other side of the link
+    pub fn inverted(&self) -> Relation {
+        Relation {
+            name: self.inverse.clone(),
+            inverse: self.name.clone(),
+            attributes: self.attributes.clone(),
+        }
+    }
+
+    fn into_value(self, link: String) -> Value {
+        let mut table = BTreeMap::new();
+        table.insert(String::from("link"), Value::String(link));
+        table.insert(String::from("relation"), Value::String(self.name));
+        table.insert(String::from("inverse"), Value::String(self.inverse));
+        if !self.attributes.is_empty() {
+            table.insert(String::from("attributes"), Value::Table(self.attributes));
+        }
+        Value::Table(table)
+    }
+
+    fn from_value(v: Value) -> Result<(String, Relation)> {
+        let mut table = match v {
+            Value::Table(t) => t,
+            _ => return Err(LEK::ExistingRelationTypeWrong.into_error()),
+        };
+
+        let link = match table.remove("link") {
+            Some(Value::String(s)) => s,
+            _ => return Err(LEK::ExistingRelationTypeWrong.into_error()),
+        };
+        let name = match table.remove("relation") {
+            Some(Value::String(s)) => s,
+            _ => return Err(LEK::ExistingRelationTypeWrong.into_error()),
+        };
+        let inverse = match table.remove("inverse") {
+            Some(Value::String(s)) => s,
+            None                   => name.clone(),
+            _ => return Err(LEK::ExistingRelationTypeWrong.into_error()),
+        };
+        let attributes = match table.remove("attributes") {
+            Some(Value::Table(t)) => t,
+            None                  => BTreeMap::new(),
+            _ => return Err(LEK::ExistingRelationTypeWrong.into_error()),
+        };
+
+        Ok((link, Relation { name: name, inverse: inverse, attributes: attributes }))
+    }
+
+}
+
+/// An internal link together with its relation, if it has one
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelatedLink {
+    link: Link,
+    relation: Option<Relation>,
+}
+
+impl RelatedLink {
+
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    pub fn relation(&self) -> Option<&Relation> {
+        self.relation.as_ref()
+    }
+
+}
+
 pub trait InternalLinker {
 
     /// Get the internal links from the implementor object
@@ -47,6 +168,18 @@ pub trait InternalLinker {
     /// Remove an internal link from the implementor object
     fn remove_internal_link(&mut self, link: &mut Entry) -> Result<()>;
 
+    /// Get the internal links from the implementor object, together with their relations
+    fn get_related_links(&self) -> Result<Vec<RelatedLink>>;
+
+    /// Get the internal links from the implementor object which have the relation `relation`
+    fn get_internal_links_by_relation(&self, relation: &str) -> Result<LinkIter>;
+
+    /// Add an internal link with a relation to the implementor object
+    ///
+    /// The linked entry gets the inverted relation. If the entries are already linked, the
+    /// relation of the link is replaced.
+    fn add_related_link(&mut self, link: &mut Entry, relation: Relation) -> Result<()>;
+
 }
 
 pub mod iter {
@@ -315,6 +448,47 @@ impl InternalLinker for Entry {
             })
     }
 
+    fn get_related_links(&self) -> Result<Vec<RelatedLink>> {
+        let mut relations = try!(get_relations(self.get_header()));
+
+        self.get_internal_links()
+            .map(|links| {
+                links.map(|link| {
+                        let relation = link.clone()
+                            .without_base()
+                            .to_str()
+                            .ok()
+                            .and_then(|s| relations.remove(&s));
+
+                        RelatedLink { link: link, relation: relation }
+                    })
+                    .collect()
+            })
+    }
+
+    fn get_internal_links_by_relation(&self, relation: &str) -> Result<LinkIter> {
+        self.get_related_links()
+            .map(|links| {
+                let links = links.into_iter()
+                    .filter(|l| l.relation().map(|r| r.name() == relation).unwrap_or(false))
+                    .map(|l| l.link)
+                    .collect();
+                LinkIter::new(links)
+            })
+    }
+
+    fn add_related_link(&mut self, link: &mut Entry, relation: Relation) -> Result<()> {
+        let own_loc   = self.get_location().clone();
+        let other_loc = link.get_location().clone();
+
+        debug!("Adding internal link from {:?} to {:?} with relation {:?}",
+               own_loc, other_loc, relation);
+
+        try!(self.add_internal_link(link));
+        try!(set_relation(link.get_header_mut(), &own_loc, Some(relation.inverted())));
+        set_relation(self.get_header_mut(), &other_loc, Some(relation))
+    }
+
     fn remove_internal_link(&mut self, link: &mut Entry) -> Result<()> {
         let own_loc   = self.get_location().clone().without_base();
         let other_loc = link.get_location().clone().without_base();
@@ -326,6 +500,7 @@ impl InternalLinker for Entry {
                 debug!("Rewriting own links for {:?}, without {:?}", other_loc, own_loc);
                 rewrite_links(link.get_header_mut(), links.filter(|l| *l != own_loc))
             })
+            .and_then(|_| set_relation(link.get_header_mut(), &own_loc, None))
             .and_then(|_| {
                 self.get_internal_links()
                     .and_then(|links| {
@@ -333,10 +508,61 @@ impl InternalLinker for Entry {
                         rewrite_links(self.get_header_mut(), links.filter(|l| *l != other_loc))
                     })
             })
+            .and_then(|_| set_relation(self.get_header_mut(), &other_loc, None))
+    }
+
+}
+
+/// Keeps the links of linked entries intact when entries are moved or deleted
+///
+/// Register it with `Store::register_link_updater()`. The store only uses it if `update-links` is
+/// enabled in the store configuration. Moving an entry re-points the links to it (and their
+/// relations) to the new id, deleting an entry removes the links to it.
+#[derive(Debug, Clone)]
+pub struct InternalLinkUpdater;
+
+impl LinkUpdater for InternalLinkUpdater {
+
+    fn linked(&self, entry: &Entry) -> StoreResult<Vec<StoreId>> {
+        entry.get_internal_links()
+            .map(|links| links.collect())
+            .map_err(|e| SEK::LinkUpdateError.into_error_with_cause(Box::new(e)))
+    }
+
+    fn update(&self, entry: &mut Entry, old: &StoreId, new: Option<&StoreId>) -> StoreResult<()> {
+        repoint_link(entry, old, new)
+            .map_err(|e| SEK::LinkUpdateError.into_error_with_cause(Box::new(e)))
     }
 
 }
 
+/// Replace the link of `entry` to `old` by a link to `new` with the same relation, or remove it if
+/// `new` is `None`
+fn repoint_link(entry: &mut Entry, old: &StoreId, new: Option<&StoreId>) -> Result<()> {
+    let old      = old.clone().without_base();
+    let old_str  = try!(old.to_str().map_err_into(LEK::StoreIdError));
+    let relation = try!(get_relations(entry.get_header())).remove(&old_str);
+    let links    = try!(entry.get_internal_links()).filter(|link| *link != old);
+
+    debug!("Re-pointing link from {:?} to {:?} in {:?}", old, new, entry.get_location());
+    match new {
+        Some(new) => {
+            let new   = new.clone().without_base();
+            let links = links.chain(LinkIter::new(vec![new.clone()]));
+            try!(rewrite_links(entry.get_header_mut(), links));
+            try!(set_relation(entry.get_header_mut(), &old, None));
+            match relation {
+                Some(relation) => set_relation(entry.get_header_mut(), &new, Some(relation)),
+                None           => Ok(()),
+            }
+        },
+        None => {
+            try!(rewrite_links(entry.get_header_mut(), links));
+            set_relation(entry.get_header_mut(), &old, None)
+        },
+    }
+}
+
 fn rewrite_links<I: Iterator<Item = Link>>(header: &mut EntryHeader, links: I) -> Result<()> {
     let links = try!(links.into_values()
                      .fold(Ok(vec![]), |acc, elem| {
@@ -378,6 +604,47 @@ fn add_foreign_link(target: &mut Entry, from: StoreId) -> Result<()> {
         })
 }
 
+/// Get the relations from the `imag.relations` header, by the link they belong to
+fn get_relations(header: &EntryHeader) -> Result<BTreeMap<String, Relation>> {
+    let relations = match header.read("imag.relations") {
+        Err(e) => return Err(LEK::EntryHeaderReadError.into_error_with_cause(Box::new(e))),
+        Ok(None) => return Ok(BTreeMap::new()),
+        Ok(Some(Value::Array(r))) => r,
+        Ok(Some(_)) => return Err(LEK::ExistingRelationTypeWrong.into_error()),
+    };
+
+    relations.into_iter().map(Relation::from_value).collect()
+}
+
+/// Set the relation of the link to `link` in the `imag.relations` header, or remove it if
+/// `relation` is `None`
+///
+/// If there are no relations left, the `imag.relations` header is removed.
+pub fn set_relation(header: &mut EntryHeader, link: &Link, relation: Option<Relation>)
+    -> Result<()>
+{
+    let mut relations = try!(get_relations(header));
+    let link = try!(link.clone().without_base().to_str().map_err_into(LEK::StoreIdError));
+
+    match relation {
+        Some(relation) => { relations.insert(link, relation); },
+        None => {
+            if relations.remove(&link).is_none() {
+                return Ok(());
+            }
+        },
+    }
+
+    let result = if relations.is_empty() {
+        header.delete("imag.relations")
+    } else {
+        let relations = relations.into_iter().map(|(l, r)| r.into_value(l)).collect();
+        header.set("imag.relations", Value::Array(relations))
+    };
+
+    result.map(|_| ()).map_err_into(LEK::EntryHeaderWriteError)
+}
+
 fn process_rw_result(links: StoreResult<Option<Value>>) -> Result<LinkIter> {
     use std::path::PathBuf;
 
@@ -431,7 +698,7 @@ mod test {
     }
 
     pub fn get_store() -> Store {
-        Store::new(PathBuf::from("/"), None).unwrap()
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
     }
 
     #[test]
@@ -562,5 +829,146 @@ mod test {
 
     }
 
+    fn get_store_with_link_updates() -> Store {
+        use toml::Parser;
+        use super::InternalLinkUpdater;
+
+        let cfg = Parser::new(r#"
+[store]
+update-links = true
+store-unload-hook-aspects  = []
+pre-create-hook-aspects    = []
+post-create-hook-aspects   = []
+pre-move-hook-aspects      = []
+post-move-hook-aspects     = []
+pre-retrieve-hook-aspects  = []
+post-retrieve-hook-aspects = []
+pre-update-hook-aspects    = []
+post-update-hook-aspects   = []
+pre-delete-hook-aspects    = []
+post-delete-hook-aspects   = []
+
+[store.aspects]
+
+[store.hooks]
+        "#).parse().unwrap();
+
+        let store = Store::new_in_memory(PathBuf::from("/"), cfg.get("store").cloned()).unwrap();
+        assert!(store.register_link_updater(Box::new(InternalLinkUpdater)).is_ok());
+        store
+    }
+
+    #[test]
+    fn test_move_repoints_links() {
+        use libimagstore::storeid::StoreId;
+        use super::Relation;
+
+        setup_logging();
+        let store = get_store_with_link_updates();
+
+        {
+            let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+            let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+            let mut e3 = store.retrieve(PathBuf::from("3")).unwrap();
+            assert!(e1.add_related_link(&mut e2, Relation::new("related")).is_ok());
+            assert!(e1.add_internal_link(&mut e3).is_ok());
+        }
+
+        let old = StoreId::new_baseless(PathBuf::from("1")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("4")).unwrap();
+        assert!(store.move_by_id(old, new.clone()).is_ok());
+
+        for id in &["2", "3"] {
+            let e     = store.get(PathBuf::from(id)).unwrap().unwrap();
+            let links = e.get_internal_links().unwrap().collect::<Vec<_>>();
+            assert_eq!(links, vec![new.clone()]);
+        }
+
+        let e2 = store.get(PathBuf::from("2")).unwrap().unwrap();
+        assert_eq!(e2.get_internal_links_by_relation("related").unwrap().collect::<Vec<_>>(),
+                   vec![new.clone()]);
+
+        let e4 = store.get(PathBuf::from("4")).unwrap().unwrap();
+        assert_eq!(e4.get_internal_links().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_delete_removes_links() {
+        use super::Relation;
+
+        setup_logging();
+        let store = get_store_with_link_updates();
+
+        {
+            let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+            let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+            let mut e3 = store.retrieve(PathBuf::from("3")).unwrap();
+            assert!(e1.add_related_link(&mut e2, Relation::new("related")).is_ok());
+            assert!(e3.add_internal_link(&mut e2).is_ok());
+            assert!(e1.add_internal_link(&mut e3).is_ok());
+        }
+
+        assert!(store.delete(PathBuf::from("2")).is_ok());
+
+        let e1 = store.get(PathBuf::from("1")).unwrap().unwrap();
+        let e3 = store.get(PathBuf::from("3")).unwrap().unwrap();
+        assert_eq!(e1.get_internal_links().unwrap().count(), 1);
+        assert_eq!(e3.get_internal_links().unwrap().count(), 1);
+        assert!(e1.get_related_links().unwrap().iter().all(|l| l.relation().is_none()));
+    }
+
+    #[test]
+    fn test_related_links() {
+        use toml::Value;
+        use super::Relation;
+
+        setup_logging();
+        let store = get_store();
+
+        let mut note = store.retrieve(PathBuf::from("note")).unwrap();
+        let mut task = store.retrieve(PathBuf::from("task")).unwrap();
+        let mut bm   = store.retrieve(PathBuf::from("bookmark")).unwrap();
+
+        let relation = Relation::new("references")
+            .with_inverse("referenced-by")
+            .with_attribute("page", Value::Integer(3));
+        assert!(note.add_related_link(&mut task, relation.clone()).is_ok());
+        assert!(note.add_internal_link(&mut bm).is_ok());
+
+        let links = note.get_related_links().unwrap();
+        assert_eq!(links.len(), 2);
+        assert!(links.iter().any(|l| l.relation().is_none()));
+        assert!(links.iter().any(|l| l.relation() == Some(&relation)));
+
+        let refs = note.get_internal_links_by_relation("references").unwrap().collect::<Vec<_>>();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].clone().with_base(store.path().clone()), *task.get_location());
+
+        let back = task.get_related_links().unwrap();
+        assert_eq!(back.len(), 1);
+        assert_eq!(back[0].relation(), Some(&relation.inverted()));
+        assert_eq!(task.get_internal_links_by_relation("referenced-by").unwrap().count(), 1);
+
+        assert!(note.remove_internal_link(&mut task).is_ok());
+        assert_eq!(note.get_internal_links_by_relation("references").unwrap().count(), 0);
+        assert!(task.get_header().read("imag.relations").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_plain_links_have_no_relation() {
+        use toml::Value;
+
+        setup_logging();
+        let store = get_store();
+
+        let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+        let links  = Value::Array(vec![Value::String(String::from("2"))]);
+        assert!(e1.get_header_mut().set("imag.links", links).is_ok());
+
+        let links = e1.get_related_links().unwrap();
+        assert_eq!(links.len(), 1);
+        assert!(links[0].relation().is_none());
+    }
+
 }
 
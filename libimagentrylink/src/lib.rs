@@ -47,8 +47,10 @@ extern crate env_logger;
 
 module_entry_path_mod!("links");
 
+pub mod check;
 pub mod error;
 pub mod external;
+pub mod graph;
 pub mod internal;
 pub mod result;
 
@@ -6,6 +6,8 @@ generate_error_module!(
 
         FetchByHashError => "Error fetching mail from Store by hash",
         FetchError       => "Error fetching mail from Store",
+        MailHashingError => "Error while hashing mail",
+        NotAMaildir      => "Directory is not a Maildir",
         IOError => "IO Error"
     );
 );
@@ -0,0 +1,552 @@
+//! Module for importing many mails at once
+//!
+//! The `Importer` imports all mails of a Maildir or a mbox file into the store. Mails which are
+//! already in the store (as identified by their Message-Id, or by the hash of the whole mail if
+//! they have none) are skipped, so importing the same mail archive again only imports the mails
+//! which are new.
+//!
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use mailparse::parse_mail;
+
+use libimagstore::store::Store;
+use libimagref::hasher::Hasher;
+use libimagref::hashers::sha256::Sha256Hasher;
+use libimagref::reference::Ref;
+use libimagerror::into::IntoError;
+use libimagerror::trace::trace_error;
+
+use mail::Mail;
+use result::Result;
+use error::{MapErrInto, MailErrorKind as MEK};
+
+/// The number of mails an import imported, skipped and failed to import
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    imported: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+impl ImportReport {
+
+    /// The number of mails which were imported
+    pub fn imported(&self) -> usize {
+        self.imported
+    }
+
+    /// The number of mails which were skipped because they were already in the store
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// The number of mails which could not be imported
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+}
+
+pub struct Importer<'a> {
+    store: &'a Store,
+
+    /// The keys of the mails in the store, see `mail_key()`
+    known: HashSet<String>,
+}
+
+impl<'a> Importer<'a> {
+
+    /// Create a new Importer, which remembers all mails already in the store
+    ///
+    /// Mail clients move new mails of a Maildir from "new" to "cur", and rename them when their
+    /// flags change. Refs to such mails are pointed to the new file of the mail here. Refs which
+    /// cannot be read are reported and skipped.
+    pub fn new(store: &'a Store) -> Result<Importer<'a>> {
+        let ids = try!(store.retrieve_for_module("ref").map_err_into(MEK::FetchError));
+
+        let mut known = HashSet::new();
+        for id in ids {
+            let mut r = match Ref::get(store, id) {
+                Ok(r) => r,
+                Err(e) => {
+                    trace_error(&e);
+                    continue;
+                },
+            };
+
+            match Mail::is_mail_ref(&r) {
+                Ok(true)  => {},
+                Ok(false) => continue,
+                Err(e)    => {
+                    trace_error(&e);
+                    continue;
+                },
+            }
+
+            match ref_key(&mut r) {
+                Ok(key) => { known.insert(key); },
+                Err(e)  => trace_error(&e),
+            }
+        }
+        debug!("Found {} mails in the store", known.len());
+
+        Ok(Importer {
+            store: store,
+            known: known,
+        })
+    }
+
+    /// Import all mails of the Maildir at `path`
+    ///
+    /// The mails in the "cur", "new" and "tmp" directories of the Maildir are imported. Mails
+    /// which cannot be imported are reported and counted, but do not abort the import.
+    pub fn import_maildir<P: AsRef<Path>>(&mut self, path: P) -> Result<ImportReport> {
+        let path = path.as_ref();
+        if !path.join("cur").is_dir() || !path.join("new").is_dir() {
+            return Err(MEK::NotAMaildir.into_error());
+        }
+
+        let mut report = ImportReport::default();
+        for subdir in ["cur", "new", "tmp"].iter() {
+            let dir = path.join(subdir);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            let mut files = vec![];
+            for entry in try!(fs::read_dir(&dir).map_err_into(MEK::IOError)) {
+                let file = try!(entry.map_err_into(MEK::IOError)).path();
+                let hidden = file.file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| name.starts_with("."))
+                    .unwrap_or(false);
+
+                if file.is_file() && !hidden {
+                    files.push(file);
+                }
+            }
+            files.sort();
+
+            for file in files {
+                let key = file_key(&file);
+                self.import_file(&file, key, &mut report);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Import all mails of the mbox file at `path`
+    ///
+    /// As mails in the store are references to mail files, each mail is written to a file in the
+    /// directory `target`, which is named after the hash of the key of the mail. The directory is
+    /// created if it does not exist.
+    pub fn import_mbox<P, D>(&mut self, path: P, target: D) -> Result<ImportReport>
+        where P: AsRef<Path>,
+              D: AsRef<Path>
+    {
+        let target = target.as_ref();
+        let mut content = vec![];
+        try!(File::open(path.as_ref())
+             .and_then(|mut file| file.read_to_end(&mut content))
+             .map_err_into(MEK::IOError));
+        try!(fs::create_dir_all(target).map_err_into(MEK::IOError));
+
+        let mut report = ImportReport::default();
+        for mail in split_mbox(&content) {
+            let key = match mail_key(&mail) {
+                Ok(key) => key,
+                Err(e) => {
+                    trace_error(&e);
+                    report.failed += 1;
+                    continue;
+                },
+            };
+
+            if self.known.contains(&key) {
+                report.skipped += 1;
+                continue;
+            }
+
+            let written = hash(key.as_bytes())
+                .map(|name| target.join(name))
+                .and_then(|file| {
+                    File::create(&file)
+                        .and_then(|mut f| f.write_all(&mail))
+                        .map(|_| file)
+                        .map_err_into(MEK::IOError)
+                });
+
+            match written {
+                Ok(file) => self.import_file(&file, Ok(key), &mut report),
+                Err(e) => {
+                    trace_error(&e);
+                    report.failed += 1;
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn import_file(&mut self, file: &PathBuf, key: Result<String>, report: &mut ImportReport) {
+        let key = match key {
+            Ok(key) => key,
+            Err(e) => {
+                trace_error(&e);
+                report.failed += 1;
+                return;
+            },
+        };
+
+        if self.known.contains(&key) {
+            debug!("Skipping already imported mail: {:?}", file);
+            report.skipped += 1;
+            return;
+        }
+
+        match Mail::import_from_path(self.store, file) {
+            Ok(_) => {
+                debug!("Imported mail: {:?}", file);
+                self.known.insert(key);
+                report.imported += 1;
+            },
+            Err(e) => {
+                trace_error(&e);
+                report.failed += 1;
+            },
+        }
+    }
+
+}
+
+/// Get the key mails are identified by when importing
+///
+/// This is the Message-Id of the mail or, if it has none, the hash of the whole mail. Different
+/// mails can have the same subject, sender and recipients, so the hash of the `MailHasher` cannot
+/// be used.
+fn mail_key(mail: &[u8]) -> Result<String> {
+    let parsed = try!(parse_mail(mail)
+        .map_err(Box::new)
+        .map_err(|e| MEK::MailParsingError.into_error_with_cause(e)));
+
+    let message_id = parsed.headers
+        .iter()
+        .find(|hdr| hdr.get_key().map(|k| k.to_lowercase() == "message-id").unwrap_or(false))
+        .and_then(|hdr| hdr.get_value().ok())
+        .map(|id| String::from(id.trim()));
+
+    match message_id {
+        Some(ref id) if !id.is_empty() => Ok(id.clone()),
+        _ => hash(mail),
+    }
+}
+
+/// Get the key of the mail in `file`, see `mail_key()`
+fn file_key(file: &Path) -> Result<String> {
+    let mut content = vec![];
+    try!(File::open(file)
+         .and_then(|mut f| f.read_to_end(&mut content))
+         .map_err_into(MEK::IOError));
+    mail_key(&content)
+}
+
+/// Get the key of the mail `r` refers to, see `mail_key()`
+///
+/// If the file of the mail is gone because it was moved within its Maildir, `r` is pointed to the
+/// new file first.
+fn ref_key(r: &mut Ref) -> Result<String> {
+    let mut file = try!(r.fs_file().map_err_into(MEK::RefHandlingError));
+    if !file.exists() {
+        if let Some(moved) = find_in_maildir(&file) {
+            info!("{:?} was moved to {:?}", file, moved);
+            try!(r.set_fs_file(moved.clone()).map_err_into(MEK::RefHandlingError));
+            file = moved;
+        }
+    }
+    file_key(&file)
+}
+
+/// Find the file of a mail which was moved within its Maildir
+///
+/// The file name of a mail in a Maildir is a unique name, which is followed by ":" and the flags
+/// of the mail once it is in "cur". When the mail is moved from "new" to "cur" or its flags
+/// change, the unique name stays the same.
+fn find_in_maildir(file: &Path) -> Option<PathBuf> {
+    let maildir = match file.parent() {
+        Some(dir) if dir.ends_with("cur") || dir.ends_with("new") => dir.parent(),
+        _ => None,
+    };
+    let maildir = match maildir {
+        Some(maildir) => maildir,
+        None          => return None,
+    };
+
+    let unique = match file.file_name().and_then(|name| name.to_str()) {
+        Some(name) => String::from(name.split(':').next().unwrap_or(name)),
+        None       => return None,
+    };
+
+    for subdir in ["cur", "new"].iter() {
+        let entries = match fs::read_dir(maildir.join(subdir)) {
+            Ok(entries) => entries,
+            Err(_)      => continue,
+        };
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let found = entry.file_name()
+                .to_str()
+                .map(|name| name.split(':').next().unwrap_or(name) == unique)
+                .unwrap_or(false);
+
+            if found {
+                return Some(entry.path());
+            }
+        }
+    }
+
+    None
+}
+
+/// Hash `data` with SHA-256
+fn hash(mut data: &[u8]) -> Result<String> {
+    Sha256Hasher::new()
+        .create_hash(&PathBuf::new(), &mut data)
+        .map_err_into(MEK::MailHashingError)
+}
+
+/// Split the content of a mbox file into the mails
+///
+/// Each mail starts with a "From " line after an empty line (or at the start of the file). Lines
+/// in the mail which start with "From " are escaped as ">From " in the mbox file, the escaping is
+/// undone here. Mails are not necessarily UTF-8, so the content is split as bytes.
+fn split_mbox(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut mails   = vec![];
+    let mut current : Option<Vec<u8>> = None;
+    let mut after_empty_line = true;
+
+    let content = if content.ends_with(b"\n") {
+        &content[..content.len() - 1]
+    } else {
+        content
+    };
+
+    for line in content.split(|b| *b == b'\n') {
+        if after_empty_line && line.starts_with(b"From ") {
+            if let Some(mail) = current.take() {
+                mails.push(mail);
+            }
+            current = Some(vec![]);
+        } else if let Some(ref mut mail) = current {
+            let unquoted = line.iter().position(|b| *b != b'>').map(|i| &line[i..]);
+            let escaped  = line.starts_with(b">") &&
+                unquoted.map(|l| l.starts_with(b"From ")).unwrap_or(false);
+            mail.extend_from_slice(if escaped { &line[1..] } else { line });
+            mail.push(b'\n');
+        }
+
+        after_empty_line = line.is_empty() || line == &b"\r"[..];
+    }
+
+    if let Some(mail) = current {
+        mails.push(mail);
+    }
+
+    mails
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{self, File, create_dir_all};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use tempdir::TempDir;
+
+    use libimagstore::store::Store;
+
+    use mail::Mail;
+    use super::{Importer, ImportReport, split_mbox};
+
+    fn get_store() -> Store {
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
+    }
+
+    /// A mail with the given Message-Id and body, all mails have the same subject and addresses
+    fn mail(id: Option<&str>, body: &str) -> String {
+        let mut mail = String::from("From: alice@example.com\n\
+                                     To: bob@example.com\n\
+                                     Subject: Hello\n");
+        if let Some(id) = id {
+            mail.push_str(&format!("Message-ID: <{}>\n", id));
+        }
+        mail.push_str(&format!("\n{}\n", body));
+        mail
+    }
+
+    fn write(path: PathBuf, content: &str) {
+        File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+    }
+
+    fn maildir(path: &Path) -> PathBuf {
+        for subdir in ["cur", "new", "tmp"].iter() {
+            create_dir_all(path.join(subdir)).unwrap();
+        }
+        path.to_path_buf()
+    }
+
+    fn counts(report: ImportReport) -> (usize, usize, usize) {
+        (report.imported(), report.skipped(), report.failed())
+    }
+
+    fn import_maildir(store: &Store, path: &Path) -> (usize, usize, usize) {
+        counts(Importer::new(store).unwrap().import_maildir(path).unwrap())
+    }
+
+    #[test]
+    fn test_import_maildir() {
+        let dir   = TempDir::new("imag-mail-import").unwrap();
+        let md    = maildir(&dir.path().join("Mail"));
+        let store = get_store();
+
+        write(md.join("cur/1.host:2,S"), &mail(Some("1@example.com"), "first"));
+        write(md.join("new/2.host"), &mail(Some("2@example.com"), "second"));
+        write(md.join("new/.hidden"), &mail(Some("3@example.com"), "hidden"));
+        write(md.join("tmp/4.host"), &mail(None, "fourth"));
+
+        assert_eq!(import_maildir(&store, &md), (3, 0, 0));
+        assert_eq!(Mail::all(&store, None).unwrap().count(), 3);
+
+        // importing again only skips
+        assert_eq!(import_maildir(&store, &md), (0, 3, 0));
+
+        assert!(Importer::new(&store).unwrap().import_maildir(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_import_maildir_duplicates() {
+        let dir   = TempDir::new("imag-mail-import").unwrap();
+        let md    = maildir(&dir.path().join("Mail"));
+        let store = get_store();
+
+        // the same mail twice, once with a different body
+        write(md.join("cur/1.host:2,S"), &mail(Some("1@example.com"), "first"));
+        write(md.join("new/2.host"), &mail(Some("1@example.com"), "first, again"));
+
+        // the same mail without a Message-Id twice
+        write(md.join("cur/3.host:2,S"), &mail(None, "third"));
+        write(md.join("new/4.host"), &mail(None, "third"));
+
+        assert_eq!(import_maildir(&store, &md), (2, 2, 0));
+    }
+
+    #[test]
+    fn test_mail_moved_within_maildir() {
+        let dir   = TempDir::new("imag-mail-import").unwrap();
+        let md    = maildir(&dir.path().join("Mail"));
+        let store = get_store();
+
+        write(md.join("new/1.host"), &mail(Some("1@example.com"), "first"));
+        assert_eq!(import_maildir(&store, &md), (1, 0, 0));
+
+        // the mail client moves the mail to "cur" when it was read
+        fs::rename(md.join("new/1.host"), md.join("cur/1.host:2,S")).unwrap();
+        assert_eq!(import_maildir(&store, &md), (0, 1, 0));
+
+        let mail = Mail::all(&store, None).unwrap().next().unwrap();
+        assert_eq!(mail.fs_file().unwrap(), md.join("cur/1.host:2,S").canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_import_mbox() {
+        let dir    = TempDir::new("imag-mail-import").unwrap();
+        let target = dir.path().join("mails");
+        let store  = get_store();
+
+        let mails = vec![
+            mail(Some("1@example.com"), "first"),
+            mail(Some("2@example.com"), "second"),
+            mail(None, "third"),
+            mail(None, "third"),
+            mail(Some("1@example.com"), "first"),
+        ];
+
+        let mut mbox = String::new();
+        for mail in mails {
+            mbox.push_str("From alice@example.com Mon Jan  2 10:00:00 2017\n");
+            mbox.push_str(&mail);
+            mbox.push_str("\n");
+        }
+        let path = dir.path().join("mbox");
+        write(path.clone(), &mbox);
+
+        let report = Importer::new(&store).unwrap().import_mbox(&path, &target).unwrap();
+        assert_eq!(counts(report), (3, 2, 0));
+        assert_eq!(fs::read_dir(&target).unwrap().count(), 3);
+        assert_eq!(Mail::all(&store, None).unwrap().count(), 3);
+
+        let report = Importer::new(&store).unwrap().import_mbox(&path, &target).unwrap();
+        assert_eq!(counts(report), (0, 5, 0));
+    }
+
+    /// A mail with a body which is not valid UTF-8: "café" in latin-1
+    fn latin1_mail(id: &str) -> Vec<u8> {
+        let mut mail = mail(Some(id), "caf").into_bytes();
+        mail.pop();
+        mail.extend_from_slice(b"\xe9\n");
+        mail
+    }
+
+    #[test]
+    fn test_import_8bit_mails() {
+        let dir    = TempDir::new("imag-mail-import").unwrap();
+        let md     = maildir(&dir.path().join("Mail"));
+        let target = dir.path().join("mails");
+        let store  = get_store();
+
+        File::create(md.join("new/1.host"))
+            .unwrap()
+            .write_all(&latin1_mail("1@example.com"))
+            .unwrap();
+        assert_eq!(import_maildir(&store, &md), (1, 0, 0));
+
+        let mut mbox = b"From alice@example.com Mon Jan  2 10:00:00 2017\n".to_vec();
+        mbox.extend_from_slice(&latin1_mail("2@example.com"));
+        let path = dir.path().join("mbox");
+        File::create(&path).unwrap().write_all(&mbox).unwrap();
+
+        let report = Importer::new(&store).unwrap().import_mbox(&path, &target).unwrap();
+        assert_eq!(counts(report), (1, 0, 0));
+        assert_eq!(Mail::all(&store, None).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = "From alice@example.com Mon Jan  2 10:00:00 2017\n\
+                    From: alice@example.com\n\
+                    Subject: first\n\
+                    \n\
+                    >From the start\n\
+                    \n\
+                    From bob@example.com Mon Jan  2 11:00:00 2017\n\
+                    From: bob@example.com\n\
+                    Subject: second\n\
+                    \n\
+                    body\n";
+
+        let mails = split_mbox(mbox.as_bytes())
+            .into_iter()
+            .map(|mail| String::from_utf8(mail).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(2, mails.len());
+        assert!(mails[0].starts_with("From: alice@example.com\n"));
+        assert!(mails[0].contains("\nFrom the start\n"));
+        assert!(mails[1].starts_with("From: bob@example.com\n"));
+        assert!(mails[1].ends_with("body\n"));
+    }
+
+}
@@ -2,14 +2,17 @@
 extern crate mailparse;
 extern crate semver;
 extern crate toml;
-extern crate filters;
+#[cfg(test)] extern crate tempdir;
 
 #[macro_use] extern crate libimagerror;
 extern crate libimagstore;
 extern crate libimagref;
+#[cfg(test)] extern crate libimagentrylink;
+#[cfg(test)] extern crate libimagentrytag;
 
 pub mod error;
 pub mod hasher;
+pub mod import;
 pub mod iter;
 pub mod mail;
 pub mod result;
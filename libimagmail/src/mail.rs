@@ -3,10 +3,14 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::fs::File;
 use std::io::Read;
+use std::ops::{Deref, DerefMut};
 
 use libimagstore::store::{FileLockEntry, Store};
+use libimagstore::storeid::StoreId;
 use libimagref::reference::Ref;
 use libimagref::flags::RefFlags;
+use libimagref::hasher::Hasher;
+use libimagerror::trace::MapErrTrace;
 
 use mailparse::{MailParseError, ParsedMail, parse_mail};
 
@@ -14,16 +18,16 @@ use hasher::MailHasher;
 use result::Result;
 use error::{MapErrInto, MailErrorKind as MEK};
 
-struct Buffer(String);
+struct Buffer(Vec<u8>);
 
 impl Buffer {
     pub fn parsed<'a>(&'a self) -> RResult<ParsedMail<'a>, MailParseError> {
-        parse_mail(self.0.as_bytes())
+        parse_mail(&self.0)
     }
 }
 
-impl From<String> for Buffer {
-    fn from(data: String) -> Buffer {
+impl From<Vec<u8>> for Buffer {
+    fn from(data: Vec<u8>) -> Buffer {
         Buffer(data)
     }
 }
@@ -45,9 +49,9 @@ impl<'a> Mail<'a> {
                     .map_err_into(MEK::RefHandlingError)
                     .and_then(|path| File::open(path).map_err_into(MEK::IOError))
                     .and_then(|mut file| {
-                        let mut s = String::new();
-                        file.read_to_string(&mut s)
-                            .map(|_| s)
+                        let mut v = vec![];
+                        file.read_to_end(&mut v)
+                            .map(|_| v)
                             .map_err_into(MEK::IOError)
                     })
                     .map(Buffer::from)
@@ -67,15 +71,45 @@ impl<'a> Mail<'a> {
 
     }
 
+    /// Check whether the passed Ref was imported as a mail, that is whether it has a content hash
+    /// created by the `MailHasher`
+    ///
+    /// The name of the hasher in `ref.hasher` is not checked, mails which were imported before it
+    /// was stored do not have it.
+    pub fn is_mail_ref(r: &Ref) -> Result<bool> {
+        let key = format!("ref.content_hash.{}", MailHasher::new().hash_name());
+        r.get_header()
+            .read(&key)
+            .map(|hash| hash.is_some())
+            .map_err_into(MEK::RefHandlingError)
+    }
+
+    /// Get all mails in the store
+    ///
+    /// Refs which are not mails and mails which cannot be read are skipped. The entry `except` is
+    /// skipped as well, because it cannot be borrowed twice.
+    pub fn all(store: &'a Store, except: Option<&StoreId>)
+        -> Result<Box<Iterator<Item = Mail<'a>> + 'a>>
+    {
+        let ids    = try!(store.retrieve_for_module("ref").map_err_into(MEK::FetchError));
+        let except = except.map(|id| id.local().clone());
+
+        Ok(Box::new(ids
+            .filter(move |id| except.as_ref().map(|e| id.local() != e).unwrap_or(true))
+            .filter_map(move |id| Ref::get(store, id).map_err_trace().ok())
+            .filter(|r| Mail::is_mail_ref(r).map_err_trace().unwrap_or(false))
+            .filter_map(|r| Mail::from_ref(r).map_err_trace().ok())))
+    }
+
     /// Implement me as TryFrom as soon as it is stable
     pub fn from_ref(r: Ref<'a>) -> Result<Mail> {
         r.fs_file()
             .map_err_into(MEK::RefHandlingError)
             .and_then(|path| File::open(path).map_err_into(MEK::IOError))
             .and_then(|mut file| {
-                let mut s = String::new();
-                file.read_to_string(&mut s)
-                    .map(|_| s)
+                let mut v = vec![];
+                file.read_to_end(&mut v)
+                    .map(|_| v)
                     .map_err_into(MEK::IOError)
             })
             .map(Buffer::from)
@@ -117,4 +151,211 @@ impl<'a> Mail<'a> {
         self.get_field("In-Reply-To")
     }
 
+    pub fn get_date(&self) -> Result<Option<String>> {
+        self.get_field("Date")
+    }
+
+    /// Find the mail in the store which this mail is a reply to
+    pub fn get_parent<'b>(&self, store: &'b Store) -> Result<Option<Mail<'b>>> {
+        let in_reply_to = match try!(self.get_in_reply_to()) {
+            Some(id) => String::from(id.trim()),
+            None     => return Ok(None),
+        };
+
+        let mut mails = try!(Mail::all(store, Some(self.get_location())));
+        Ok(mails.find(|m| {
+            m.get_message_id()
+                .map_err_trace()
+                .ok()
+                .and_then(|id| id)
+                .map(|id| id.trim() == in_reply_to)
+                .unwrap_or(false)
+        }))
+    }
+
+    /// Get the body of the mail
+    ///
+    /// For multipart mails, this is the body of the first part, which is the plain text part for
+    /// most mails.
+    pub fn get_body(&self) -> Result<String> {
+        fn first_part<'a, 'b>(mail: &'b ParsedMail<'a>) -> &'b ParsedMail<'a> {
+            match mail.subparts.first() {
+                Some(part) => first_part(part),
+                None       => mail,
+            }
+        }
+
+        self.1
+            .parsed()
+            .map_err_into(MEK::MailParsingError)
+            .and_then(|parsed| first_part(&parsed).get_body().map_err_into(MEK::MailParsingError))
+    }
+
+    /// Get the hash the mail can be opened with by `Mail::open()`
+    pub fn get_hash(&self) -> Result<String> {
+        self.0.get_path_hash().map_err_into(MEK::RefHandlingError)
+    }
+
+}
+
+impl<'a> Deref for Mail<'a> {
+    type Target = Ref<'a>;
+
+    fn deref(&self) -> &Ref<'a> {
+        &self.0
+    }
+
+}
+
+impl<'a> DerefMut for Mail<'a> {
+
+    fn deref_mut(&mut self) -> &mut Ref<'a> {
+        &mut self.0
+    }
+
+}
+
+impl<'a> Into<FileLockEntry<'a>> for Mail<'a> {
+
+    fn into(self) -> FileLockEntry<'a> {
+        self.0.into()
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use tempdir::TempDir;
+
+    use libimagstore::store::Store;
+    use libimagref::flags::RefFlags;
+    use libimagref::reference::Ref;
+    use libimagentrylink::internal::InternalLinker;
+    use libimagentrytag::tagable::Tagable;
+
+    use super::Mail;
+
+    static MAIL: &'static str = "From: alice@example.com\n\
+                                 To: bob@example.com\n\
+                                 Subject: Hello\n\
+                                 Date: Mon, 2 Jan 2017 10:00:00 +0100\n\
+                                 Message-ID: <1@example.com>\n\
+                                 \n\
+                                 Hi Bob\n";
+
+    static REPLY: &'static str = "From: bob@example.com\n\
+                                  To: alice@example.com\n\
+                                  Subject: Re: Hello\n\
+                                  Message-ID: <2@example.com>\n\
+                                  In-Reply-To: <1@example.com>\n\
+                                  \n\
+                                  Hi Alice\n";
+
+    fn get_store() -> Store {
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+        path
+    }
+
+    fn import(store: &Store, path: PathBuf) -> String {
+        Mail::import_from_path(store, path).unwrap().get_hash().unwrap()
+    }
+
+    #[test]
+    fn test_import_and_show() {
+        let dir   = TempDir::new("imag-mail").unwrap();
+        let store = get_store();
+        let hash  = import(&store, write(dir.path(), "mail", MAIL));
+
+        let mail = Mail::open(&store, &hash).unwrap().unwrap();
+        assert_eq!(mail.get_from().unwrap(), Some(String::from("alice@example.com")));
+        assert_eq!(mail.get_to().unwrap(), Some(String::from("bob@example.com")));
+        assert_eq!(mail.get_subject().unwrap(), Some(String::from("Hello")));
+        assert_eq!(mail.get_message_id().unwrap(), Some(String::from("<1@example.com>")));
+        assert_eq!(mail.get_in_reply_to().unwrap(), None);
+        assert_eq!(mail.get_body().unwrap().trim(), "Hi Bob");
+
+        assert!(Mail::open(&store, "unknown").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_is_mail_ref() {
+        let dir   = TempDir::new("imag-mail").unwrap();
+        let store = get_store();
+
+        let id = {
+            let mut mail = Mail::import_from_path(&store, write(dir.path(), "mail", MAIL)).unwrap();
+
+            // mails imported before the hasher was stored do not have `ref.hasher`
+            mail.get_header_mut().delete("ref.hasher").unwrap();
+            mail.get_location().clone()
+        };
+        assert!(Mail::is_mail_ref(&Ref::get(&store, id).unwrap()).unwrap());
+
+        let flags = RefFlags::default().with_content_hashing(true);
+        let file  = Ref::create(&store, write(dir.path(), "file", "text"), flags).unwrap();
+        assert!(!Mail::is_mail_ref(&file).unwrap());
+    }
+
+    #[test]
+    fn test_all() {
+        let dir   = TempDir::new("imag-mail").unwrap();
+        let store = get_store();
+        import(&store, write(dir.path(), "mail", MAIL));
+        import(&store, write(dir.path(), "reply", REPLY));
+
+        let flags = RefFlags::default().with_content_hashing(true);
+        let _     = Ref::create(&store, write(dir.path(), "file", "text"), flags).unwrap();
+
+        let mut subjects = Mail::all(&store, None)
+            .unwrap()
+            .map(|mail| mail.get_subject().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        subjects.sort();
+        assert_eq!(subjects, vec!["Hello", "Re: Hello"]);
+
+        let mail = Mail::all(&store, None).unwrap().next().unwrap();
+        assert_eq!(Mail::all(&store, Some(mail.get_location())).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_tag() {
+        let dir   = TempDir::new("imag-mail").unwrap();
+        let store = get_store();
+        let hash  = import(&store, write(dir.path(), "mail", MAIL));
+
+        {
+            let mut mail = Mail::open(&store, &hash).unwrap().unwrap();
+            mail.add_tag(String::from("inbox")).unwrap();
+        }
+
+        let mail = Mail::open(&store, &hash).unwrap().unwrap();
+        assert_eq!(mail.get_tags().unwrap(), vec![String::from("inbox")]);
+    }
+
+    #[test]
+    fn test_link_thread() {
+        let dir   = TempDir::new("imag-mail").unwrap();
+        let store = get_store();
+        let first = import(&store, write(dir.path(), "mail", MAIL));
+        let reply = import(&store, write(dir.path(), "reply", REPLY));
+
+        let mut reply  = Mail::open(&store, &reply).unwrap().unwrap();
+        let mut parent = reply.get_parent(&store).unwrap().unwrap();
+        assert_eq!(parent.get_hash().unwrap(), first);
+        assert!(parent.get_parent(&store).unwrap().is_none());
+
+        reply.add_internal_link(&mut parent).unwrap();
+        let links = reply.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].local(), parent.get_location().local());
+    }
 }
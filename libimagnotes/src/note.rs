@@ -30,6 +30,7 @@ use libimagstore::storeid::StoreId;
 use libimagstore::storeid::StoreIdIterator;
 use libimagstore::store::FileLockEntry;
 use libimagstore::store::Store;
+use libimagstore::schema::{FieldType, Schema};
 use libimagentrytag::tag::{Tag, TagSlice};
 use libimagentrytag::tagable::Tagable;
 use libimagentrytag::result::Result as TagResult;
@@ -40,6 +41,12 @@ use error::NoteError as NE;
 use error::NoteErrorKind as NEK;
 use error::MapErrInto;
 
+/// The header schema of note entries, to be registered with `Store::register_schema()`
+pub fn header_schema() -> Schema {
+    Schema::new("notes")
+        .required("note.name", FieldType::String)
+}
+
 #[derive(Debug)]
 pub struct Note<'a> {
     entry: FileLockEntry<'a>,
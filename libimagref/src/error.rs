@@ -44,7 +44,8 @@ generate_error_module!(
         RefTargetFileCannotBeOpened => "Ref Target File cannot be open()ed",
         RefTargetCannotReadPermissions => "Ref Target: Cannot read permissions",
 
-        RefHashingError => "Error while hashing"
+        RefHashingError => "Error while hashing",
+        UnknownHasher   => "Unknown hasher"
     );
 );
 
@@ -36,6 +36,14 @@ pub trait Hasher {
     fn hash_name(&self) -> &'static str;
     fn create_hash<R: Read>(&mut self, pb: &PathBuf, contents: &mut R) -> Result<String>;
 
+    /// The number of bytes at the start of the file which are hashed, if not the whole file is
+    /// hashed
+    ///
+    /// It is stored in `ref.hashed_bytes`, so the hasher can be created again for the ref.
+    fn hashed_bytes(&self) -> Option<usize> {
+        None
+    }
+
 }
 
 pub struct DefaultHasher {
@@ -0,0 +1,52 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::io::Read;
+use std::path::PathBuf;
+
+use crypto::blake2b::Blake2b;
+use crypto::digest::Digest;
+
+use hasher::Hasher;
+use hashers::digest_reader;
+use result::Result;
+
+/// A hasher which hashes the whole content of the file with BLAKE2b, with a 512 bit digest
+pub struct Blake2Hasher;
+
+impl Blake2Hasher {
+
+    pub fn new() -> Blake2Hasher {
+        Blake2Hasher
+    }
+
+}
+
+impl Hasher for Blake2Hasher {
+
+    fn hash_name(&self) -> &'static str {
+        "blake2b"
+    }
+
+    fn create_hash<R: Read>(&mut self, _: &PathBuf, contents: &mut R) -> Result<String> {
+        let mut hasher = Blake2b::new(64);
+        try!(digest_reader(&mut hasher, contents));
+        Ok(hasher.result_str())
+    }
+
+}
@@ -0,0 +1,80 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::io::Read;
+use std::path::PathBuf;
+
+use mailparse::parse_mail;
+
+use libimagerror::into::IntoError;
+
+use error::RefErrorKind as REK;
+use error::MapErrInto;
+use hasher::{DefaultHasher, Hasher};
+use result::Result;
+
+/// The hasher of the refs to mails
+///
+/// Only the `Subject`, `From` and `To` headers of the mail are hashed, so the hash does not change
+/// if a mail client adds headers or flags to the mail.
+pub struct MailHasher {
+    defaulthasher: DefaultHasher,
+}
+
+impl MailHasher {
+
+    pub fn new() -> MailHasher {
+        MailHasher { defaulthasher: DefaultHasher::new() }
+    }
+
+}
+
+impl Hasher for MailHasher {
+
+    fn hash_name(&self) -> &'static str {
+        "default_mail_hasher"
+    }
+
+    fn create_hash<R: Read>(&mut self, pb: &PathBuf, c: &mut R) -> Result<String> {
+        let mut v = vec![];
+        try!(c.read_to_end(&mut v).map_err_into(REK::IOError));
+
+        let mail = try!(parse_mail(&v)
+            .map_err(Box::new)
+            .map_err(|e| REK::RefHashingError.into_error_with_cause(e)));
+
+        let mut v = vec![];
+        for hdr in mail.headers.iter() {
+            let is_hashed = hdr.get_key()
+                .map(|k| k == "Subject" || k == "From" || k == "To")
+                .unwrap_or(false);
+
+            if is_hashed {
+                let s = try!(hdr.get_value()
+                    .map_err(Box::new)
+                    .map_err(|e| REK::RefHashingError.into_error_with_cause(e)));
+
+                v.push(s);
+            }
+        }
+        let s : String = v.join("");
+
+        self.defaulthasher.create_hash(pb, &mut s.as_bytes())
+    }
+
+}
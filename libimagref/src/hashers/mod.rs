@@ -17,4 +17,220 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+//! Implementations of the `Hasher` trait
+//!
+//! The hashers which can be selected by their name (for example on the commandline or in the
+//! header of a ref) are collected in `NamedHasher`. It also knows the hashers which are used by
+//! other modules, so every ref can be re-hashed with the hasher it was created with.
+
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+
+use crypto::digest::Digest;
+
+use libimagerror::into::IntoError;
+
+use error::RefErrorKind as REK;
+use hasher::{DefaultHasher, Hasher};
+use result::Result;
+
+pub mod blake2;
+pub mod mail;
 pub mod nbytes;
+pub mod sampling;
+pub mod sha256;
+
+use self::blake2::Blake2Hasher;
+use self::mail::MailHasher;
+use self::nbytes::NBytesHasher;
+use self::sampling::SamplingHasher;
+use self::sha256::Sha256Hasher;
+
+/// A hasher which is selected by its name
+///
+/// A new instance of the hasher is used for each file, so a `NamedHasher` can hash any number of
+/// files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamedHasher {
+    Default,
+    Sha256,
+    Blake2,
+    Sampling,
+
+    /// The hasher of mails, see `MailHasher`. It cannot be selected for new refs.
+    Mail,
+
+    /// The `NBytesHasher`, hashing the given number of bytes. It cannot be selected for new refs.
+    NBytes(usize),
+}
+
+impl NamedHasher {
+
+    /// Get the hasher with the name `name`, as returned by `Hasher::hash_name()`
+    ///
+    /// Only the hashers which can be selected for new refs are returned.
+    pub fn from_name(name: &str) -> Option<NamedHasher> {
+        NamedHasher::all().into_iter().find(|h| h.hash_name() == name)
+    }
+
+    /// Get the hasher of a ref, from the name and the number of hashed bytes stored in its header
+    ///
+    /// Unlike `NamedHasher::from_name()`, this also returns the hashers which cannot be selected
+    /// for new refs. The `NBytesHasher` is only returned if the number of hashed bytes is known.
+    pub fn from_stored(name: &str, hashed_bytes: Option<usize>) -> Option<NamedHasher> {
+        if name == MailHasher::new().hash_name() {
+            Some(NamedHasher::Mail)
+        } else if name == NBytesHasher::new(0).hash_name() {
+            hashed_bytes.map(NamedHasher::NBytes)
+        } else {
+            NamedHasher::from_name(name)
+        }
+    }
+
+    /// Get all hashers which can be selected by name for new refs
+    pub fn all() -> Vec<NamedHasher> {
+        vec![NamedHasher::Default, NamedHasher::Sha256, NamedHasher::Blake2, NamedHasher::Sampling]
+    }
+
+    /// Get the names of all hashers which can be selected by name
+    pub fn names() -> Vec<&'static str> {
+        NamedHasher::all().into_iter().map(|h| h.hash_name()).collect()
+    }
+
+}
+
+impl Hasher for NamedHasher {
+
+    fn hash_name(&self) -> &'static str {
+        match *self {
+            NamedHasher::Default   => DefaultHasher::new().hash_name(),
+            NamedHasher::Sha256    => Sha256Hasher::new().hash_name(),
+            NamedHasher::Blake2    => Blake2Hasher::new().hash_name(),
+            NamedHasher::Sampling  => SamplingHasher::new().hash_name(),
+            NamedHasher::Mail      => MailHasher::new().hash_name(),
+            NamedHasher::NBytes(n) => NBytesHasher::new(n).hash_name(),
+        }
+    }
+
+    fn create_hash<R: Read>(&mut self, pb: &PathBuf, contents: &mut R) -> Result<String> {
+        match *self {
+            NamedHasher::Default   => DefaultHasher::new().create_hash(pb, contents),
+            NamedHasher::Sha256    => Sha256Hasher::new().create_hash(pb, contents),
+            NamedHasher::Blake2    => Blake2Hasher::new().create_hash(pb, contents),
+            NamedHasher::Sampling  => SamplingHasher::new().create_hash(pb, contents),
+            NamedHasher::Mail      => MailHasher::new().create_hash(pb, contents),
+            NamedHasher::NBytes(n) => NBytesHasher::new(n).create_hash(pb, contents),
+        }
+    }
+
+    fn hashed_bytes(&self) -> Option<usize> {
+        match *self {
+            NamedHasher::NBytes(n) => Some(n),
+            _                      => None,
+        }
+    }
+
+}
+
+/// Feed everything `reader` yields into `digest`
+fn digest_reader<D: Digest, R: Read>(digest: &mut D, reader: &mut R) -> Result<()> {
+    let mut buf = [0; 4096];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0)  => return Ok(()),
+            Ok(n)  => digest.input(&buf[..n]),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(REK::IOError.into_error_with_cause(Box::new(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use hasher::Hasher;
+    use super::NamedHasher;
+    use super::sampling::SAMPLE_SIZE;
+
+    fn hash(mut hasher: NamedHasher, path: &PathBuf) -> String {
+        let mut file = File::open(path).unwrap();
+        hasher.create_hash(path, &mut file).unwrap()
+    }
+
+    fn write(path: &PathBuf, content: &[u8]) {
+        File::create(path).unwrap().write_all(content).unwrap();
+    }
+
+    #[test]
+    fn test_sha256_and_blake2() {
+        let dir  = TempDir::new("imag-ref-hashers").unwrap();
+        let path = dir.path().join("abc");
+        write(&path, b"abc");
+
+        assert_eq!(hash(NamedHasher::Sha256, &path),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash(NamedHasher::Blake2, &path),
+                   "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+                    7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923");
+    }
+
+    #[test]
+    fn test_sampling_small_file() {
+        let dir  = TempDir::new("imag-ref-hashers").unwrap();
+        let path = dir.path().join("abc");
+        write(&path, b"abc");
+
+        // SHA-256 of the size followed by the content
+        assert_eq!(hash(NamedHasher::Sampling, &path),
+                   "7b0393befdc28ff2a7c5e08fa8a38a4c845da5c600476eb01ae7b865fa344f18");
+    }
+
+    #[test]
+    fn test_sampling_ignores_the_middle_of_large_files() {
+        let dir  = TempDir::new("imag-ref-hashers").unwrap();
+        let path = dir.path().join("large");
+        let size = 3 * SAMPLE_SIZE as usize;
+
+        let mut content = vec![0; size];
+        write(&path, &content);
+        let original = hash(NamedHasher::Sampling, &path);
+
+        content[size / 2] = 1;
+        write(&path, &content);
+        assert_eq!(hash(NamedHasher::Sampling, &path), original);
+
+        content[size - 1] = 1;
+        write(&path, &content);
+        assert!(hash(NamedHasher::Sampling, &path) != original);
+
+        // the size is hashed as well
+        content[size - 1] = 0;
+        content.insert(size / 2, 0);
+        write(&path, &content);
+        assert!(hash(NamedHasher::Sampling, &path) != original);
+    }
+
+    #[test]
+    fn test_from_name() {
+        for hasher in NamedHasher::all() {
+            assert_eq!(NamedHasher::from_name(hasher.hash_name()), Some(hasher));
+        }
+        assert_eq!(NamedHasher::from_name("default_mail_hasher"), None);
+        assert_eq!(NamedHasher::from_name("n-bytes-hasher"), None);
+    }
+
+    #[test]
+    fn test_from_stored() {
+        assert_eq!(NamedHasher::from_stored("sha256", None), Some(NamedHasher::Sha256));
+        assert_eq!(NamedHasher::from_stored("default_mail_hasher", None), Some(NamedHasher::Mail));
+        assert_eq!(NamedHasher::from_stored("n-bytes-hasher", Some(16)),
+                   Some(NamedHasher::NBytes(16)));
+        assert_eq!(NamedHasher::from_stored("n-bytes-hasher", None), None);
+        assert_eq!(NamedHasher::from_stored("unknown", None), None);
+    }
+}
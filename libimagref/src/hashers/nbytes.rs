@@ -67,5 +67,9 @@ impl Hasher for NBytesHasher {
         Ok(self.hasher.result_str())
     }
 
+    fn hashed_bytes(&self) -> Option<usize> {
+        Some(self.n)
+    }
+
 }
 
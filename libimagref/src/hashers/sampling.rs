@@ -0,0 +1,73 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::cmp::max;
+use std::fs::{File, metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+
+use error::RefErrorKind as REK;
+use error::MapErrInto;
+use hasher::Hasher;
+use hashers::digest_reader;
+use result::Result;
+
+/// The number of bytes which are hashed from the start and from the end of the file
+pub const SAMPLE_SIZE : u64 = 64 * 1024;
+
+/// A fast hasher for very large files, like videos or disk images
+///
+/// Only the size of the file, its first and its last `SAMPLE_SIZE` bytes are hashed (with
+/// SHA-256), so changes in the middle of the file are not noticed.
+pub struct SamplingHasher;
+
+impl SamplingHasher {
+
+    pub fn new() -> SamplingHasher {
+        SamplingHasher
+    }
+
+}
+
+impl Hasher for SamplingHasher {
+
+    fn hash_name(&self) -> &'static str {
+        "sampling"
+    }
+
+    fn create_hash<R: Read>(&mut self, pb: &PathBuf, contents: &mut R) -> Result<String> {
+        let size = try!(metadata(pb).map_err_into(REK::IOError)).len();
+
+        let mut hasher = Sha256::new();
+        hasher.input_str(&format!("{}", size));
+        try!(digest_reader(&mut hasher, &mut contents.by_ref().take(SAMPLE_SIZE)));
+
+        if size > SAMPLE_SIZE {
+            let mut file = try!(File::open(pb).map_err_into(REK::IOError));
+            let offset   = max(SAMPLE_SIZE, size - SAMPLE_SIZE);
+            try!(file.seek(SeekFrom::Start(offset)).map_err_into(REK::IOError));
+            try!(digest_reader(&mut hasher, &mut file));
+        }
+
+        Ok(hasher.result_str())
+    }
+
+}
@@ -0,0 +1,54 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+use std::io::Read;
+use std::path::PathBuf;
+
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+
+use hasher::Hasher;
+use hashers::digest_reader;
+use result::Result;
+
+/// A hasher which hashes the whole content of the file with SHA-256
+///
+/// Unlike the `DefaultHasher`, it also works for files which are not valid UTF-8.
+pub struct Sha256Hasher;
+
+impl Sha256Hasher {
+
+    pub fn new() -> Sha256Hasher {
+        Sha256Hasher
+    }
+
+}
+
+impl Hasher for Sha256Hasher {
+
+    fn hash_name(&self) -> &'static str {
+        "sha256"
+    }
+
+    fn create_hash<R: Read>(&mut self, _: &PathBuf, contents: &mut R) -> Result<String> {
+        let mut hasher = Sha256::new();
+        try!(digest_reader(&mut hasher, contents));
+        Ok(hasher.result_str())
+    }
+
+}
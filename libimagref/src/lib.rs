@@ -34,10 +34,12 @@
 #[macro_use] extern crate log;
 extern crate crypto;
 extern crate itertools;
+extern crate mailparse;
 extern crate semver;
 extern crate toml;
 extern crate version;
 extern crate walkdir;
+#[cfg(test)] extern crate tempdir;
 
 #[macro_use] extern crate libimagstore;
 #[macro_use] extern crate libimagerror;
@@ -51,5 +53,7 @@ pub mod flags;
 pub mod hasher;
 pub mod hashers;
 pub mod lister;
+pub mod migration;
 pub mod reference;
+pub mod repair;
 pub mod result;
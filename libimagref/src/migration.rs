@@ -0,0 +1,218 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Re-hashing the content of existing refs
+//!
+//! Refs which were created before the name of the hasher was stored in `ref.hasher` were hashed
+//! with the `DefaultHasher`. The `RehashMigration` hashes the referenced files of these refs with
+//! another hasher and records the hasher in the header. It is applied by `Store::migrate()`.
+//!
+//! Refs whose hasher cannot be selected for new refs, like the refs to mails, are left untouched:
+//! other modules rely on their hasher.
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use semver::Version;
+use toml::Value;
+
+use libimagstore::error::StoreErrorKind as SEK;
+use libimagstore::migration::Migration;
+use libimagstore::store::Entry;
+use libimagstore::store::EntryHeader;
+use libimagstore::store::Result as StoreResult;
+use libimagerror::into::IntoError;
+
+use error::RefErrorKind as REK;
+use error::MapErrInto;
+use hasher::Hasher;
+use hashers::NamedHasher;
+use reference::hasher_name;
+use result::Result;
+
+/// The version of imag which started to store the name of the hasher in `ref.hasher`
+const HASHER_NAME_VERSION : &'static str = "0.3.0";
+
+/// Re-hash the content of refs with the hasher `hasher`
+///
+/// Refs without a content hash are left untouched. If the referenced file cannot be hashed (for
+/// example because it was moved), only the hasher of the existing hash is recorded.
+///
+/// The migration applies to entries which were written by an imag older than 0.3.0.
+#[derive(Debug)]
+pub struct RehashMigration {
+    hasher: NamedHasher,
+}
+
+impl RehashMigration {
+
+    pub fn new(hasher: NamedHasher) -> RehashMigration {
+        RehashMigration { hasher: hasher }
+    }
+
+}
+
+impl Migration for RehashMigration {
+
+    fn module(&self) -> Option<&str> {
+        Some("ref")
+    }
+
+    fn version(&self) -> Version {
+        Version::parse(HASHER_NAME_VERSION).unwrap()
+    }
+
+    fn migrate(&self, entry: &mut Entry) -> StoreResult<()> {
+        rehash(entry, self.hasher)
+            .map_err(Box::new)
+            .map_err(|e| SEK::MigrationError.into_error_with_cause(e))
+    }
+
+}
+
+fn rehash(entry: &mut Entry, mut hasher: NamedHasher) -> Result<()> {
+    let header = entry.get_header_mut();
+
+    let read = |header: &EntryHeader, key: &str| {
+        header.read(key).map_err_into(REK::HeaderFieldReadError)
+    };
+
+    let hashes = match try!(read(&*header, "ref.content_hash")) {
+        Some(Value::Table(t)) => t,
+        Some(_)               => return Err(REK::HeaderTypeError.into_error()),
+        None                  => return Ok(()),
+    };
+
+    let current = try!(hasher_name(&*header));
+    if hashes.is_empty() || current == hasher.hash_name() {
+        return Ok(());
+    }
+
+    if NamedHasher::from_name(&current).is_none() {
+        debug!("Not re-hashing a ref hashed with {}", current);
+        return Ok(());
+    }
+
+    let path = match try!(read(&*header, "ref.path")) {
+        Some(Value::String(s)) => PathBuf::from(s),
+        Some(_)                => return Err(REK::HeaderTypeError.into_error()),
+        None                   => return Err(REK::HeaderFieldMissingError.into_error()),
+    };
+
+    let hash = File::open(&path)
+        .map_err_into(REK::IOError)
+        .and_then(|mut file| hasher.create_hash(&path, &mut file));
+
+    let name = match hash {
+        Ok(hash) => {
+            let key = format!("ref.content_hash.{}", hasher.hash_name());
+            try!(header.set(&key, Value::String(hash)).map_err_into(REK::HeaderFieldWriteError));
+            String::from(hasher.hash_name())
+        },
+        Err(e) => {
+            warn!("Cannot re-hash {:?}, keeping the {} hash: {:?}", path, current, e);
+            current
+        },
+    };
+
+    header.set("ref.hasher", Value::String(name))
+        .map(|_| ())
+        .map_err_into(REK::HeaderFieldWriteError)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+    use toml::Value;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+
+    use flags::RefFlags;
+    use hasher::Hasher;
+    use hashers::NamedHasher;
+    use hashers::mail::MailHasher;
+    use reference::Ref;
+    use super::RehashMigration;
+
+    fn get_store() -> Store {
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
+    }
+
+    /// Create a ref to `path`, as it was created before the hasher was stored in `ref.hasher`
+    fn create_old_ref<H: Hasher>(store: &Store, path: PathBuf, h: H) -> StoreId {
+        let flags = RefFlags::default().with_content_hashing(true);
+        let mut r = Ref::create_with_hasher(store, path, flags, h).unwrap();
+        r.get_header_mut().delete("ref.hasher").unwrap();
+        r.get_header_mut().set("imag.version", Value::String(String::from("0.2.0"))).unwrap();
+        r.get_location().clone()
+    }
+
+    fn migrate(store: &Store, id: StoreId) -> Ref {
+        let migration = Box::new(RehashMigration::new(NamedHasher::Sha256));
+        assert!(store.register_migration(migration).is_ok());
+
+        let reports = store.migrate(false).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].id().local(), id.local());
+        assert_eq!(reports[0].applied(), 1);
+        Ref::get(store, id).unwrap()
+    }
+
+    #[test]
+    fn test_rehash_old_ref() {
+        let dir  = TempDir::new("imag-ref-migration").unwrap();
+        let path = dir.path().join("file");
+        File::create(&path).unwrap().write_all(b"abc").unwrap();
+
+        let store = get_store();
+        let id    = create_old_ref(&store, path, NamedHasher::Default);
+        assert_eq!(Ref::get(&store, id.clone()).unwrap().get_hasher().unwrap(),
+                   NamedHasher::Default);
+
+        let r = migrate(&store, id);
+        assert_eq!(r.get_hasher().unwrap(), NamedHasher::Sha256);
+        assert_eq!(r.get_stored_hash().unwrap(),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert!(r.fs_link_valid_hash().unwrap());
+    }
+
+    #[test]
+    fn test_mail_ref_is_not_rehashed() {
+        let dir  = TempDir::new("imag-ref-migration").unwrap();
+        let path = dir.path().join("mail");
+        File::create(&path).unwrap()
+            .write_all(b"From: a@example.com\nTo: b@example.com\nSubject: Hi\n\nHello\n")
+            .unwrap();
+
+        let store = get_store();
+        let id    = create_old_ref(&store, path, MailHasher::new());
+        assert_eq!(Ref::get(&store, id.clone()).unwrap().get_hasher().unwrap(),
+                   NamedHasher::Mail);
+
+        let r = migrate(&store, id);
+        assert_eq!(r.get_header().read("ref.hasher").unwrap(), None);
+        assert_eq!(r.get_header().read("ref.content_hash.sha256").unwrap(), None);
+        assert_eq!(r.get_hasher().unwrap(), NamedHasher::Mail);
+        assert!(r.fs_link_valid_hash().unwrap());
+    }
+}
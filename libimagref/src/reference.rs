@@ -29,10 +29,12 @@ use std::fmt::{Display, Error as FmtError, Formatter};
 use std::fs::Permissions;
 use std::result::Result as RResult;
 
+use libimagstore::store::EntryHeader;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
 use libimagstore::storeid::IntoStoreId;
 use libimagstore::store::Store;
+use libimagstore::schema::{FieldType, Schema};
 use libimagerror::into::IntoError;
 
 use toml::Value;
@@ -42,8 +44,45 @@ use error::MapErrInto;
 use flags::RefFlags;
 use result::Result;
 use hasher::*;
+use hashers::NamedHasher;
 use module_path::ModuleEntryPath;
 
+/// The header schema of ref entries, to be registered with `Store::register_schema()`
+pub fn header_schema() -> Schema {
+    Schema::new("ref")
+        .required("ref.path", FieldType::String)
+        .required("ref.content_hash", FieldType::Table)
+        .optional("ref.permissions", FieldType::Table)
+        .optional("ref.hasher", FieldType::String)
+        .optional("ref.hashed_bytes", FieldType::Integer)
+}
+
+/// Get the name of the hasher the content hash in the header of a ref was created with
+///
+/// Refs which were created before the name of the hasher was stored in `ref.hasher` have a single
+/// content hash, which is named after its hasher. Without any content hash, the `DefaultHasher` is
+/// assumed.
+pub fn hasher_name(header: &EntryHeader) -> Result<String> {
+    match header.read("ref.hasher") {
+        Ok(Some(Value::String(s))) => return Ok(s),
+        Ok(Some(_)) => return Err(REK::HeaderTypeError.into_error()),
+        Ok(None)    => {},
+        Err(e)      => return Err(REK::StoreReadError.into_error_with_cause(Box::new(e))),
+    }
+
+    let hashes = match header.read("ref.content_hash") {
+        Ok(Some(Value::Table(t))) => t,
+        Ok(_)  => BTreeMap::new(),
+        Err(e) => return Err(REK::StoreReadError.into_error_with_cause(Box::new(e))),
+    };
+
+    let mut names = hashes.into_iter().map(|(name, _)| name);
+    match (names.next(), names.next()) {
+        (Some(name), None) => Ok(name),
+        _                  => Ok(String::from(DefaultHasher::new().hash_name())),
+    }
+}
+
 #[derive(Debug)]
 pub struct Ref<'a>(FileLockEntry<'a>);
 
@@ -192,6 +231,12 @@ impl<'a> Ref<'a> {
                 Some((String::from("ref.path"),         Value::String(canonical_path))),
                 Some((String::from("ref.content_hash"), Value::Table(BTreeMap::new()))),
 
+                content_hash.as_ref().map(|_| {
+                    (String::from("ref.hasher"), Value::String(String::from(h.hash_name())))
+                }),
+                content_hash.as_ref().and_then(|_| h.hashed_bytes()).map(|n| {
+                    (String::from("ref.hashed_bytes"), Value::Integer(n as i64))
+                }),
                 content_hash.map(|hash| {
                     (format!("ref.content_hash.{}", h.hash_name()), Value::String(hash))
                 }),
@@ -263,15 +308,39 @@ impl<'a> Ref<'a> {
             })
     }
 
+    /// Get the name of the hasher the content hash of the ref object was created with
+    ///
+    /// See `hasher_name()` for refs which were created before the name of the hasher was stored.
+    pub fn get_hasher_name(&self) -> Result<String> {
+        hasher_name(self.0.get_header())
+    }
+
+    /// Get the hasher the content hash of the ref object was created with
+    pub fn get_hasher(&self) -> Result<NamedHasher> {
+        let name  = try!(self.get_hasher_name());
+        let bytes = match self.0.get_header().read("ref.hashed_bytes") {
+            Ok(Some(Value::Integer(n))) if n >= 0 => Some(n as usize),
+            Ok(Some(_)) => return Err(REK::HeaderTypeError.into_error()),
+            Ok(None)    => None,
+            Err(e)      => return Err(REK::StoreReadError.into_error_with_cause(Box::new(e))),
+        };
+
+        NamedHasher::from_stored(&name, bytes).ok_or(REK::UnknownHasher.into_error())
+    }
+
     /// Get the hash of the link target which is stored in the ref object
     pub fn get_stored_hash(&self) -> Result<String> {
-        self.get_stored_hash_with_hasher(&DefaultHasher::new())
+        self.get_hasher_name().and_then(|name| self.get_stored_hash_by_name(&name))
     }
 
     /// Get the hahs of the link target which is stored in the ref object, which is hashed with a
     /// custom Hasher instance.
     pub fn get_stored_hash_with_hasher<H: Hasher>(&self, h: &H) -> Result<String> {
-        match self.0.get_header().read(&format!("ref.content_hash.{}", h.hash_name())[..]) {
+        self.get_stored_hash_by_name(h.hash_name())
+    }
+
+    fn get_stored_hash_by_name(&self, name: &str) -> Result<String> {
+        match self.0.get_header().read(&format!("ref.content_hash.{}", name)[..]) {
             // content hash stored...
             Ok(Some(Value::String(s))) => Ok(s),
 
@@ -287,8 +356,10 @@ impl<'a> Ref<'a> {
     }
 
     /// Get the hash of the link target by reading the link target and hashing the contents
+    ///
+    /// The hasher the stored hash was created with is used, so the hashes can be compared.
     pub fn get_current_hash(&self) -> Result<String> {
-        self.get_current_hash_with_hasher(DefaultHasher::new())
+        self.get_hasher().and_then(|h| self.get_current_hash_with_hasher(h))
     }
 
     /// Get the hash of the link target by reading the link target and hashing the contents with the
@@ -384,13 +455,14 @@ impl<'a> Ref<'a> {
     /// Update the Ref by re-checking the file from FS
     /// This errors if the file is not present or cannot be read()
     pub fn update_ref(&mut self) -> Result<()> {
-        self.update_ref_with_hasher(&DefaultHasher::new())
+        let h = try!(self.get_hasher());
+        self.update_ref_with_hasher(&h)
     }
 
     /// Update the Ref by re-checking the file from FS using the passed Hasher instance
     /// This errors if the file is not present or cannot be read()
     pub fn update_ref_with_hasher<H: Hasher>(&mut self, h: &H) -> Result<()> {
-        let current_hash = try!(self.get_current_hash()); // uses the default hasher
+        let current_hash = try!(self.get_current_hash()); // uses the stored hasher
         let current_perm = try!(self.get_current_permissions());
 
         try!(self.0
@@ -420,6 +492,24 @@ impl<'a> Ref<'a> {
         }
     }
 
+    /// Point the Ref to the file at `pb`, for example because the file was moved
+    ///
+    /// Only the path is changed, neither the hashes nor the id of the Ref are touched.
+    pub fn set_fs_file(&mut self, pb: PathBuf) -> Result<()> {
+        let can = try!(pb.canonicalize()
+            .map_err(Box::new)
+            .map_err(|e| REK::PathCanonicalizationError.into_error_with_cause(e)));
+
+        let can = try!(can.to_str().map(String::from).ok_or(REK::PathUTF8Error.into_error()));
+
+        self.0
+            .get_header_mut()
+            .set("ref.path", Value::String(can))
+            .map(|_| ())
+            .map_err(Box::new)
+            .map_err(|e| REK::StoreWriteError.into_error_with_cause(e))
+    }
+
     /// Check whether there is a reference to the file at `pb`
     pub fn exists(store: &Store, pb: PathBuf) -> Result<bool> {
         pb.canonicalize()
@@ -483,7 +573,7 @@ impl<'a> Ref<'a> {
     ///
     /// This option causes heavy I/O as it recursively searches the Filesystem.
     pub fn refind(&self, search_roots: Option<Vec<PathBuf>>) -> Result<PathBuf> {
-        self.refind_with_hasher(search_roots, DefaultHasher::new())
+        self.get_hasher().and_then(|h| self.refind_with_hasher(search_roots, h))
     }
 
     pub fn refind_with_hasher<H: Hasher>(&self, search_roots: Option<Vec<PathBuf>>, mut h: H)
@@ -0,0 +1,265 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Re-finding the files of dead refs in bulk
+//!
+//! `Ref::refind()` walks the search roots for every single ref. To repair many refs at once, the
+//! search roots are walked only once here, building a `HashIndex` of the content hashes of all
+//! files below them, which is then used to look up the files of all dead refs.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::PathBuf;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagerror::into::IntoError;
+use libimagerror::trace::trace_error;
+
+use walkdir::WalkDir;
+
+use error::RefErrorKind as REK;
+use error::MapErrInto;
+use hasher::Hasher;
+use hashers::NamedHasher;
+use reference::Ref;
+use result::Result;
+
+/// A map from content hashes to the files with this content
+///
+/// The hashes are kept per hasher, so the index can hold the hashes of several hashers at once.
+#[derive(Debug, Default)]
+pub struct HashIndex {
+    hashes: BTreeMap<(String, String), Vec<PathBuf>>,
+}
+
+impl HashIndex {
+
+    /// Build the index by hashing all files below `roots` with each of `hashers`
+    ///
+    /// The roots are walked only once. Files which cannot be read or hashed are skipped.
+    pub fn build(roots: &[PathBuf], hashers: &[NamedHasher]) -> HashIndex {
+        let mut index = HashIndex::default();
+
+        for root in roots {
+            debug!("Indexing files below {:?}", root);
+            for entry in WalkDir::new(root).follow_links(false) {
+                let path = match entry {
+                    Ok(entry) => PathBuf::from(entry.path()),
+                    Err(e) => {
+                        debug!("Cannot walk: {:?}", e);
+                        continue;
+                    },
+                };
+
+                if !path.is_file() {
+                    continue;
+                }
+
+                for hasher in hashers {
+                    let hash = File::open(&path)
+                        .map_err_into(REK::IOError)
+                        .and_then(|mut file| {
+                            let mut hasher = *hasher;
+                            hasher.create_hash(&path, &mut file)
+                        });
+
+                    match hash {
+                        Ok(hash) => index.hashes
+                            .entry((String::from(hasher.hash_name()), hash))
+                            .or_insert_with(Vec::new)
+                            .push(path.clone()),
+                        Err(e) => debug!("Cannot hash {:?}: {:?}", path, e),
+                    }
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Get the number of distinct hashes in the index
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Get the files with the content hash `hash`, as created by the hasher named `hasher`
+    pub fn find(&self, hasher: &str, hash: &str) -> Option<&Vec<PathBuf>> {
+        self.hashes.get(&(String::from(hasher), String::from(hash)))
+    }
+
+}
+
+/// A ref whose file does not exist anymore
+#[derive(Debug, Clone)]
+pub struct DeadRef {
+    id: StoreId,
+    path: PathBuf,
+    hasher: Option<NamedHasher>,
+    found: Option<PathBuf>,
+}
+
+impl DeadRef {
+
+    /// Get the id of the ref entry
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// Get the path the ref points to
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Get the hasher the content hash of the ref was created with, if the ref has a content hash
+    pub fn hasher(&self) -> Option<NamedHasher> {
+        self.hasher
+    }
+
+    /// Get the path where the file of the ref was found, if it was found
+    pub fn found(&self) -> Option<&PathBuf> {
+        self.found.as_ref()
+    }
+
+}
+
+/// Find all dead refs in the store
+///
+/// The files of the dead refs are looked up in `index` by their content hash. Refs which do not
+/// store a content hash cannot be found.
+pub fn find_dead_refs(store: &Store, index: &HashIndex) -> Result<Vec<DeadRef>> {
+    let ids = try!(store.retrieve_for_module("ref").map_err_into(REK::StoreReadError));
+    let mut dead = vec![];
+
+    for id in ids {
+        let r = match Ref::get(store, id.clone()) {
+            Ok(r) => r,
+            Err(e) => {
+                trace_error(&e);
+                continue;
+            },
+        };
+
+        if !try!(r.is_dangling()) {
+            continue;
+        }
+
+        let hasher = match r.get_hasher().and_then(|h| r.get_stored_hash().map(|hash| (h, hash))) {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                debug!("No content hash for {:?}: {:?}", id, e);
+                None
+            },
+        };
+
+        let found = hasher.as_ref().and_then(|&(ref h, ref hash)| {
+            index.find(h.hash_name(), hash).and_then(|files| files.first().cloned())
+        });
+
+        dead.push(DeadRef {
+            id: id,
+            path: try!(r.fs_file()),
+            hasher: hasher.map(|(h, _)| h),
+            found: found,
+        });
+    }
+
+    Ok(dead)
+}
+
+/// Point the dead ref to the path where its file was found
+pub fn repair(store: &Store, dead: &DeadRef) -> Result<()> {
+    let found = match dead.found {
+        Some(ref found) => found.clone(),
+        None            => return Err(REK::RefTargetDoesNotExist.into_error()),
+    };
+
+    let mut r = try!(Ref::get(store, dead.id.clone()));
+    r.set_fs_file(found)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{File, create_dir_all, remove_file, rename};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+
+    use flags::RefFlags;
+    use hashers::NamedHasher;
+    use reference::Ref;
+    use super::{HashIndex, find_dead_refs, repair};
+
+    fn get_store() -> Store {
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
+    }
+
+    fn create_ref(store: &Store, path: PathBuf, content_hashing: bool) -> StoreId {
+        let flags = RefFlags::default().with_content_hashing(content_hashing);
+        let r     = Ref::create_with_hasher(store, path, flags, NamedHasher::Sha256).unwrap();
+        r.get_location().clone()
+    }
+
+    #[test]
+    fn test_repair_moved_file() {
+        let dir = TempDir::new("imag-ref-repair").unwrap();
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        create_dir_all(&old).unwrap();
+        create_dir_all(&new).unwrap();
+        File::create(old.join("moved")).unwrap().write_all(b"moved").unwrap();
+        File::create(old.join("deleted")).unwrap().write_all(b"deleted").unwrap();
+        File::create(old.join("alive")).unwrap().write_all(b"alive").unwrap();
+
+        let store   = get_store();
+        let moved   = create_ref(&store, old.join("moved"), true);
+        let deleted = create_ref(&store, old.join("deleted"), false);
+        let _alive  = create_ref(&store, old.join("alive"), true);
+
+        rename(old.join("moved"), new.join("moved")).unwrap();
+        remove_file(old.join("deleted")).unwrap();
+
+        let index = HashIndex::build(&[dir.path().to_path_buf()], &[NamedHasher::Sha256]);
+        assert_eq!(index.len(), 2);
+
+        let dead = find_dead_refs(&store, &index).unwrap();
+        assert_eq!(dead.len(), 2);
+
+        let dead_moved = dead.iter().find(|d| *d.id() == moved).unwrap();
+        assert_eq!(dead_moved.hasher(), Some(NamedHasher::Sha256));
+        assert_eq!(dead_moved.found(), Some(&new.join("moved")));
+
+        let dead_deleted = dead.iter().find(|d| *d.id() == deleted).unwrap();
+        assert_eq!(dead_deleted.found(), None);
+        assert!(repair(&store, dead_deleted).is_err());
+
+        assert!(repair(&store, dead_moved).is_ok());
+        let r = Ref::get(&store, moved).unwrap();
+        assert!(!r.is_dangling().unwrap());
+        assert_eq!(r.fs_file().unwrap(), new.join("moved").canonicalize().unwrap());
+    }
+
+}
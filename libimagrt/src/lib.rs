@@ -45,6 +45,7 @@ extern crate toml;
 
 extern crate libimagstore;
 extern crate libimagstorestdhook;
+extern crate libimagentrylink;
 extern crate libimagutil;
 #[macro_use] extern crate libimagerror;
 
@@ -62,9 +62,12 @@ impl<'a> Runtime<'a> {
         use libimagstore::hook::Hook;
         use libimagstore::error::StoreErrorKind;
         use libimagstorestdhook::debug::DebugHook;
+        use libimagstorestdhook::external::external_hooks_from_config;
         use libimagstorestdhook::vcs::git::delete::DeleteHook as GitDeleteHook;
+        use libimagstorestdhook::vcs::git::mv::MoveHook as GitMoveHook;
         use libimagstorestdhook::vcs::git::update::UpdateHook as GitUpdateHook;
         use libimagstorestdhook::vcs::git::store_unload::StoreUnloadHook as GitStoreUnloadHook;
+        use libimagentrylink::internal::InternalLinkUpdater;
         use libimagerror::trace::trace_error;
         use libimagerror::trace::trace_error_dbg;
         use libimagerror::into::IntoError;
@@ -141,6 +144,8 @@ impl<'a> Runtime<'a> {
                     (Box::new(DebugHook::new(HP::PostUpdate))         , "debug", HP::PostUpdate),
                     (Box::new(DebugHook::new(HP::PreDelete))          , "debug", HP::PreDelete),
                     (Box::new(DebugHook::new(HP::PostDelete))         , "debug", HP::PostDelete),
+                    (Box::new(DebugHook::new(HP::PreMove))            , "debug", HP::PreMove),
+                    (Box::new(DebugHook::new(HP::PostMove))           , "debug", HP::PostMove),
                 ];
 
                 // If hook registration fails, trace the error and warn, but continue.
@@ -161,6 +166,7 @@ impl<'a> Runtime<'a> {
             let hooks : Vec<(Box<Hook>, &str, HP)> = vec![
                 (Box::new(GitDeleteHook::new(sp.clone(), HP::PostDelete)), "vcs", HP::PostDelete),
                 (Box::new(GitUpdateHook::new(sp.clone(), HP::PostUpdate)), "vcs", HP::PostUpdate),
+                (Box::new(GitMoveHook::new(sp.clone(), HP::PostMove)),     "vcs", HP::PostMove),
                 (Box::new(GitStoreUnloadHook::new(sp)),                    "vcs", HP::StoreUnload),
             ];
 
@@ -175,6 +181,20 @@ impl<'a> Runtime<'a> {
                 }
             }
 
+            if let Err(e) = store.register_link_updater(Box::new(InternalLinkUpdater)) {
+                trace_error(&e);
+                warn!("Registering link updater with store failed");
+            }
+
+            let hooks = external_hooks_from_config(store.config());
+            for (hook, aspectname) in hooks {
+                let position = hook.position().clone();
+                if let Err(e) = store.register_hook(position, &aspectname, Box::new(hook)) {
+                    trace_error(&e);
+                    warn!("Registering external hook with store failed");
+                }
+            }
+
             Runtime {
                 cli_matches: matches,
                 configuration: cfg,
@@ -0,0 +1,362 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The inverted index
+//!
+//! The `SearchIndex` maps each token to the entries it appears in, together with the positions of
+//! the token in the entry, so phrases can be matched. It is built from the store each time it is
+//! needed.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use toml::Value;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagstore::storeid::StoreIdIterator;
+use libimagerror::trace::trace_error;
+
+use error::SearchErrorKind as SEK;
+use error::MapErrInto;
+use query::{Query, QueryPart};
+use result::Result;
+use tokenizer::tokenize;
+
+/// A matching entry, with its relevance for the query
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    id: StoreId,
+    score: f64,
+}
+
+impl SearchResult {
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// The relevance of the entry. Higher is more relevant.
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+}
+
+/// Positions of a token, per entry
+type Postings = BTreeMap<StoreId, Vec<usize>>;
+
+#[derive(Debug)]
+pub struct SearchIndex {
+    /// Number of tokens per entry
+    documents: BTreeMap<StoreId, usize>,
+
+    postings: HashMap<String, Postings>,
+}
+
+impl SearchIndex {
+
+    pub fn new() -> SearchIndex {
+        SearchIndex {
+            documents: BTreeMap::new(),
+            postings: HashMap::new(),
+        }
+    }
+
+    /// Build the index for all entries in `store`, or only the entries of `module`
+    ///
+    /// If `with_header` is true, the string values in the headers of the entries are indexed as
+    /// well. Entries which cannot be read are skipped.
+    pub fn build(store: &Store, module: Option<&str>, with_header: bool) -> Result<SearchIndex> {
+        let ids : StoreIdIterator = try!(match module {
+            Some(m) => store.retrieve_for_module(m),
+            None    => store.entries(),
+        }.map_err_into(SEK::StoreReadError));
+
+        let mut index = SearchIndex::new();
+        for id in ids {
+            match store.retrieve_copy(id.clone()) {
+                Ok(entry) => index.add(&entry, with_header),
+                Err(e)    => {
+                    trace_error(&e);
+                    debug!("Could not read {:?}, not indexing it", id);
+                },
+            }
+        }
+
+        debug!("Indexed {} entries", index.len());
+        Ok(index)
+    }
+
+    /// Add `entry` to the index, replacing the entry if it was indexed before
+    pub fn add(&mut self, entry: &Entry, with_header: bool) {
+        let id         = entry.get_location().clone();
+        self.remove(&id);
+
+        let mut tokens = tokenize(entry.get_content());
+
+        if with_header {
+            let mut strings = vec![];
+            collect_header_strings(entry.get_header().header(), true, &mut strings);
+
+            for s in strings {
+                // leave a gap, so phrases do not match across values
+                tokens.push(String::new());
+                tokens.extend(tokenize(&s));
+            }
+        }
+
+        for (pos, token) in tokens.iter().enumerate() {
+            if token.is_empty() {
+                continue;
+            }
+
+            self.postings
+                .entry(token.clone())
+                .or_insert_with(BTreeMap::new)
+                .entry(id.clone())
+                .or_insert_with(Vec::new)
+                .push(pos);
+        }
+
+        self.documents.insert(id, tokens.len());
+    }
+
+    /// Remove the entry `id` from the index
+    pub fn remove(&mut self, id: &StoreId) {
+        if self.documents.remove(id).is_some() {
+            let mut unused = vec![];
+            for (token, postings) in self.postings.iter_mut() {
+                postings.remove(id);
+                if postings.is_empty() {
+                    unused.push(token.clone());
+                }
+            }
+
+            for token in unused {
+                self.postings.remove(&token);
+            }
+        }
+    }
+
+    /// The number of indexed entries
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Search the index
+    ///
+    /// Returns the entries which contain all terms and phrases of `query`, most relevant first.
+    pub fn search(&self, query: &Query) -> Vec<SearchResult> {
+        let mut scores : Option<BTreeMap<&StoreId, f64>> = None;
+
+        for part in query.parts() {
+            let matches = self.matches(part);
+            let idf     = self.idf(matches.len());
+
+            scores = Some(match scores {
+                None => matches
+                    .into_iter()
+                    .map(|(id, tf)| (id, tf as f64 * idf))
+                    .collect(),
+                Some(prev) => prev
+                    .into_iter()
+                    .filter_map(|(id, score)| {
+                        matches.get(&id).map(|tf| (id, score + *tf as f64 * idf))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut results : Vec<SearchResult> = scores
+            .unwrap_or_else(BTreeMap::new)
+            .into_iter()
+            .filter(|&(id, _)| {
+                query.module().map(|m| id.local().starts_with(m)).unwrap_or(true)
+            })
+            .map(|(id, score)| {
+                let len = *self.documents.get(id).unwrap_or(&1) as f64;
+                SearchResult {
+                    id: id.clone(),
+                    score: score / len.max(1.0).sqrt(),
+                }
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            match b.score.partial_cmp(&a.score) {
+                Some(Ordering::Equal) | None => a.id.cmp(&b.id),
+                Some(ord)                    => ord,
+            }
+        });
+        results
+    }
+
+    /// Get the entries which match `part`, with the number of occurences in the entry
+    fn matches(&self, part: &QueryPart) -> BTreeMap<&StoreId, usize> {
+        match *part {
+            QueryPart::Term(ref t) => self.postings
+                .get(t)
+                .map(|p| p.iter().map(|(id, pos)| (id, pos.len())).collect())
+                .unwrap_or_else(BTreeMap::new),
+
+            QueryPart::Phrase(ref tokens) => {
+                let postings : Vec<&Postings> = match tokens
+                    .iter()
+                    .map(|t| self.postings.get(t))
+                    .collect::<Option<Vec<&Postings>>>()
+                {
+                    Some(p) => p,
+                    None    => return BTreeMap::new(),
+                };
+
+                postings[0]
+                    .iter()
+                    .filter_map(|(id, positions)| {
+                        let following : Option<Vec<HashSet<usize>>> = postings[1..]
+                            .iter()
+                            .map(|p| p.get(id).map(|pos| pos.iter().cloned().collect()))
+                            .collect();
+
+                        following.and_then(|following| {
+                            let n = positions
+                                .iter()
+                                .filter(|&start| {
+                                    following
+                                        .iter()
+                                        .enumerate()
+                                        .all(|(i, set)| set.contains(&(start + i + 1)))
+                                })
+                                .count();
+
+                            if n > 0 { Some((id, n)) } else { None }
+                        })
+                    })
+                    .collect()
+            },
+        }
+    }
+
+    /// The inverse document frequency for a query part which is found in `n` entries
+    fn idf(&self, n: usize) -> f64 {
+        (1.0 + self.documents.len() as f64 / (n.max(1) as f64)).ln()
+    }
+
+}
+
+/// Collect all string values of a header, except the ones in the "imag" section
+fn collect_header_strings(v: &Value, toplevel: bool, strings: &mut Vec<String>) {
+    match *v {
+        Value::String(ref s) => strings.push(s.clone()),
+        Value::Array(ref a)  => for elem in a.iter() {
+            collect_header_strings(elem, false, strings);
+        },
+        Value::Table(ref t)  => for (key, elem) in t.iter() {
+            if !(toplevel && key == "imag") {
+                collect_header_strings(elem, false, strings);
+            }
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::SearchIndex;
+    use query::Query;
+
+    fn entry(id: &str, content: &str) -> Entry {
+        let id        = StoreId::new_baseless(PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id);
+        *entry.get_content_mut() = String::from(content);
+        entry
+    }
+
+    fn get_index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+        index.add(&entry("notes/a", "The store format is TOML. The store is great."), false);
+        index.add(&entry("notes/b", "Format the disk, then store the data."), false);
+        index.add(&entry("diary/c", "Today I wrote about the store."), false);
+        index
+    }
+
+    fn ids(index: &SearchIndex, q: &str) -> Vec<String> {
+        index.search(&Query::parse(q).unwrap())
+            .into_iter()
+            .map(|r| r.id().local().to_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_terms() {
+        let index = get_index();
+        assert_eq!(ids(&index, "store format"), vec!["notes/a", "notes/b"]);
+        assert_eq!(ids(&index, "store").len(), 3);
+        assert!(ids(&index, "nothing").is_empty());
+    }
+
+    #[test]
+    fn test_phrase() {
+        let index = get_index();
+        assert_eq!(ids(&index, "\"store format\""), vec!["notes/a"]);
+        assert_eq!(ids(&index, "\"format the\""), vec!["notes/b"]);
+    }
+
+    #[test]
+    fn test_module_scope() {
+        let index = get_index();
+        assert_eq!(ids(&index, "module:diary store"), vec!["diary/c"]);
+    }
+
+    #[test]
+    fn test_ranking() {
+        let index = get_index();
+        // notes/a contains "store" twice
+        assert_eq!(ids(&index, "store")[0], "notes/a");
+    }
+
+    #[test]
+    fn test_header() {
+        let mut index = SearchIndex::new();
+        let mut e     = entry("notes/d", "nothing here");
+        e.get_header_mut().insert("note", Value::Table(Default::default())).unwrap();
+        e.get_header_mut().insert("note.name", Value::String(String::from("Shopping"))).unwrap();
+
+        index.add(&e, false);
+        assert!(ids(&index, "shopping").is_empty());
+
+        index.add(&e, true);
+        assert_eq!(ids(&index, "shopping"), vec!["notes/d"]);
+    }
+
+}
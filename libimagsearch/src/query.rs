@@ -0,0 +1,140 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Search queries
+//!
+//! A query is a list of words and "quoted phrases". All words and phrases have to be found in an
+//! entry for it to match. A word of the form `module:<name>` restricts the search to the entries
+//! of the module `<name>`.
+//!
+//! ```ignore
+//!  let query = try!(Query::parse("module:notes rust \"store format\""));
+//! ```
+
+use libimagerror::into::IntoError;
+
+use error::SearchErrorKind as SEK;
+use result::Result;
+use tokenizer::tokenize;
+
+/// A part of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryPart {
+    /// A single token
+    Term(String),
+
+    /// Several tokens which have to appear in this order, without anything in between
+    Phrase(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    parts: Vec<QueryPart>,
+    module: Option<String>,
+}
+
+impl Query {
+
+    /// Parse a query string
+    ///
+    /// # Errors
+    ///
+    /// * If a phrase is not terminated
+    /// * If the query does not contain anything to search for
+    pub fn parse(s: &str) -> Result<Query> {
+        let mut parts  = vec![];
+        let mut module = None;
+
+        let pieces : Vec<&str> = s.split('"').collect();
+        if pieces.len() % 2 == 0 {
+            return Err(SEK::UnterminatedPhrase.into_error());
+        }
+
+        for (i, piece) in pieces.into_iter().enumerate() {
+            if i % 2 == 1 {
+                // inside of quotes
+                let mut tokens = tokenize(piece);
+                match tokens.len() {
+                    0 => {},
+                    1 => parts.push(QueryPart::Term(tokens.pop().unwrap())),
+                    _ => parts.push(QueryPart::Phrase(tokens)),
+                }
+                continue;
+            }
+
+            for word in piece.split_whitespace() {
+                if word.starts_with("module:") {
+                    module = Some(String::from(&word["module:".len()..]));
+                } else {
+                    parts.extend(tokenize(word).into_iter().map(QueryPart::Term));
+                }
+            }
+        }
+
+        if parts.is_empty() {
+            return Err(SEK::EmptyQuery.into_error());
+        }
+
+        Ok(Query {
+            parts: parts,
+            module: module,
+        })
+    }
+
+    /// Restrict the query to the entries of `module`
+    pub fn with_module<S: Into<String>>(mut self, module: S) -> Query {
+        self.module = Some(module.into());
+        self
+    }
+
+    pub fn parts(&self) -> &Vec<QueryPart> {
+        &self.parts
+    }
+
+    /// The module the query is restricted to, if any
+    pub fn module(&self) -> Option<&String> {
+        self.module.as_ref()
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Query, QueryPart};
+
+    #[test]
+    fn test_parse() {
+        let q = Query::parse("module:notes Rust \"the Store format\" \"x\"").unwrap();
+
+        assert_eq!(q.module(), Some(&String::from("notes")));
+        assert_eq!(q.parts(), &vec![
+            QueryPart::Term(String::from("rust")),
+            QueryPart::Phrase(vec![String::from("the"), String::from("store"), String::from("format")]),
+            QueryPart::Term(String::from("x")),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("\"unterminated phrase").is_err());
+        assert!(Query::parse("module:notes").is_err());
+        assert!(Query::parse("   ").is_err());
+    }
+
+}
@@ -212,6 +212,82 @@ pub fn config_implicit_store_create_allowed(config: Option<&Value>) -> bool {
     }).unwrap_or(false)
 }
 
+/// Default for the maximum number of not borrowed entries the store keeps in its cache
+pub static DEFAULT_CACHE_SIZE : usize = 1024;
+
+/// Get the maximum number of not borrowed entries the store keeps in its cache
+///
+/// Falls back to `DEFAULT_CACHE_SIZE` if the key is missing.
+pub fn config_cache_size(config: Option<&Value>) -> usize {
+    config.map(|t| {
+        match *t {
+            Value::Table(ref t) => {
+                match t.get("cache-size") {
+                    Some(&Value::Integer(i)) if i >= 0 => i as usize,
+                    Some(_) => {
+                        warn!("Key 'cache-size' does not contain a positive Integer value");
+                        DEFAULT_CACHE_SIZE
+                    }
+                    None => DEFAULT_CACHE_SIZE,
+                }
+            }
+            _ => {
+                warn!("Store configuration seems to be no Table");
+                DEFAULT_CACHE_SIZE
+            },
+        }
+    }).unwrap_or(DEFAULT_CACHE_SIZE)
+}
+
+/// Checks whether the store configuration enables the header index
+///
+/// The index is disabled if the key is missing.
+pub fn config_header_index_enabled(config: Option<&Value>) -> bool {
+    config.map(|t| {
+        match *t {
+            Value::Table(ref t) => {
+                match t.get("header-index") {
+                    Some(&Value::Boolean(b)) => b,
+                    Some(_) => {
+                        warn!("Key 'header-index' does not contain a Boolean value");
+                        false
+                    }
+                    None => false,
+                }
+            }
+            _ => {
+                warn!("Store configuration seems to be no Table");
+                false
+            },
+        }
+    }).unwrap_or(false)
+}
+
+/// Checks whether the store configuration enables updating the links of linked entries
+///
+/// If enabled, moving an entry re-points the links of the entries linked to it to the new id and
+/// deleting an entry removes the links to it. Disabled if the key is missing.
+pub fn config_update_links(config: Option<&Value>) -> bool {
+    config.map(|t| {
+        match *t {
+            Value::Table(ref t) => {
+                match t.get("update-links") {
+                    Some(&Value::Boolean(b)) => b,
+                    Some(_) => {
+                        warn!("Key 'update-links' does not contain a Boolean value");
+                        false
+                    }
+                    None => false,
+                }
+            }
+            _ => {
+                warn!("Store configuration seems to be no Table");
+                false
+            },
+        }
+    }).unwrap_or(false)
+}
+
 pub fn get_store_unload_aspect_names(value: &Option<Value>) -> Vec<String> {
     get_aspect_names_for_aspect_position("store-unload-hook-aspects", value)
 }
@@ -308,6 +384,11 @@ impl AspectConfig {
         self.mutable_hooks
     }
 
+    /// Whether the non-mutating hooks of the aspect may be executed in parallel
+    pub fn allow_parallel_execution(&self) -> bool {
+        self.parallel
+    }
+
     /// Get the aspect configuration for an aspect.
     ///
     /// Pass the store configuration object, this searches in `[aspects][<aspect_name>]`.
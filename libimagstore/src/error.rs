@@ -77,17 +77,38 @@ generate_custom_error_types!(StoreError, StoreErrorKind, CustomErrorData,
     StoreIdLocalPartAbsoluteError => "StoreId 'id' part is absolute (starts with '/') which is not allowed",
     StoreIdBuildFromFullPathError => "Building StoreId from full file path failed",
     StoreIdHasNoBaseError   => "StoreId has no 'base' part",
+    HeaderIndexDisabled     => "The header index is not enabled in the store configuration",
+    HeaderIndexParseError   => "The header index could not be parsed",
+    HeaderIndexWriteError   => "The header index could not be written",
+    MigrationError          => "Migrating an entry failed",
+    FileLocked              => "File is locked by another store",
+    FileNotUnlocked         => "File could not be unlocked",
+    LinkUpdateError         => "The links of a linked entry could not be updated",
 
     CreateCallError            => "Error when calling create()",
     RetrieveCallError          => "Error when calling retrieve()",
     GetCallError               => "Error when calling get()",
     GetAllVersionsCallError    => "Error when calling get_all_versions()",
     RetrieveForModuleCallError => "Error when calling retrieve_for_module()",
+    EntriesCallError           => "Error when calling entries()",
+    SnapshotCallError          => "Error when calling snapshot()",
+    ImportCallError            => "Error when calling import_entry()",
     UpdateCallError            => "Error when calling update()",
     RetrieveCopyCallError      => "Error when calling retrieve_copy()",
     DeleteCallError            => "Error when calling delete()",
     MoveCallError              => "Error when calling move()",
-    MoveByIdCallError          => "Error when calling move_by_id()"
+    MoveByIdCallError          => "Error when calling move_by_id()",
+    RebuildHeaderIndexCallError => "Error when calling rebuild_header_index()",
+    FindByHeaderCallError      => "Error when calling find_by_header()",
+    RegisterSchemaCallError    => "Error when calling register_schema()",
+    CheckSchemaCallError       => "Error when calling check_schema()",
+    RegisterMigrationCallError => "Error when calling register_migration()",
+    RegisterLinkUpdaterCallError => "Error when calling register_link_updater()",
+    MigrateCallError           => "Error when calling migrate()",
+
+    TransactionCommitCallError => "Error when calling Transaction::commit()",
+    TransactionDuplicateId     => "Entry is used more than once in the transaction",
+    TransactionRollbackError   => "Transaction could not be rolled back"
 );
 
 generate_result_helper!(StoreError, StoreErrorKind);
@@ -0,0 +1,214 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use libimagerror::into::IntoError;
+
+use error::MapErrInto;
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+
+type Backend = Arc<Mutex<InMemoryBackend>>;
+
+#[derive(Debug, Default)]
+struct InMemoryBackend {
+    files: HashMap<PathBuf, Vec<u8>>,
+    dirs: HashSet<PathBuf>,
+    locks: HashSet<PathBuf>,
+}
+
+/// `FileAbstractionInstance` type for the in-memory backend
+///
+/// All instances created by one `InMemoryFileAbstraction` share the same memory.
+#[derive(Debug)]
+pub struct InMemoryFileAbstractionInstance {
+    fs_abstraction: Backend,
+    absent_path: PathBuf,
+    locked: bool,
+}
+
+impl InMemoryFileAbstractionInstance {
+
+    fn new(fs: Backend, pb: PathBuf) -> InMemoryFileAbstractionInstance {
+        InMemoryFileAbstractionInstance {
+            fs_abstraction: fs,
+            absent_path: pb,
+            locked: false,
+        }
+    }
+
+}
+
+impl FileAbstractionInstance for InMemoryFileAbstractionInstance {
+
+    /**
+     * Get the mutable file behind a InMemoryFileAbstraction object
+     */
+    fn get_file_content(&mut self) -> Result<String, SE> {
+        debug!("Getting lazy file: {:?}", self);
+        let backend = try!(self.fs_abstraction.lock().map_err(|_| SEK::LockPoisoned.into_error()));
+        backend.files
+            .get(&self.absent_path)
+            .ok_or(SEK::FileNotFound.into_error())
+            .and_then(|vec| String::from_utf8(vec.clone()).map_err_into(SEK::EncodingError))
+    }
+
+    fn write_file_content(&mut self, buf: &[u8]) -> Result<(), SE> {
+        let mut backend = try!(self.fs_abstraction
+                               .lock()
+                               .map_err(|_| SEK::LockPoisoned.into_error()));
+        backend.files.insert(self.absent_path.clone(), Vec::from(buf));
+        Ok(())
+    }
+
+    fn lock(&mut self) -> Result<(), SE> {
+        if self.locked {
+            return Ok(());
+        }
+
+        let mut backend = try!(self.fs_abstraction
+                               .lock()
+                               .map_err(|_| SEK::LockPoisoned.into_error()));
+        if !backend.locks.insert(self.absent_path.clone()) {
+            return Err(SEK::FileLocked.into_error());
+        }
+        backend.files.entry(self.absent_path.clone()).or_insert_with(Vec::new);
+        self.locked = true;
+        Ok(())
+    }
+
+    fn unlock(&mut self) -> Result<(), SE> {
+        if !self.locked {
+            return Ok(());
+        }
+
+        let mut backend = try!(self.fs_abstraction
+                               .lock()
+                               .map_err(|_| SEK::LockPoisoned.into_error()));
+        backend.locks.remove(&self.absent_path);
+        self.locked = false;
+        Ok(())
+    }
+
+}
+
+impl Drop for InMemoryFileAbstractionInstance {
+
+    fn drop(&mut self) {
+        let _ = self.unlock();
+    }
+
+}
+
+/// `FileAbstraction` type which holds all files in memory
+///
+/// Nothing is written to the filesystem, which makes this backend suitable for tests. Directories
+/// exist if they were created with `create_dir_all()` or if a file exists below them.
+///
+/// Clones share the same memory, so several `Store` objects can be opened on the same virtual
+/// filesystem.
+#[derive(Debug, Clone)]
+pub struct InMemoryFileAbstraction {
+    virtual_filesystem: Backend,
+}
+
+impl InMemoryFileAbstraction {
+
+    pub fn new() -> InMemoryFileAbstraction {
+        InMemoryFileAbstraction {
+            virtual_filesystem: Arc::new(Mutex::new(InMemoryBackend::default())),
+        }
+    }
+
+    fn backend(&self) -> Result<::std::sync::MutexGuard<InMemoryBackend>, SE> {
+        self.virtual_filesystem.lock().map_err(|_| SEK::LockPoisoned.into_error())
+    }
+
+}
+
+impl FileAbstraction for InMemoryFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        debug!("Removing: {:?}", path);
+        try!(self.backend())
+            .files
+            .remove(path)
+            .map(|_| ())
+            .ok_or(SEK::FileNotFound.into_error())
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        debug!("Copying : {:?} -> {:?}", from, to);
+        let mut backend = try!(self.backend());
+        let a = try!(backend.files.get(from).cloned().ok_or(SEK::FileNotFound.into_error()));
+        backend.files.insert(to.clone(), a);
+        Ok(())
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        debug!("Renaming: {:?} -> {:?}", from, to);
+        let mut backend = try!(self.backend());
+        let a = try!(backend.files.remove(from).ok_or(SEK::FileNotFound.into_error()));
+        backend.files.insert(to.clone(), a);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &PathBuf) -> Result<(), SE> {
+        debug!("Creating directory: {:?}", path);
+        let mut backend = try!(self.backend());
+        backend.dirs.insert(path.clone());
+        Ok(())
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE> {
+        let backend = try!(self.backend());
+        Ok(backend.files.contains_key(path) ||
+           backend.dirs.iter().any(|d| d.starts_with(path)) ||
+           backend.files.keys().any(|f| f.starts_with(path)))
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE> {
+        self.backend().map(|backend| backend.files.contains_key(path))
+    }
+
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<Vec<PathBuf>, SE> {
+        debug!("Getting all pathes below: {:?}", basepath);
+        self.backend()
+            .map(|backend| {
+                backend.files
+                    .keys()
+                    .filter(|p| p.starts_with(&basepath) && *p != &basepath)
+                    .cloned()
+                    .collect()
+            })
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(InMemoryFileAbstractionInstance::new(self.virtual_filesystem.clone(), p))
+    }
+
+}
@@ -0,0 +1,134 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The storage backend abstraction of the store
+//!
+//! The store does not talk to the filesystem directly, but through an object implementing the
+//! `FileAbstraction` trait. Two implementations are shipped: `FSFileAbstraction`, which is the
+//! default and operates on the real filesystem, and `InMemoryFileAbstraction`, which holds all
+//! entries in memory and is meant to be used for testing.
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use error::StoreError as SE;
+
+mod fs;
+mod inmemory;
+
+pub use self::fs::FSFileAbstraction;
+pub use self::fs::FSFileAbstractionInstance;
+pub use self::inmemory::InMemoryFileAbstraction;
+pub use self::inmemory::InMemoryFileAbstractionInstance;
+
+/// An abstraction trait over filesystem actions
+///
+/// This is the storage backend of the store. All operations which act on pathes rather than on a
+/// single file are part of this trait, operations on a single file are done through the
+/// `FileAbstractionInstance` objects which are created by `FileAbstraction::new_instance()`.
+pub trait FileAbstraction : Debug + Send + Sync {
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE>;
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE>;
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE>;
+    fn create_dir_all(&self, path: &PathBuf) -> Result<(), SE>;
+
+    /// Check whether there is a file or a directory at `path`
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE>;
+
+    /// Check whether there is a file at `path`
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE>;
+
+    /// Get the pathes of all files below `basepath`
+    ///
+    /// Directories are not returned. Backends which have hidden files should not return them.
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<Vec<PathBuf>, SE>;
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance>;
+}
+
+/// An abstraction trait over actions on files
+///
+/// A lazy file is either absent, but a path to it is available, or it is present.
+pub trait FileAbstractionInstance : Debug + Send + Sync {
+    fn get_file_content(&mut self) -> Result<String, SE>;
+    fn write_file_content(&mut self, buf: &[u8]) -> Result<(), SE>;
+
+    /// Acquire an exclusive advisory lock on the file
+    ///
+    /// The file is created if it does not exist yet. Fails with `FileLocked` if the lock is held
+    /// by another instance, which might live in another `Store` or in another process.
+    fn lock(&mut self) -> Result<(), SE>;
+
+    /// Release the lock acquired with `lock()`
+    ///
+    /// Unlocking a file which is not locked does nothing. The lock is released as well when the
+    /// instance is dropped.
+    fn unlock(&mut self) -> Result<(), SE>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileAbstraction;
+    use super::FileAbstractionInstance;
+    use super::InMemoryFileAbstraction;
+    use std::path::PathBuf;
+
+    #[test]
+    fn lazy_file() {
+        let fs = InMemoryFileAbstraction::new();
+
+        let mut path = PathBuf::from("/tests");
+        path.set_file_name("test1");
+        let mut lf = fs.new_instance(path);
+        lf.write_file_content(b"Hello World").unwrap();
+        let bah = lf.get_file_content().unwrap();
+        assert_eq!(bah, "Hello World");
+    }
+
+    #[test]
+    fn lazy_file_shares_backend() {
+        let fs = InMemoryFileAbstraction::new();
+        let path = PathBuf::from("/tests/test2");
+
+        fs.new_instance(path.clone()).write_file_content(b"Hello World").unwrap();
+
+        assert!(fs.exists(&path).unwrap());
+        assert!(fs.is_file(&path).unwrap());
+        assert!(fs.exists(&PathBuf::from("/tests")).unwrap());
+        assert!(!fs.is_file(&PathBuf::from("/tests")).unwrap());
+
+        let bah = fs.new_instance(path).get_file_content().unwrap();
+        assert_eq!(bah, "Hello World");
+    }
+
+    #[test]
+    fn rename_removes_source() {
+        let fs   = InMemoryFileAbstraction::new();
+        let from = PathBuf::from("/tests/from");
+        let to   = PathBuf::from("/tests/to");
+
+        fs.new_instance(from.clone()).write_file_content(b"Hello World").unwrap();
+        fs.rename(&from, &to).unwrap();
+
+        assert!(!fs.exists(&from).unwrap());
+        assert!(fs.exists(&to).unwrap());
+        assert_eq!(fs.pathes_recursively(PathBuf::from("/tests")).unwrap(), vec![to]);
+    }
+
+}
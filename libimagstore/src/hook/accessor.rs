@@ -23,7 +23,11 @@ use hook::result::HookResult;
 use store::FileLockEntry;
 use storeid::StoreId;
 
-pub trait StoreIdAccessor : Debug + Send {
+/// Accessor for hooks which only need the `StoreId` of an entry
+///
+/// As these hooks do not alter the entry, they might be executed in parallel with other hooks of
+/// the same aspect, hence implementations have to be `Sync`.
+pub trait StoreIdAccessor : Debug + Send + Sync {
     fn access(&self, &StoreId) -> HookResult<()>;
 }
 
@@ -31,15 +35,27 @@ pub trait MutableHookDataAccessor : Debug + Send {
     fn access_mut(&self, &mut FileLockEntry) -> HookResult<()>;
 }
 
-pub trait NonMutableHookDataAccessor : Debug + Send {
+/// Accessor for hooks which read, but do not alter an entry
+///
+/// As these hooks do not alter the entry, they might be executed in parallel with other hooks of
+/// the same aspect, hence implementations have to be `Sync`.
+pub trait NonMutableHookDataAccessor : Debug + Send + Sync {
     fn access(&self, &FileLockEntry) -> HookResult<()>;
 }
 
+/// Accessor for hooks which are executed when an entry is moved
+///
+/// The hook gets the old and the new `StoreId` of the entry.
+pub trait MoveAccessor : Debug + Send {
+    fn access(&self, old: &StoreId, new: &StoreId) -> HookResult<()>;
+}
+
 #[derive(Debug)]
 pub enum HookDataAccessor<'a> {
     StoreIdAccess(&'a StoreIdAccessor),
     MutableAccess(&'a MutableHookDataAccessor),
     NonMutableAccess(&'a NonMutableHookDataAccessor),
+    MoveAccess(&'a MoveAccessor),
 }
 
 pub trait HookDataAccessorProvider {
@@ -17,6 +17,12 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::error::Error;
+use std::fmt::{Display, Formatter, Error as FmtError};
+use std::time::Instant;
+
+use crossbeam;
+
 use libimagerror::trace::trace_error;
 use libimagutil::iter::FoldResult;
 
@@ -56,6 +62,58 @@ impl Aspect {
         self.hooks.push(h);
     }
 
+    /// Whether the non-mutating hooks of this aspect are executed in parallel
+    pub fn is_parallel(&self) -> bool {
+        self.cfg.as_ref().map(|c| c.allow_parallel_execution()).unwrap_or(false)
+    }
+
+    /// Execute the hooks of this aspect for an entry which is moved from `old` to `new`
+    ///
+    /// Hooks which only access a `StoreId` get `current`, which is the id the entry has at the
+    /// time the hooks are executed: `old` before the move, `new` after it.
+    pub fn access_move(&self, old: &StoreId, new: &StoreId, current: &StoreId) -> HookResult<()> {
+        let accessors : Vec<HDA> = self.hooks.iter().map(|h| h.accessor()).collect();
+        if !accessors.iter().all(|a| {
+            let x = is_match!(*a, HDA::StoreIdAccess(_)) || is_match!(*a, HDA::MoveAccess(_));
+            if !x {
+                warn!("Denied execution of Entry-Accessing Hook in move");
+                debug!("Accessor: {:?}", a);
+                debug!("in Move-Aspect execution: {:?}", self);
+            }
+            x
+        }) {
+            return Err(HE::new(HEK::AccessTypeViolation, None));
+        }
+
+        self.hooks.iter().zip(accessors.iter()).fold_defresult(|(hook, accessor)| {
+            execute_timed(&self.name, hook.name(), || match accessor {
+                &HDA::StoreIdAccess(accessor) => accessor.access(current),
+                &HDA::MoveAccess(accessor)    => accessor.access(old, new),
+                _ => unreachable!(),
+            })
+        })
+    }
+
+    /// Execute non-mutating hooks, in parallel if the aspect is configured to be parallel
+    ///
+    /// `fle` has to be `Some(_)` if there are `ConcurrentAccessor::NonMutable` accessors in
+    /// `hooks`.
+    fn execute_non_mutating(&self,
+                            hooks: &[(&'static str, ConcurrentAccessor)],
+                            id: &StoreId,
+                            fle: Option<&FileLockEntry>)
+        -> HookResult<()>
+    {
+        if hooks.len() > 1 && self.is_parallel() {
+            debug!("[Aspect][{}]: Executing {} hooks in parallel", self.name, hooks.len());
+            execute_parallel(&self.name, hooks, id, fle)
+        } else {
+            hooks.iter().fold_defresult(|&(name, accessor)| {
+                execute_timed(&self.name, name, || accessor.access(id, fle))
+            })
+        }
+    }
+
 }
 
 impl StoreIdAccessor for Aspect {
@@ -73,13 +131,16 @@ impl StoreIdAccessor for Aspect {
             return Err(HE::new(HEK::AccessTypeViolation, None));
         }
 
-        accessors.iter().fold_defresult(|accessor| {
-            let res = match accessor {
-                &HDA::StoreIdAccess(accessor) => accessor.access(id),
+        let hooks : Vec<_> = self.hooks
+            .iter()
+            .zip(accessors.into_iter())
+            .map(|(hook, accessor)| match accessor {
+                HDA::StoreIdAccess(accessor) => (hook.name(), ConcurrentAccessor::StoreId(accessor)),
                 _ => unreachable!(),
-            };
-            trace_hook_errors(res)
-        })
+            })
+            .collect();
+
+        self.execute_non_mutating(&hooks, id, None)
     }
 }
 
@@ -88,27 +149,38 @@ impl MutableHookDataAccessor for Aspect {
         debug!("Checking whether mutable hooks are allowed");
         debug!("-> config = {:?}", self.cfg);
 
-        let accessors : Vec<HDA> = self.hooks.iter().map(|h| h.accessor()).collect();
+        // Consecutive non-mutating hooks are collected into a chunk, which is executed before the
+        // next mutating hook, so the order of hooks relative to the mutating ones is preserved.
+        let mut chunk = vec![];
 
-        // TODO: Naiive implementation.
-        // More sophisticated version would check whether there are _chunks_ of
-        // NonMutableAccess accessors and execute these chunks in parallel. We do not have
-        // performance concerns yet, so this is okay.
-        accessors.iter().fold_defresult(|accessor| {
-            let res = match accessor {
-                &HDA::StoreIdAccess(ref accessor)    => accessor.access(fle.get_location()),
-                &HDA::NonMutableAccess(ref accessor) => accessor.access(fle),
-                &HDA::MutableAccess(ref accessor)    => {
+        for hook in self.hooks.iter() {
+            match hook.accessor() {
+                HDA::StoreIdAccess(accessor) => {
+                    chunk.push((hook.name(), ConcurrentAccessor::StoreId(accessor)))
+                },
+                HDA::NonMutableAccess(accessor) => {
+                    chunk.push((hook.name(), ConcurrentAccessor::NonMutable(accessor)))
+                },
+                HDA::MutableAccess(accessor) => {
                     if !self.cfg.as_ref().map(|c| c.allow_mutable_hooks()).unwrap_or(false) {
                         debug!("Apparently mutable hooks are not allowed... failing now.");
                         return Err(HE::new(HEK::MutableHooksNotAllowed, None));
                     }
 
-                    accessor.access_mut(fle)
+                    try!(self.execute_non_mutating(&chunk, fle.get_location(), Some(&*fle)));
+                    chunk.clear();
+
+                    try!(execute_timed(&self.name, hook.name(), || accessor.access_mut(fle)));
                 },
-            };
-            trace_hook_errors(res)
-        })
+                HDA::MoveAccess(_) => {
+                    warn!("Denied execution of Move-Accessing Hook");
+                    debug!("in MutableAccess-Aspect execution: {:?}", self);
+                    return Err(HE::new(HEK::AccessTypeViolation, None));
+                },
+            }
+        }
+
+        self.execute_non_mutating(&chunk, fle.get_location(), Some(&*fle))
     }
 }
 
@@ -127,14 +199,128 @@ impl NonMutableHookDataAccessor for Aspect {
             return Err(HE::new(HEK::AccessTypeViolation, None));
         }
 
-        accessors.iter().fold_defresult(|accessor| {
-            let res = match accessor {
-                &HDA::NonMutableAccess(accessor) => accessor.access(fle),
+        let hooks : Vec<_> = self.hooks
+            .iter()
+            .zip(accessors.into_iter())
+            .map(|(hook, accessor)| match accessor {
+                HDA::NonMutableAccess(accessor) => {
+                    (hook.name(), ConcurrentAccessor::NonMutable(accessor))
+                },
                 _ => unreachable!(),
-            };
-            trace_hook_errors(res)
-        })
+            })
+            .collect();
+
+        self.execute_non_mutating(&hooks, fle.get_location(), Some(fle))
+    }
+}
+
+/// Accessor of a hook which does not alter the entry and can therefor be executed concurrently
+/// with other hooks of the same kind.
+#[derive(Debug, Clone, Copy)]
+enum ConcurrentAccessor<'a> {
+    StoreId(&'a StoreIdAccessor),
+    NonMutable(&'a NonMutableHookDataAccessor),
+}
+
+impl<'a> ConcurrentAccessor<'a> {
+
+    fn access(&self, id: &StoreId, fle: Option<&FileLockEntry>) -> HookResult<()> {
+        match (*self, fle) {
+            (ConcurrentAccessor::StoreId(accessor), _)              => accessor.access(id),
+            (ConcurrentAccessor::NonMutable(accessor), Some(fle))   => accessor.access(fle),
+            (ConcurrentAccessor::NonMutable(_), None)               => {
+                Err(HE::new(HEK::AccessTypeViolation, None))
+            },
+        }
+    }
+
+}
+
+/// Execute the hooks in `hooks`, each in its own thread, and wait for all of them to finish.
+///
+/// Errors cannot be passed between threads, so aborting errors are traced in the thread they
+/// occur in and the first one (in order of `hooks`) is returned as a new error of the same kind,
+/// with the messages of the causes of the original error as cause.
+fn execute_parallel(aspect: &str,
+                    hooks: &[(&'static str, ConcurrentAccessor)],
+                    id: &StoreId,
+                    fle: Option<&FileLockEntry>)
+    -> HookResult<()>
+{
+    let failed = crossbeam::scope(|scope| {
+        let handles : Vec<_> = hooks
+            .iter()
+            .map(|&(name, accessor)| {
+                scope.spawn(move || {
+                    execute_timed(aspect, name, || accessor.access(id, fle))
+                        .map_err(|e| {
+                            trace_error(&e);
+                            (e.err_type(), e.cause().map(ErrorMessages::from_error))
+                        })
+                        .err()
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|handle| handle.join()).collect::<Vec<_>>()
+    });
+
+    match failed.into_iter().filter_map(|failure| failure).next() {
+        Some((kind, cause)) => Err(HE::new(kind, cause.map(|c| Box::new(c) as Box<Error>))),
+        None                => Ok(()),
+    }
+}
+
+/// The messages of an error and its causes, which can be sent between threads other than the
+/// error itself
+#[derive(Debug)]
+struct ErrorMessages {
+    message: String,
+    cause: Option<Box<ErrorMessages>>,
+}
+
+impl ErrorMessages {
+
+    fn from_error(e: &Error) -> ErrorMessages {
+        ErrorMessages {
+            message: format!("{}", e),
+            cause: e.cause().map(|c| Box::new(ErrorMessages::from_error(c))),
+        }
+    }
+
+}
+
+impl Display for ErrorMessages {
+
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        write!(fmt, "{}", self.message)
     }
+
+}
+
+impl Error for ErrorMessages {
+
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        self.cause.as_ref().map(|c| &**c as &Error)
+    }
+
+}
+
+/// Execute a single hook, print how long its execution took and trace its error if it is not an
+/// aborting one.
+fn execute_timed<F>(aspect: &str, hook: &str, f: F) -> HookResult<()>
+    where F: FnOnce() -> HookResult<()>
+{
+    let start   = Instant::now();
+    let res     = f();
+    let elapsed = start.elapsed();
+    debug!("[Aspect][{}]: Hook '{}' took {}.{:06}s",
+           aspect, hook, elapsed.as_secs(), elapsed.subsec_nanos() / 1000);
+    trace_hook_errors(res)
 }
 
 fn trace_hook_errors(res: HookResult<()>) -> HookResult<()> {
@@ -149,3 +335,148 @@ fn trace_hook_errors(res: HookResult<()>) -> HookResult<()> {
     })
 }
 
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    use toml::Parser;
+
+    use libimagerror::into::IntoError;
+
+    use configuration::AspectConfig;
+    use hook::Hook;
+    use hook::accessor::HookDataAccessor as HDA;
+    use hook::accessor::HookDataAccessorProvider;
+    use hook::accessor::{StoreIdAccessor, MutableHookDataAccessor};
+    use hook::error::HookErrorKind as HEK;
+    use hook::result::HookResult;
+    use store::FileLockEntry;
+    use storeid::StoreId;
+
+    use super::Aspect;
+
+    type Record = Arc<Mutex<Vec<&'static str>>>;
+
+    #[derive(Debug)]
+    struct RecordingHook {
+        name: &'static str,
+        mutable: bool,
+        succeed: bool,
+        record: Record,
+    }
+
+    impl RecordingHook {
+
+        fn record(&self) -> HookResult<()> {
+            self.record.lock().unwrap().push(self.name);
+            if self.succeed {
+                Ok(())
+            } else {
+                let cause = Box::new(HEK::AccessTypeViolation.into_error());
+                Err(HEK::HookExecutionError.into_error_with_cause(cause))
+            }
+        }
+
+    }
+
+    impl Hook for RecordingHook {
+        fn name(&self) -> &'static str { self.name }
+        fn set_config(&mut self, _: &::toml::Value) { }
+    }
+
+    impl HookDataAccessorProvider for RecordingHook {
+        fn accessor(&self) -> HDA {
+            if self.mutable {
+                HDA::MutableAccess(self)
+            } else {
+                HDA::StoreIdAccess(self)
+            }
+        }
+    }
+
+    impl StoreIdAccessor for RecordingHook {
+        fn access(&self, _: &StoreId) -> HookResult<()> {
+            self.record()
+        }
+    }
+
+    impl MutableHookDataAccessor for RecordingHook {
+        fn access_mut(&self, _: &mut FileLockEntry) -> HookResult<()> {
+            self.record()
+        }
+    }
+
+    fn parallel_aspect(hooks: &[(&'static str, bool, bool)], record: &Record) -> Aspect {
+        let cfg = Parser::new("parallel = true\nmutable_hooks = true").parse().unwrap();
+        let mut aspect = Aspect::new(String::from("test"),
+                                     Some(AspectConfig::new(::toml::Value::Table(cfg))));
+
+        for &(name, mutable, succeed) in hooks {
+            aspect.register_hook(Box::new(RecordingHook {
+                name: name,
+                mutable: mutable,
+                succeed: succeed,
+                record: record.clone(),
+            }));
+        }
+
+        aspect
+    }
+
+    #[test]
+    fn test_parallel_aspect_executes_all_hooks() {
+        let record = Arc::new(Mutex::new(vec![]));
+        let aspect = parallel_aspect(&[("a", false, true), ("b", false, true), ("c", false, true)],
+                                     &record);
+        let id = StoreId::new_baseless(PathBuf::from("test")).unwrap();
+
+        assert!(aspect.is_parallel());
+        assert!(StoreIdAccessor::access(&aspect, &id).is_ok());
+
+        let mut record = record.lock().unwrap().clone();
+        record.sort();
+        assert_eq!(record, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_parallel_aspect_fails_if_one_hook_fails() {
+        let record = Arc::new(Mutex::new(vec![]));
+        let aspect = parallel_aspect(&[("a", false, true), ("b", false, false), ("c", false, true)],
+                                     &record);
+        let id = StoreId::new_baseless(PathBuf::from("test")).unwrap();
+
+        let err = StoreIdAccessor::access(&aspect, &id).unwrap_err();
+        assert_eq!(record.lock().unwrap().len(), 3);
+
+        // the cause of the error of the hook is kept
+        let cause = HEK::AccessTypeViolation.into_error();
+        assert_eq!(err.cause().map(|c| format!("{}", c)), Some(format!("{}", cause)));
+    }
+
+    #[test]
+    fn test_parallel_aspect_keeps_order_around_mutable_hooks() {
+        use store::Store;
+
+        let record = Arc::new(Mutex::new(vec![]));
+        let aspect = parallel_aspect(&[("a", false, true),
+                                       ("b", false, true),
+                                       ("m", true, true),
+                                       ("c", false, true)],
+                                     &record);
+
+        let store = Store::new_in_memory(PathBuf::from("/"), None).unwrap();
+        let mut fle = store.create(PathBuf::from("test")).unwrap();
+
+        assert!(aspect.access_mut(&mut fle).is_ok());
+
+        let record = record.lock().unwrap().clone();
+        assert_eq!(record.len(), 4);
+        assert!(record[0] == "a" || record[0] == "b");
+        assert!(record[1] == "a" || record[1] == "b");
+        assert_eq!(record[2], "m");
+        assert_eq!(record[3], "c");
+    }
+
+}
@@ -0,0 +1,556 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The header index of the store
+//!
+//! The index holds the headers of all entries in the store in one file, so queries on the headers
+//! do not have to read and parse every entry. In memory, it maps each header path to the values
+//! found there and each value to the ids of the entries which have it. Members of arrays (like
+//! the tags in `imag.tags` or the links in `imag.links`) are mapped to the ids of the entries as
+//! well, so looking up the entries with a certain tag does not touch the other entries. The index
+//! is only maintained by the store if the store configuration enables it:
+//!
+//! ```toml
+//! [store]
+//! header-index = true
+//! ```
+//!
+//! The store keeps the index up to date when entries are updated, deleted or moved and writes it
+//! after each change. While writing, the index file is locked and the changes are merged into the
+//! index which is on disk, so several processes working on the same store do not overwrite each
+//! others changes. If the index goes stale anyways (for example because entries were modified by
+//! hand), it can be rebuilt with `Store::rebuild_header_index()`.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use toml::{Parser, Value};
+
+use libimagerror::into::IntoError;
+use libimagerror::trace::trace_error;
+
+use error::StoreErrorKind as SEK;
+use error::MapErrInto;
+use file_abstraction::FileAbstraction;
+use file_abstraction::FileAbstractionInstance;
+use storeid::StoreId;
+use store::Entry;
+use store::EntryHeader;
+use store::Result;
+
+/// The name of the index file, relative to the store path
+pub static INDEX_FILE_NAME : &'static str = ".imag-header-index";
+
+/// Map from a header path to the values found at it, and from each value to the entries
+///
+/// Values are keyed by their TOML representation, as `Value` is not `Ord`.
+type ValueMap = BTreeMap<String, BTreeMap<String, BTreeSet<PathBuf>>>;
+
+/// The header index
+#[derive(Debug)]
+pub struct Index {
+    store_path: PathBuf,
+    headers: BTreeMap<PathBuf, EntryHeader>,
+
+    /// The ids of the entries by header path and value
+    values: ValueMap,
+
+    /// The ids of the entries by header path of an array and member of the array
+    members: ValueMap,
+
+    /// The changes since the index was written the last time. `None` means the entry was removed.
+    changes: BTreeMap<PathBuf, Option<EntryHeader>>,
+
+    /// Whether the index was cleared since it was written the last time, in which case the index
+    /// on disk is replaced instead of merged with the changes
+    cleared: bool,
+}
+
+impl Index {
+
+    /// Create a new, empty index for the store at `store_path`
+    pub fn new(store_path: PathBuf) -> Index {
+        Index {
+            store_path: store_path,
+            headers: BTreeMap::new(),
+            values: BTreeMap::new(),
+            members: BTreeMap::new(),
+            changes: BTreeMap::new(),
+            cleared: false,
+        }
+    }
+
+    /// Load the index for the store at `store_path`
+    ///
+    /// If there is no index file yet, an empty index is returned.
+    pub fn load(store_path: PathBuf, backend: &FileAbstraction) -> Result<Index> {
+        let mut index = Index::new(store_path);
+        let path      = index.file_path();
+
+        if !try!(backend.is_file(&path)) {
+            debug!("No header index found at {:?}, starting with empty index", path);
+            return Ok(index);
+        }
+
+        let content = try!(backend.new_instance(path).get_file_content());
+        try!(index.read_content(&content));
+
+        debug!("Loaded header index with {} entries", index.headers.len());
+        Ok(index)
+    }
+
+    /// Add the entries of the index file `content` to the index, without recording them as changes
+    fn read_content(&mut self, content: &str) -> Result<()> {
+        if content.is_empty() {
+            // The file was created by locking it, but nothing was written yet
+            return Ok(());
+        }
+
+        let table = try!(Parser::new(content)
+                         .parse()
+                         .ok_or(SEK::HeaderIndexParseError.into_error()));
+
+        match table.get("entries") {
+            Some(&Value::Table(ref entries)) => {
+                for (id, header) in entries.iter() {
+                    match *header {
+                        Value::Table(ref t) => {
+                            self.index_header(PathBuf::from(id), EntryHeader::from(t.clone()));
+                        },
+                        _ => {
+                            warn!("Header of {} in header index is not a table, ignoring", id);
+                        },
+                    }
+                }
+                Ok(())
+            },
+            Some(_) => Err(SEK::HeaderIndexParseError.into_error()),
+            None    => {
+                debug!("Header index is empty");
+                Ok(())
+            },
+        }
+    }
+
+    /// Write the changes to the index file, if there are any
+    ///
+    /// The index file is locked while it is written. The changes are applied to the index which is
+    /// on disk, so changes which were written by other processes in the meantime are kept and are
+    /// part of this index afterwards. If the index was cleared, the index on disk is replaced.
+    ///
+    /// If writing fails, the changes are kept and written by the next call.
+    pub fn save(&mut self, backend: &FileAbstraction) -> Result<()> {
+        if self.changes.is_empty() && !self.cleared {
+            debug!("Header index unchanged, not writing it");
+            return Ok(());
+        }
+
+        let mut file = backend.new_instance(self.file_path());
+        try!(file.lock().map_err_into(SEK::HeaderIndexWriteError));
+
+        let res = self.merge_into_file(&mut *file);
+        if let Err(e) = file.unlock() {
+            trace_error(&e);
+        }
+        res
+    }
+
+    fn merge_into_file(&mut self, file: &mut FileAbstractionInstance) -> Result<()> {
+        let mut merged = Index::new(self.store_path.clone());
+        if !self.cleared {
+            let content = try!(file.get_file_content());
+            try!(merged.read_content(&content));
+        }
+
+        for (id, header) in self.changes.iter() {
+            merged.unindex_header(id);
+            if let Some(ref header) = *header {
+                merged.index_header(id.clone(), header.clone());
+            }
+        }
+
+        let mut entries = BTreeMap::new();
+        for (id, header) in merged.headers.iter() {
+            let id = try!(id.to_str().ok_or(SEK::EncodingError.into_error()));
+            entries.insert(String::from(id), header.header().clone());
+        }
+
+        let mut table = BTreeMap::new();
+        table.insert(String::from("entries"), Value::Table(entries));
+
+        debug!("Writing header index with {} entries", merged.headers.len());
+        let content = ::toml::encode_str(&Value::Table(table));
+        try!(file.write_file_content(content.as_bytes()).map_err_into(SEK::HeaderIndexWriteError));
+
+        *self = merged;
+        Ok(())
+    }
+
+    /// The path of the index file
+    pub fn file_path(&self) -> PathBuf {
+        let mut path = self.store_path.clone();
+        path.push(INDEX_FILE_NAME);
+        path
+    }
+
+    /// Insert the header of `entry` into the index, replacing the header which was in the index for
+    /// this entry before
+    pub fn insert(&mut self, entry: &Entry) {
+        let id = entry.get_location().local().clone();
+        self.unindex_header(&id);
+        self.index_header(id.clone(), entry.get_header().clone());
+        self.changes.insert(id, Some(entry.get_header().clone()));
+    }
+
+    /// Remove the entry `id` from the index
+    pub fn remove(&mut self, id: &StoreId) {
+        if self.unindex_header(id.local()).is_some() {
+            self.changes.insert(id.local().clone(), None);
+        }
+    }
+
+    /// Move the indexed header of `old_id` to `new_id`
+    pub fn rename(&mut self, old_id: &StoreId, new_id: &StoreId) {
+        if let Some(header) = self.unindex_header(old_id.local()) {
+            self.changes.insert(old_id.local().clone(), None);
+            self.changes.insert(new_id.local().clone(), Some(header.clone()));
+            self.index_header(new_id.local().clone(), header);
+        }
+    }
+
+    /// Remove all entries from the index
+    pub fn clear(&mut self) {
+        self.headers.clear();
+        self.values.clear();
+        self.members.clear();
+        self.changes.clear();
+        self.cleared = true;
+    }
+
+    /// Add `header` to the maps, without recording it as change
+    fn index_header(&mut self, id: PathBuf, header: EntryHeader) {
+        {
+            let values  = &mut self.values;
+            let members = &mut self.members;
+            walk_header(header.header(), String::new(), &mut |path, value| {
+                if let Value::Array(ref a) = *value {
+                    for member in a {
+                        add_id(members, path, member, &id);
+                    }
+                }
+                add_id(values, path, value, &id);
+            });
+        }
+        self.headers.insert(id, header);
+    }
+
+    /// Remove the header of `id` from the maps, without recording it as change
+    fn unindex_header(&mut self, id: &PathBuf) -> Option<EntryHeader> {
+        let header = match self.headers.remove(id) {
+            Some(header) => header,
+            None         => return None,
+        };
+
+        {
+            let values  = &mut self.values;
+            let members = &mut self.members;
+            walk_header(header.header(), String::new(), &mut |path, value| {
+                if let Value::Array(ref a) = *value {
+                    for member in a {
+                        remove_id(members, path, member, id);
+                    }
+                }
+                remove_id(values, path, value, id);
+            });
+        }
+        Some(header)
+    }
+
+    /// The number of indexed entries
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Get the indexed header of the entry `id`
+    pub fn get_header(&self, id: &StoreId) -> Option<&EntryHeader> {
+        self.headers.get(id.local())
+    }
+
+    /// Read a header field of the entry `id` from the index
+    ///
+    /// Returns `None` if the entry is not in the index or the entry has no value at `spec`.
+    pub fn read(&self, id: &StoreId, spec: &str) -> Result<Option<Value>> {
+        match self.get_header(id) {
+            Some(header) => header.read(spec),
+            None         => Ok(None),
+        }
+    }
+
+    /// Get the ids of all indexed entries
+    pub fn ids(&self) -> Vec<StoreId> {
+        self.to_storeids(self.headers.keys())
+    }
+
+    /// Get the ids of all indexed entries of the module `mod_name`
+    pub fn ids_for_module(&self, mod_name: &str) -> Vec<StoreId> {
+        self.to_storeids(self.headers.keys().filter(|id| id.starts_with(mod_name)))
+    }
+
+    /// Get the ids of all entries where `f` returns true for the indexed header
+    ///
+    /// The `Entry` objects which are passed to `f` only contain the header, their content is
+    /// empty. Hence, `f` should only look at the header, which makes this suitable for the header
+    /// filters from libimagentryfilter. As `f` can look at any part of the header, it is called for
+    /// every indexed entry. Use `Index::find_by_value()` or `Index::find_by_array_member()` where
+    /// possible.
+    pub fn find<F>(&self, f: F) -> Vec<StoreId>
+        where F: Fn(&Entry) -> bool
+    {
+        self.ids()
+            .into_iter()
+            .filter(|sid| {
+                let mut entry = Entry::new(sid.clone());
+                if let Some(header) = self.headers.get(sid.local()) {
+                    *entry.get_header_mut() = header.clone();
+                }
+                f(&entry)
+            })
+            .collect()
+    }
+
+    /// Get the ids of all entries which have `value` at the header path `spec`
+    pub fn find_by_value(&self, spec: &str, value: &Value) -> Vec<StoreId> {
+        self.lookup(&self.values, spec, value)
+    }
+
+    /// Get the ids of all entries which have an array at the header path `spec`, which contains
+    /// `value`
+    pub fn find_by_array_member(&self, spec: &str, value: &Value) -> Vec<StoreId> {
+        self.lookup(&self.members, spec, value)
+    }
+
+    /// Get the ids of all entries which are tagged with `tag`
+    pub fn ids_with_tag(&self, tag: &str) -> Vec<StoreId> {
+        self.find_by_array_member("imag.tags", &Value::String(String::from(tag)))
+    }
+
+    /// Get the ids of all entries which link to `id`
+    pub fn ids_linking_to(&self, id: &StoreId) -> Vec<StoreId> {
+        match id.local().to_str() {
+            Some(s) => self.find_by_array_member("imag.links", &Value::String(String::from(s))),
+            None    => vec![],
+        }
+    }
+
+    fn lookup(&self, map: &ValueMap, spec: &str, value: &Value) -> Vec<StoreId> {
+        match map.get(spec).and_then(|values| values.get(&value.to_string())) {
+            Some(ids) => self.to_storeids(ids.iter()),
+            None      => vec![],
+        }
+    }
+
+    fn to_storeids<'a, I>(&self, ids: I) -> Vec<StoreId>
+        where I: Iterator<Item = &'a PathBuf>
+    {
+        ids.filter_map(|id| StoreId::new(Some(self.store_path.clone()), id.clone()).ok())
+            .collect()
+    }
+
+}
+
+/// Call `f` with the path and the value of each field in `value`, descending into tables
+fn walk_header<F>(value: &Value, path: String, f: &mut F)
+    where F: FnMut(&str, &Value)
+{
+    match *value {
+        Value::Table(ref t) => for (key, value) in t.iter() {
+            let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            walk_header(value, path, f);
+        },
+        _ => f(&path, value),
+    }
+}
+
+fn add_id(map: &mut ValueMap, path: &str, value: &Value, id: &PathBuf) {
+    map.entry(String::from(path))
+        .or_insert_with(BTreeMap::new)
+        .entry(value.to_string())
+        .or_insert_with(BTreeSet::new)
+        .insert(id.clone());
+}
+
+fn remove_id(map: &mut ValueMap, path: &str, value: &Value, id: &PathBuf) {
+    let now_empty = match map.get_mut(path) {
+        Some(values) => {
+            let key = value.to_string();
+            let no_ids = match values.get_mut(&key) {
+                Some(ids) => {
+                    ids.remove(id);
+                    ids.is_empty()
+                },
+                None => false,
+            };
+            if no_ids {
+                values.remove(&key);
+            }
+            values.is_empty()
+        },
+        None => false,
+    };
+
+    if now_empty {
+        map.remove(path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::Value;
+
+    use super::Index;
+    use file_abstraction::InMemoryFileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn entry(id: &str, tags: Vec<&str>) -> Entry {
+        let id        = StoreId::new_baseless(PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id);
+        let tags      = tags.into_iter().map(|t| Value::String(String::from(t))).collect();
+        entry.get_header_mut().insert("imag.tags", Value::Array(tags)).unwrap();
+        entry
+    }
+
+    fn get_index() -> Index {
+        let mut index = Index::new(PathBuf::from("/"));
+        index.insert(&entry("notes/a", vec!["work"]));
+        index.insert(&entry("notes/b", vec!["home"]));
+        index.insert(&entry("diary/c", vec!["work", "home"]));
+        index
+    }
+
+    #[test]
+    fn test_index_queries() {
+        let index = get_index();
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.ids_for_module("notes").len(), 2);
+        assert_eq!(index.ids_with_tag("work").len(), 2);
+        assert_eq!(index.ids_with_tag("nothing").len(), 0);
+
+        let ids = index.find(|e| e.get_location().local().starts_with("diary"));
+        assert_eq!(ids.len(), 1);
+        assert_eq!(ids[0].local(), &PathBuf::from("diary/c"));
+    }
+
+    #[test]
+    fn test_index_rename_remove() {
+        let mut index = get_index();
+        let a = StoreId::new_baseless(PathBuf::from("notes/a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("notes/d")).unwrap();
+
+        index.rename(&a, &d);
+        assert!(index.get_header(&a).is_none());
+        assert!(index.get_header(&d).is_some());
+
+        index.remove(&d);
+        assert!(index.get_header(&d).is_none());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_index_save_load() {
+        let backend   = InMemoryFileAbstraction::new();
+        let mut index = get_index();
+
+        assert!(index.save(&backend).is_ok());
+
+        let loaded = Index::load(PathBuf::from("/"), &backend).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.ids_with_tag("home").len(), 2);
+    }
+
+    #[test]
+    fn test_index_find_by_value() {
+        let mut index = get_index();
+        let mut e     = entry("notes/d", vec![]);
+        e.get_header_mut().insert("notes.name", Value::String(String::from("d"))).unwrap();
+        index.insert(&e);
+
+        let d = Value::String(String::from("d"));
+        assert_eq!(index.find_by_value("notes.name", &d).len(), 1);
+        assert_eq!(index.find_by_value("notes.other", &d).len(), 0);
+        assert_eq!(index.find_by_value("imag.tags", &Value::Array(vec![])).len(), 1);
+
+        let mut e = entry("notes/d", vec!["work"]);
+        e.get_header_mut().insert("notes.name", Value::String(String::from("e"))).unwrap();
+        index.insert(&e);
+
+        assert_eq!(index.find_by_value("notes.name", &d).len(), 0);
+        assert_eq!(index.ids_with_tag("work").len(), 3);
+    }
+
+    #[test]
+    fn test_index_save_merges_changes() {
+        let backend = InMemoryFileAbstraction::new();
+        assert!(get_index().save(&backend).is_ok());
+
+        // Two processes load the index and change different entries
+        let mut first  = Index::load(PathBuf::from("/"), &backend).unwrap();
+        let mut second = Index::load(PathBuf::from("/"), &backend).unwrap();
+        first.insert(&entry("notes/d", vec!["work"]));
+        second.remove(&StoreId::new_baseless(PathBuf::from("notes/a")).unwrap());
+
+        assert!(first.save(&backend).is_ok());
+        assert!(second.save(&backend).is_ok());
+        assert_eq!(second.ids_with_tag("work").len(), 2);
+
+        let loaded = Index::load(PathBuf::from("/"), &backend).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.ids_for_module("notes").len(), 2);
+    }
+
+    #[test]
+    fn test_index_save_smaller_on_fs() {
+        use tempdir::TempDir;
+        use file_abstraction::FSFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-index").unwrap();
+        let backend   = FSFileAbstraction::new();
+        let mut index = get_index();
+        index.store_path = dir.path().to_path_buf();
+        assert!(index.save(&backend).is_ok());
+
+        for id in vec!["notes/a", "notes/b"] {
+            index.remove(&StoreId::new_baseless(PathBuf::from(id)).unwrap());
+        }
+        assert!(index.save(&backend).is_ok());
+
+        let loaded = Index::load(dir.path().to_path_buf(), &backend).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.ids_with_tag("work").len(), 1);
+    }
+
+}
@@ -50,6 +50,10 @@ pub mod storeid;
 pub mod error;
 pub mod hook;
 pub mod store;
+pub mod file_abstraction;
+pub mod index;
+pub mod schema;
+pub mod migration;
+pub mod linkupdate;
 mod configuration;
-mod file_abstraction;
 
@@ -0,0 +1,41 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Updating the links between entries when entries are moved or deleted
+//!
+//! The store does not know how modules link entries to each other. A module which stores links in
+//! the headers of both linked entries registers a `LinkUpdater` with the store. If `update-links`
+//! is enabled in the store configuration, the store uses it to re-point the links of the linked
+//! entries when an entry is moved and to remove them when an entry is deleted.
+
+use std::fmt::Debug;
+
+use storeid::StoreId;
+use store::Entry;
+use store::Result;
+
+/// Updates the links of entries which are linked with a moved or deleted entry
+pub trait LinkUpdater : Debug + Send + Sync {
+
+    /// The ids of the entries which `entry` is linked with and which link back to it
+    fn linked(&self, entry: &Entry) -> Result<Vec<StoreId>>;
+
+    /// Re-point the link of `entry` to `old` to `new`, or remove it if `new` is `None`
+    fn update(&self, entry: &mut Entry, old: &StoreId, new: Option<&StoreId>) -> Result<()>;
+
+}
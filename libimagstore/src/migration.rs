@@ -0,0 +1,145 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Migrations of the store format
+//!
+//! Every entry records the version of imag it was written with in `imag.version`. If the layout of
+//! the header of a module changes between releases, the module registers a `Migration` with the
+//! store, which rewrites entries of older versions to the new layout.
+//!
+//! `Store::migrate()` applies all registered migrations to the entries which are older than the
+//! migrations, in order of their versions, and bumps `imag.version` of all migrated entries to the
+//! version of the running imag.
+
+use std::fmt::{Debug, Display, Formatter, Error as FmtError};
+use std::result::Result as RResult;
+
+use semver::Version;
+use toml::Value;
+
+use libimagerror::into::IntoError;
+
+use error::StoreErrorKind as SEK;
+use storeid::StoreId;
+use store::Entry;
+use store::Result;
+
+/// A migration of entries to a new version
+pub trait Migration : Debug + Send + Sync {
+
+    /// The module whose entries are migrated, or `None` if the migration applies to all entries
+    fn module(&self) -> Option<&str>;
+
+    /// The version the entries have after the migration
+    ///
+    /// All entries with an older `imag.version` are migrated.
+    fn version(&self) -> Version;
+
+    /// Migrate `entry` to the layout of `version()`
+    ///
+    /// The store sets `imag.version` itself, the migration does not have to do that.
+    fn migrate(&self, entry: &mut Entry) -> Result<()>;
+
+}
+
+/// What was (or, in a dry run, would be) done to an entry by `Store::migrate()`
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    id: StoreId,
+    from: Version,
+    to: Version,
+    applied: usize,
+}
+
+impl MigrationReport {
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// The version of the entry before the migration
+    pub fn from(&self) -> &Version {
+        &self.from
+    }
+
+    /// The version of the entry after the migration
+    pub fn to(&self) -> &Version {
+        &self.to
+    }
+
+    /// The number of migrations applied to the entry. If zero, only `imag.version` is bumped.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+}
+
+impl Display for MigrationReport {
+
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FmtError> {
+        write!(fmt, "{:?}: {} -> {} ({} migrations)",
+               self.id.local(), self.from, self.to, self.applied)
+    }
+
+}
+
+/// Get the version of imag the entry was written with
+pub fn entry_version(entry: &Entry) -> Result<Version> {
+    match try!(entry.get_header().read("imag.version")) {
+        Some(Value::String(ref s)) => Version::parse(&s[..])
+            .map_err(|_| SEK::HeaderTypeFailure.into_error()),
+        _ => Err(SEK::HeaderKeyNotFound.into_error()),
+    }
+}
+
+/// Apply `migrations` to `entry`, bumping it to `current`
+///
+/// `migrations` have to be sorted by their version. Returns `None` if the entry is up to date.
+pub fn migrate_entry(migrations: &[&Box<Migration>], entry: &mut Entry, current: &Version)
+    -> Result<Option<MigrationReport>>
+{
+    let from = try!(entry_version(entry));
+    if from >= *current {
+        return Ok(None);
+    }
+
+    let mut applied = 0;
+    for migration in migrations {
+        let applies = migration
+            .module()
+            .map(|m| entry.get_location().local().starts_with(m))
+            .unwrap_or(true);
+
+        let version = migration.version();
+        if applies && from < version && version <= *current {
+            debug!("Applying migration to {} on {:?}", version, entry.get_location());
+            try!(migration.migrate(entry));
+            applied += 1;
+        }
+    }
+
+    try!(entry.get_header_mut().set("imag.version", Value::String(format!("{}", current))));
+
+    Ok(Some(MigrationReport {
+        id: entry.get_location().clone(),
+        from: from,
+        to: current.clone(),
+        applied: applied,
+    }))
+}
@@ -0,0 +1,435 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Header schemas
+//!
+//! Modules can register a `Schema` for their part of the store, describing which header fields
+//! their entries have and of which type these fields are. The store checks entries against the
+//! schema of their module when they are updated and when the store is verified.
+//!
+//! A schema applies to all entries whose id starts with the module name, just like the ids
+//! created with `ModuleEntryPath`.
+//!
+//! Registered schemas are written to the `SCHEMA_DIR_NAME` directory of the store and loaded when
+//! the store is opened, so tools like `imag store verify` know the schemas of all modules without
+//! linking them.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Error as FmtError};
+
+use toml::Value;
+
+use storeid::StoreId;
+use store::EntryHeader;
+
+/// The name of the directory in the store where the registered schemas are persisted
+pub static SCHEMA_DIR_NAME : &'static str = ".imag-schemas";
+
+/// The type a header field is expected to have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Datetime,
+    Array,
+    Table,
+
+    /// Any type is fine, the field only has to be present
+    Any,
+}
+
+impl FieldType {
+
+    /// Check whether `v` is of this type
+    pub fn matches(&self, v: &Value) -> bool {
+        match (*self, v) {
+            (FieldType::Any, _)                       => true,
+            (FieldType::String,   &Value::String(_))   => true,
+            (FieldType::Integer,  &Value::Integer(_))  => true,
+            (FieldType::Float,    &Value::Float(_))    => true,
+            (FieldType::Boolean,  &Value::Boolean(_))  => true,
+            (FieldType::Datetime, &Value::Datetime(_)) => true,
+            (FieldType::Array,    &Value::Array(_))    => true,
+            (FieldType::Table,    &Value::Table(_))    => true,
+            _                                          => false,
+        }
+    }
+
+    /// Get the type of `v`
+    pub fn of(v: &Value) -> FieldType {
+        match *v {
+            Value::String(_)   => FieldType::String,
+            Value::Integer(_)  => FieldType::Integer,
+            Value::Float(_)    => FieldType::Float,
+            Value::Boolean(_)  => FieldType::Boolean,
+            Value::Datetime(_) => FieldType::Datetime,
+            Value::Array(_)    => FieldType::Array,
+            Value::Table(_)    => FieldType::Table,
+        }
+    }
+
+    /// Get the type by its name, as it is displayed
+    pub fn from_name(name: &str) -> Option<FieldType> {
+        match name {
+            "string"   => Some(FieldType::String),
+            "integer"  => Some(FieldType::Integer),
+            "float"    => Some(FieldType::Float),
+            "boolean"  => Some(FieldType::Boolean),
+            "datetime" => Some(FieldType::Datetime),
+            "array"    => Some(FieldType::Array),
+            "table"    => Some(FieldType::Table),
+            "any"      => Some(FieldType::Any),
+            _          => None,
+        }
+    }
+
+}
+
+impl Display for FieldType {
+
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        let s = match *self {
+            FieldType::String   => "string",
+            FieldType::Integer  => "integer",
+            FieldType::Float    => "float",
+            FieldType::Boolean  => "boolean",
+            FieldType::Datetime => "datetime",
+            FieldType::Array    => "array",
+            FieldType::Table    => "table",
+            FieldType::Any      => "any",
+        };
+        write!(fmt, "{}", s)
+    }
+
+}
+
+/// A single field in a `Schema`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    path: String,
+    ftype: FieldType,
+    required: bool,
+}
+
+impl Field {
+
+    /// The header path of the field, for example "counter.name"
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn field_type(&self) -> FieldType {
+        self.ftype
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+}
+
+/// The header schema of a module
+///
+/// ```ignore
+///  let schema = Schema::new("counter")
+///      .required("counter.name", FieldType::String)
+///      .required("counter.value", FieldType::Integer)
+///      .optional("counter.unit", FieldType::String);
+///
+///  try!(store.register_schema(schema));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    module: String,
+    fields: Vec<Field>,
+}
+
+impl Schema {
+
+    /// Create a new, empty schema for the module `module`
+    pub fn new<S: Into<String>>(module: S) -> Schema {
+        Schema {
+            module: module.into(),
+            fields: vec![],
+        }
+    }
+
+    /// Add a field which must be present in all entries of the module
+    pub fn required<S: Into<String>>(self, path: S, ftype: FieldType) -> Schema {
+        self.field(path.into(), ftype, true)
+    }
+
+    /// Add a field which does not have to be present, but must have the type `ftype` if it is
+    pub fn optional<S: Into<String>>(self, path: S, ftype: FieldType) -> Schema {
+        self.field(path.into(), ftype, false)
+    }
+
+    fn field(mut self, path: String, ftype: FieldType, required: bool) -> Schema {
+        self.fields.push(Field {
+            path: path,
+            ftype: ftype,
+            required: required,
+        });
+        self
+    }
+
+    /// The name of the module this schema belongs to
+    pub fn module(&self) -> &String {
+        &self.module
+    }
+
+    pub fn fields(&self) -> &Vec<Field> {
+        &self.fields
+    }
+
+    /// Convert the schema to a TOML table, in which it is persisted in the store
+    pub fn to_value(&self) -> Value {
+        let fields = self.fields
+            .iter()
+            .map(|field| {
+                let mut t = BTreeMap::new();
+                t.insert(String::from("path"), Value::String(field.path.clone()));
+                t.insert(String::from("type"), Value::String(format!("{}", field.ftype)));
+                t.insert(String::from("required"), Value::Boolean(field.required));
+                Value::Table(t)
+            })
+            .collect();
+
+        let mut table = BTreeMap::new();
+        table.insert(String::from("module"), Value::String(self.module.clone()));
+        table.insert(String::from("fields"), Value::Array(fields));
+        Value::Table(table)
+    }
+
+    /// Read a schema from a TOML table as created by `Schema::to_value()`
+    ///
+    /// Returns `None` if the table is not a valid schema.
+    pub fn from_value(v: &Value) -> Option<Schema> {
+        let table = match *v {
+            Value::Table(ref t) => t,
+            _                   => return None,
+        };
+
+        let mut schema = match table.get("module") {
+            Some(&Value::String(ref module)) => Schema::new(module.clone()),
+            _                                => return None,
+        };
+
+        let fields = match table.get("fields") {
+            Some(&Value::Array(ref fields)) => fields,
+            _                               => return None,
+        };
+
+        for field in fields {
+            let field = match *field {
+                Value::Table(ref t) => t,
+                _                   => return None,
+            };
+
+            let path = match field.get("path") {
+                Some(&Value::String(ref path)) => path.clone(),
+                _                              => return None,
+            };
+
+            let ftype = match field.get("type") {
+                Some(&Value::String(ref name)) => match FieldType::from_name(name) {
+                    Some(ftype) => ftype,
+                    None        => return None,
+                },
+                _ => return None,
+            };
+
+            let required = match field.get("required") {
+                Some(&Value::Boolean(b)) => b,
+                _                        => return None,
+            };
+
+            schema = schema.field(path, ftype, required);
+        }
+
+        Some(schema)
+    }
+
+    /// Check whether the entry `id` belongs to the module of this schema
+    pub fn applies_to(&self, id: &StoreId) -> bool {
+        id.local()
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .map(|c| c == self.module)
+            .unwrap_or(false)
+    }
+
+    /// Check the header of the entry `id` against this schema
+    ///
+    /// Returns all violations, so an empty vector means that the header is valid.
+    pub fn check(&self, id: &StoreId, header: &EntryHeader) -> Vec<SchemaViolation> {
+        self.fields
+            .iter()
+            .filter_map(|field| {
+                let kind = match header.read(&field.path[..]) {
+                    Ok(Some(ref v)) => if field.ftype.matches(v) {
+                        None
+                    } else {
+                        Some(ViolationKind::WrongType {
+                            expected: field.ftype,
+                            found: FieldType::of(v),
+                        })
+                    },
+                    Ok(None) | Err(_) => if field.required {
+                        Some(ViolationKind::Missing)
+                    } else {
+                        None
+                    },
+                };
+
+                kind.map(|kind| {
+                    SchemaViolation {
+                        id: id.clone(),
+                        path: field.path.clone(),
+                        kind: kind,
+                    }
+                })
+            })
+            .collect()
+    }
+
+}
+
+/// The ways a header can violate a schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A required field is missing
+    Missing,
+
+    /// A field has the wrong type
+    WrongType {
+        expected: FieldType,
+        found: FieldType,
+    },
+}
+
+/// A field of an entry which does not match the schema of its module
+#[derive(Debug, Clone)]
+pub struct SchemaViolation {
+    id: StoreId,
+    path: String,
+    kind: ViolationKind,
+}
+
+impl SchemaViolation {
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// The header path of the violating field
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+
+}
+
+impl Display for SchemaViolation {
+
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        match self.kind {
+            ViolationKind::Missing => {
+                write!(fmt, "{:?}: Required field '{}' is missing", self.id.local(), self.path)
+            },
+            ViolationKind::WrongType { expected, found } => {
+                write!(fmt, "{:?}: Field '{}' should be {}, but is {}",
+                       self.id.local(), self.path, expected, found)
+            },
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use toml::Value;
+
+    use super::{FieldType, Schema, ViolationKind};
+    use storeid::StoreId;
+    use store::EntryHeader;
+
+    fn schema() -> Schema {
+        Schema::new("counter")
+            .required("counter.name", FieldType::String)
+            .required("counter.value", FieldType::Integer)
+            .optional("counter.unit", FieldType::String)
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let schema = schema();
+        assert!(schema.applies_to(&id("counter/foo")));
+        assert!(!schema.applies_to(&id("counters/foo")));
+        assert!(!schema.applies_to(&id("notes/counter")));
+    }
+
+    #[test]
+    fn test_check_valid() {
+        let mut header = EntryHeader::new();
+        header.insert("counter", Value::Table(BTreeMap::new())).unwrap();
+        header.insert("counter.name", Value::String(String::from("foo"))).unwrap();
+        header.insert("counter.value", Value::Integer(1)).unwrap();
+
+        assert!(schema().check(&id("counter/foo"), &header).is_empty());
+    }
+
+    #[test]
+    fn test_check_violations() {
+        let mut header = EntryHeader::new();
+        header.insert("counter", Value::Table(BTreeMap::new())).unwrap();
+        header.insert("counter.value", Value::String(String::from("1"))).unwrap();
+        header.insert("counter.unit", Value::Integer(1)).unwrap();
+
+        let violations = schema().check(&id("counter/foo"), &header);
+        assert_eq!(violations.len(), 3);
+        assert_eq!(violations[0].path(), "counter.name");
+        assert_eq!(violations[0].kind(), &ViolationKind::Missing);
+        assert_eq!(violations[1].kind(), &ViolationKind::WrongType {
+            expected: FieldType::Integer,
+            found: FieldType::String,
+        });
+    }
+
+    #[test]
+    fn test_value_roundtrip() {
+        let schema = schema();
+        assert_eq!(Some(schema.clone()), Schema::from_value(&schema.to_value()));
+        assert_eq!(None, Schema::from_value(&Value::String(String::from("counter"))));
+    }
+
+}
@@ -23,6 +23,7 @@ use std::path::PathBuf;
 use std::result::Result as RResult;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
 use std::collections::BTreeMap;
 use std::io::Read;
 use std::convert::From;
@@ -36,7 +37,6 @@ use std::fmt::Error as FMTError;
 
 use toml::{Table, Value};
 use regex::Regex;
-use glob::glob;
 use walkdir::WalkDir;
 use walkdir::Iter as WalkDirIter;
 
@@ -45,6 +45,18 @@ use error::{StoreError as SE, StoreErrorKind as SEK};
 use error::MapErrInto;
 use storeid::{IntoStoreId, StoreId, StoreIdIterator};
 use file_abstraction::FileAbstraction;
+use file_abstraction::FileAbstractionInstance;
+use file_abstraction::FSFileAbstraction;
+use file_abstraction::InMemoryFileAbstraction;
+use index::Index;
+use index::INDEX_FILE_NAME;
+use schema::Schema;
+use schema::SchemaViolation;
+use schema::SCHEMA_DIR_NAME;
+use migration::Migration;
+use migration::MigrationReport;
+use migration::migrate_entry;
+use linkupdate::LinkUpdater;
 
 use hook::aspect::Aspect;
 use hook::error::HookErrorKind;
@@ -59,8 +71,6 @@ use libimagerror::trace::trace_error;
 use libimagutil::iter::FoldResult;
 use libimagutil::debug_result::*;
 
-use self::glob_store_iter::*;
-
 /// The Result Type returned by any interaction with the store that could fail
 pub type Result<T> = RResult<T, SE>;
 
@@ -73,10 +83,13 @@ enum StoreEntryStatus {
 
 /// A store entry, depending on the option type it is either borrowed currently
 /// or not.
+///
+/// While the entry is borrowed, its file is locked with an advisory lock, so other `Store`
+/// objects, also in other processes, cannot borrow it at the same time.
 #[derive(Debug)]
 struct StoreEntry {
     id: StoreId,
-    file: FileAbstraction,
+    file: Box<FileAbstractionInstance>,
     status: StoreEntryStatus,
 }
 
@@ -144,11 +157,11 @@ impl Iterator for Walk {
 
 impl StoreEntry {
 
-    fn new(id: StoreId) -> Result<StoreEntry> {
+    fn new(id: StoreId, backend: &Box<FileAbstraction>) -> Result<StoreEntry> {
         let pb = try!(id.clone().into_pathbuf());
         Ok(StoreEntry {
             id: id,
-            file: FileAbstraction::Absent(pb),
+            file: backend.new_instance(pb),
             status: StoreEntryStatus::Present,
         })
     }
@@ -159,20 +172,40 @@ impl StoreEntry {
         self.status == StoreEntryStatus::Borrowed
     }
 
+    /// Borrow the entry: lock its file and mark it as borrowed
+    ///
+    /// Fails if the entry is borrowed already, either by this store or by another one.
+    fn borrow(&mut self) -> Result<()> {
+        if self.is_borrowed() {
+            return Err(SEK::EntryAlreadyBorrowed.into_error());
+        }
+
+        try!(self.file.lock().map_err_into(SEK::EntryAlreadyBorrowed));
+        self.status = StoreEntryStatus::Borrowed;
+        Ok(())
+    }
+
+    /// Release the entry: unlock its file and mark it as present
+    fn release(&mut self) -> Result<()> {
+        self.status = StoreEntryStatus::Present;
+        self.file.unlock()
+    }
+
     fn get_entry(&mut self) -> Result<Entry> {
         let id = &self.id.clone();
-        if !self.is_borrowed() {
-            self.file
-                .get_file_content()
-                .and_then(|mut file| Entry::from_reader(id.clone(), &mut file))
-                .or_else(|err| if err.err_type() == SEK::FileNotFound {
-                    Ok(Entry::new(id.clone()))
-                } else {
-                    Err(err)
-                })
-        } else {
-            Err(SE::new(SEK::EntryAlreadyBorrowed, None))
-        }
+        self.file
+            .get_file_content()
+            .and_then(|content| if content.is_empty() {
+                // The file was created by locking it, but nothing was written yet
+                Ok(Entry::new(id.clone()))
+            } else {
+                Entry::from_str(id.clone(), &content[..])
+            })
+            .or_else(|err| if err.err_type() == SEK::FileNotFound {
+                Ok(Entry::new(id.clone()))
+            } else {
+                Err(err)
+            })
     }
 
     fn write_entry(&mut self, entry: &Entry) -> Result<()> {
@@ -216,24 +249,70 @@ pub struct Store {
     /**
      * Internal Path->File cache map
      *
-     * Caches the files, so they remain flock()ed
+     * Caches the files, so they remain flock()ed while they are borrowed
      *
      * Could be optimized for a threadsafe HashMap
      */
     entries: Arc<RwLock<HashMap<StoreId, StoreEntry>>>,
+
+    /// The maximum number of entries which are kept in `entries` when they are not borrowed
+    cache_size: usize,
+
+    /// Whether moving and deleting entries updates the links of the entries linked to them
+    update_links: bool,
+
+    /// The link updaters registered by the modules, used if `update_links` is set
+    link_updaters: RwLock<Vec<Box<LinkUpdater>>>,
+
+    /// The backend to use
+    ///
+    /// This provides the filesystem-operation functions (or pretends to)
+    backend: Box<FileAbstraction>,
+
+    /// The header index, if enabled in the configuration
+    index: Option<RwLock<Index>>,
+
+    /// The header schemas registered by the modules, by module name
+    schemas: RwLock<HashMap<String, Schema>>,
+
+    /// The migrations registered by the modules
+    migrations: RwLock<Vec<Box<Migration>>>,
 }
 
 impl Store {
 
     /// Create a new Store object
+    ///
+    /// The store operates on the filesystem. Use `Store::new_with_backend()` to use another
+    /// storage backend.
     pub fn new(location: PathBuf, store_config: Option<Value>) -> Result<Store> {
+        Store::new_with_backend(location, store_config, Box::new(FSFileAbstraction::new()))
+    }
+
+    /// Create a new Store object which keeps all entries in memory, for example for tests
+    ///
+    /// The entries are gone when the Store object is dropped.
+    pub fn new_in_memory(location: PathBuf, store_config: Option<Value>) -> Result<Store> {
+        let backend = InMemoryFileAbstraction::new();
+        try!(backend.create_dir_all(&location).map_err_into(SEK::StorePathCreate));
+        Store::new_with_backend(location, store_config, Box::new(backend))
+    }
+
+    /// Create a new Store object which uses `backend` to access the entries
+    ///
+    /// See `Store::new_in_memory()` to run the store in-memory.
+    pub fn new_with_backend(location: PathBuf,
+                            store_config: Option<Value>,
+                            backend: Box<FileAbstraction>)
+        -> Result<Store>
+    {
         use configuration::*;
 
         debug!("Validating Store configuration");
         let _ = try!(config_is_valid(&store_config).map_err_into(SEK::ConfigurationError));
 
         debug!("Building new Store object");
-        if !location.exists() {
+        if !try!(backend.exists(&location)) {
             if !config_implicit_store_create_allowed(store_config.as_ref()) {
                 warn!("Implicitely creating store directory is denied");
                 warn!(" -> Either because configuration does not allow it");
@@ -243,10 +322,10 @@ impl Store {
                     .map_err_into(SEK::IoError);
             }
 
-            try!(FileAbstraction::create_dir_all(&location)
+            try!(backend.create_dir_all(&location)
                  .map_err_into(SEK::StorePathCreate)
                  .map_dbg_err_str("Failed"));
-        } else if location.is_file() {
+        } else if try!(backend.is_file(&location)) {
             debug!("Store path exists as file");
             return Err(SEK::StorePathExists.into_error());
         }
@@ -317,6 +396,23 @@ impl Store {
                 Aspect::new(n, cfg)
             }).collect();
 
+        let index = if config_header_index_enabled(store_config.as_ref()) {
+            debug!("Loading header index");
+            let index = Index::load(location.clone(), &*backend).unwrap_or_else(|e| {
+                trace_error(&e);
+                warn!("Could not load header index, starting with an empty one");
+                warn!("Use the rebuild command to rebuild it from the entries");
+                Index::new(location.clone())
+            });
+            Some(RwLock::new(index))
+        } else {
+            None
+        };
+
+        let cache_size   = config_cache_size(store_config.as_ref());
+        let update_links = config_update_links(store_config.as_ref());
+        let schemas      = load_schemas(&location, &*backend);
+
         let store = Store {
             location: location.clone(),
             configuration: store_config,
@@ -334,6 +430,13 @@ impl Store {
             pre_move_aspects    : Arc::new(Mutex::new(pre_move_aspects)),
             post_move_aspects   : Arc::new(Mutex::new(post_move_aspects)),
             entries: Arc::new(RwLock::new(HashMap::new())),
+            cache_size: cache_size,
+            update_links: update_links,
+            link_updaters: RwLock::new(vec![]),
+            backend: backend,
+            index: index,
+            schemas: RwLock::new(schemas),
+            migrations: RwLock::new(vec![]),
         };
 
         debug!("Store building succeeded");
@@ -357,49 +460,53 @@ impl Store {
         info!("Header | Content length | Path");
         info!("-------+----------------+-----");
 
-        WalkDir::new(self.location.clone())
-            .into_iter()
-            .map(|res| {
-                match res {
-                    Ok(dent) => {
-                        if dent.file_type().is_file() {
-                            match self.get(PathBuf::from(dent.path())) {
-                                Ok(Some(fle)) => {
-                                    let p           = fle.get_location();
-                                    let content_len = fle.get_content().len();
-                                    let header      = if fle.get_header().verify().is_ok() {
-                                        "ok"
-                                    } else {
-                                        "broken"
-                                    };
-
-                                    info!("{: >6} | {: >14} | {:?}", header, content_len, p.deref());
-                                },
-
-                                Ok(None) => {
-                                    info!("{: >6} | {: >14} | {:?}", "?", "couldn't load", dent.path());
-                                },
-
-                                Err(e) => {
-                                    debug!("{:?}", e);
-                                },
-                            }
+        let ids = match self.entries() {
+            Ok(ids) => ids,
+            Err(e) => {
+                trace_error(&e);
+                return false;
+            },
+        };
+
+        // The entries are only read, not retrieved: retrieved entries would be written back (and
+        // checked against the schemas again) when they are dropped
+        ids.map(|id| {
+                match self.retrieve_copy(id.clone()) {
+                    Ok(entry) => {
+                        let content_len = entry.get_content().len();
+                        let violations  = self.check_schema(&entry).unwrap_or_else(|e| {
+                            debug!("{:?}", e);
+                            vec![]
+                        });
+                        let header      = if entry.get_header().verify().is_err() {
+                            "broken"
+                        } else if !violations.is_empty() {
+                            "schema"
                         } else {
-                            info!("{: >6} | {: >14} | {:?}", "?", "<no file>", dent.path());
+                            "ok"
+                        };
+
+                        info!("{: >6} | {: >14} | {:?}", header, content_len, id.local());
+                        for violation in violations.iter() {
+                            warn!("{}", violation);
                         }
+
+                        header == "ok"
                     },
 
                     Err(e) => {
                         debug!("{:?}", e);
+                        info!("{: >6} | {: >14} | {:?}", "?", "couldn't load", id.local());
+                        false
                     },
                 }
-
-                true
             })
-            .all(|b| b)
+            .fold(true, |acc, b| acc && b)
     }
 
     /// Creates the Entry at the given location (inside the entry)
+    ///
+    /// If a post-create hook fails, the entry is removed again.
     pub fn create<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
         let id = try!(id.into_storeid()).with_base(self.path().clone());
         if let Err(e) = self.execute_hooks_for_id(self.pre_create_aspects.clone(), &id) {
@@ -415,22 +522,52 @@ impl Store {
                 Ok(s) => s,
             };
 
-            if hsmap.contains_key(&id) {
+            // entries which are not borrowed might have been evicted from the cache, so we have to
+            // ask the backend as well
+            let exists = hsmap.contains_key(&id) || try!(id.clone()
+                .into_pathbuf()
+                .and_then(|pb| self.backend.exists(&pb))
+                .map_err_into(SEK::CreateCallError));
+
+            if exists {
                 return Err(SEK::EntryAlreadyExists.into_error()).map_err_into(SEK::CreateCallError);
             }
-            hsmap.insert(id.clone(), {
-                let mut se = try!(StoreEntry::new(id.clone()));
-                se.status = StoreEntryStatus::Borrowed;
-                se
-            });
+
+            let mut se = try!(StoreEntry::new(id.clone(), &self.backend));
+            try!(se.borrow().map_err_into(SEK::CreateCallError));
+            hsmap.insert(id.clone(), se);
         }
 
-        let mut fle = FileLockEntry::new(self, Entry::new(id));
-        self.execute_hooks_for_mut_file(self.post_create_aspects.clone(), &mut fle)
-            .map_err_into(SEK::PostHookExecuteError)
-            .map_err_into(SEK::HookExecutionError)
-            .map_err_into(SEK::CreateCallError)
-            .map(|_| fle)
+        let mut fle = FileLockEntry::new(self, Entry::new(id.clone()));
+        let res = self.execute_hooks_for_mut_file(self.post_create_aspects.clone(), &mut fle);
+        if let Err(e) = res {
+            // Borrowing the entry created its file, which would be left behind empty otherwise
+            fle.write_on_drop = false;
+            drop(fle);
+            if let Err(re) = self.remove_created(&id) {
+                trace_error(&re);
+            }
+
+            return Err(e)
+                .map_err_into(SEK::PostHookExecuteError)
+                .map_err_into(SEK::HookExecutionError)
+                .map_err_into(SEK::CreateCallError);
+        }
+        Ok(fle)
+    }
+
+    /// Remove the entry `id` from the cache and its file, after creating it failed
+    fn remove_created(&self, id: &StoreId) -> Result<()> {
+        try!(self.entries
+             .write()
+             .map_err(|_| SEK::LockPoisoned.into_error()))
+            .remove(id);
+
+        let pb = try!(id.clone().into_pathbuf());
+        if try!(self.backend.is_file(&pb)) {
+            try!(self.backend.remove_file(&pb));
+        }
+        Ok(())
     }
 
     /// Borrow a given Entry. When the `FileLockEntry` is either `update`d or
@@ -452,11 +589,16 @@ impl Store {
                 .write()
                 .map_err(|_| SE::new(SEK::LockPoisoned, None))
                 .and_then(|mut es| {
-                    let new_se = try!(StoreEntry::new(id.clone()));
+                    let new_se = try!(StoreEntry::new(id.clone(), &self.backend));
                     let mut se = es.entry(id.clone()).or_insert(new_se);
-                    let entry = se.get_entry();
-                    se.status = StoreEntryStatus::Borrowed;
-                    entry
+                    try!(se.borrow());
+                    match se.get_entry() {
+                        Ok(entry) => Ok(entry),
+                        Err(e) => {
+                            let _ = se.release();
+                            Err(e)
+                        },
+                    }
                 })
                 .map_err_into(SEK::RetrieveCallError)
         });
@@ -482,7 +624,12 @@ impl Store {
             .map_err_into(SEK::GetCallError)
         );
 
-        if !exists && !id.exists() {
+        let exists = exists || try!(id.clone()
+            .into_pathbuf()
+            .and_then(|pb| self.backend.exists(&pb))
+            .map_err_into(SEK::GetCallError));
+
+        if !exists {
             debug!("Does not exist in internal cache or filesystem: {:?}", id);
             return Ok(None);
         }
@@ -491,22 +638,149 @@ impl Store {
     }
 
     /// Iterate over all StoreIds for one module name
+    ///
+    /// If the header index is enabled, the ids are taken from the index and the store directory
+    /// is not walked.
     pub fn retrieve_for_module(&self, mod_name: &str) -> Result<StoreIdIterator> {
+        if let Some(ref index) = self.index {
+            let mut ids = try!(index
+                               .read()
+                               .map_err(|_| SEK::LockPoisoned.into_error())
+                               .map_err_into(SEK::RetrieveForModuleCallError))
+                .ids_for_module(mod_name);
+
+            try!(self.add_cached_ids(&mut ids, |id| id.local().starts_with(mod_name))
+                 .map_err_into(SEK::RetrieveForModuleCallError));
+            return Ok(StoreIdIterator::new(Box::new(ids.into_iter())));
+        }
+
         let mut path = self.path().clone();
         path.push(mod_name);
 
-        path.to_str()
-            .ok_or(SE::new(SEK::EncodingError, None))
-            .and_then(|path| {
-                let path = [ path, "/**/*" ].join("");
-                debug!("glob()ing with '{}'", path);
-                glob(&path[..]).map_err_into(SEK::GlobError)
+        debug!("Listing entries below {:?}", path);
+        let store_path = self.path().clone();
+        self.entry_pathes_below(path)
+            .map(|pathes| {
+                let iter = pathes.into_iter().filter_map(move |p| {
+                    StoreId::from_full_path(&store_path, p)
+                        .map_err(|e| {
+                            debug!("Could not build StoreId: {:?}", e);
+                            trace_error(&e);
+                        })
+                        .ok()
+                });
+                StoreIdIterator::new(Box::new(iter))
             })
-            .map(|paths| GlobStoreIdIterator::new(paths, self.path().clone()).into())
-            .map_err_into(SEK::GlobError)
             .map_err_into(SEK::RetrieveForModuleCallError)
     }
 
+    /// Get the ids of all entries in the store
+    pub fn entries(&self) -> Result<StoreIdIterator> {
+        let store_path = self.path().clone();
+        self.entry_pathes()
+            .map(|pathes| {
+                let iter = pathes.into_iter().filter_map(move |p| {
+                    StoreId::from_full_path(&store_path, p)
+                        .map_err(|e| {
+                            debug!("Could not build StoreId: {:?}", e);
+                            trace_error(&e);
+                        })
+                        .ok()
+                });
+                StoreIdIterator::new(Box::new(iter))
+            })
+            .map_err_into(SEK::EntriesCallError)
+    }
+
+    /// Get the ids of the entries for which `f` returns true, optionally only the ones of the
+    /// module `mod_name`
+    ///
+    /// Entries which are borrowed right now are matched with the header they had when they were
+    /// written the last time.
+    ///
+    /// If the header index is enabled, `f` is called with the indexed headers and the entries
+    /// are not read. The `Entry` objects passed to `f` have no content then, so `f` should only
+    /// look at the header, like the header filters from libimagentryfilter do. Entries which are
+    /// in the cache of the store but not in the index yet (because they were created, but not
+    /// written yet) are read from their files.
+    pub fn find_by_header<F>(&self, mod_name: Option<&str>, f: F) -> Result<Vec<StoreId>>
+        where F: Fn(&Entry) -> bool
+    {
+        let in_module = |id: &StoreId| mod_name.map(|m| id.local().starts_with(m)).unwrap_or(true);
+
+        let (mut found, to_read) = if let Some(ref index) = self.index {
+            let index = try!(index
+                             .read()
+                             .map_err(|_| SEK::LockPoisoned.into_error())
+                             .map_err_into(SEK::FindByHeaderCallError));
+            let found = index.find(|entry| in_module(entry.get_location()) && f(entry));
+
+            let mut cached = vec![];
+            try!(self.add_cached_ids(&mut cached, |id| {
+                in_module(id) && index.get_header(id).is_none()
+            }).map_err_into(SEK::FindByHeaderCallError));
+
+            (found, cached)
+        } else {
+            let ids = match mod_name {
+                Some(name) => self.retrieve_for_module(name),
+                None       => self.entries(),
+            };
+            (vec![], try!(ids.map_err_into(SEK::FindByHeaderCallError)).collect())
+        };
+
+        for id in to_read {
+            // Not using `retrieve_copy()`, as it fails for borrowed entries
+            match StoreEntry::new(id.clone(), &self.backend).and_then(|mut se| se.get_entry()) {
+                Ok(entry) => if f(&entry) {
+                    found.push(id);
+                },
+                Err(e) => trace_error(&e),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Take a read-only snapshot of the store
+    ///
+    /// Returns copies of all entries in the store, or only the ones of the module `mod_name`, if
+    /// passed. Fails with `IdLocked` if one of the entries is currently borrowed, as the snapshot
+    /// would not be consistent otherwise.
+    pub fn snapshot(&self, mod_name: Option<&str>) -> Result<Vec<Entry>> {
+        let ids = match mod_name {
+            Some(name) => self.retrieve_for_module(name),
+            None       => self.entries(),
+        };
+
+        try!(ids.map_err_into(SEK::SnapshotCallError))
+            .map(|id| self.retrieve_copy(id))
+            .collect::<Result<Vec<Entry>>>()
+            .map_err_into(SEK::SnapshotCallError)
+    }
+
+    /// Import an entry into the store
+    ///
+    /// The entry is created through `Store::create()`, so all hooks fire as if the entry was
+    /// created by hand. Fails with `EntryAlreadyExists` if there is an entry with the same id
+    /// in the store already.
+    pub fn import_entry(&self, entry: Entry) -> Result<()> {
+        let id = entry.get_location().clone().with_base(self.path().clone());
+
+        let exists = try!(id.clone()
+            .into_pathbuf()
+            .and_then(|pb| self.backend.exists(&pb))
+            .map_err_into(SEK::ImportCallError));
+
+        if exists {
+            return Err(SEK::EntryAlreadyExists.into_error()).map_err_into(SEK::ImportCallError);
+        }
+
+        let mut fle = try!(self.create(id).map_err_into(SEK::ImportCallError));
+        *fle.get_header_mut()  = entry.get_header().clone();
+        *fle.get_content_mut() = entry.get_content().clone();
+        self.update(fle).map_err_into(SEK::ImportCallError)
+    }
+
     // Walk the store tree for the module
     pub fn walk<'a>(&'a self, mod_name: &str) -> Walk {
         Walk::new(self.path().clone(), mod_name)
@@ -534,19 +808,28 @@ impl Store {
             Ok(e) => e,
         };
 
-        let mut se = try!(hsmap.get_mut(&entry.location).ok_or(SE::new(SEK::IdNotFound, None)));
+        {
+            let mut se = try!(hsmap.get_mut(&entry.location).ok_or(SE::new(SEK::IdNotFound, None)));
 
-        assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
+            assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
 
-        debug!("Verifying Entry");
-        try!(entry.entry.verify());
+            debug!("Verifying Entry");
+            try!(entry.entry.verify());
+            for violation in try!(self.check_schema(&entry.entry)) {
+                warn!("{}", violation);
+            }
 
-        debug!("Writing Entry");
-        try!(se.write_entry(&entry.entry));
+            debug!("Writing Entry");
+            try!(se.write_entry(&entry.entry));
+            if modify_presence {
+                try!(se.release());
+            }
+        }
+        self.with_header_index(|index| index.insert(&entry.entry));
         if modify_presence {
-            se.status = StoreEntryStatus::Present;
+            self.evict_cache(&mut hsmap);
         }
-
+        drop(hsmap);
 
         self.execute_hooks_for_mut_file(self.post_update_aspects.clone(), &mut entry)
             .map_err_into(SEK::PostHookExecuteError)
@@ -554,6 +837,43 @@ impl Store {
             .map_err_into(SEK::UpdateCallError)
     }
 
+    /// Internal method to release a borrowed entry without writing it
+    fn _release(&self, id: &StoreId) -> Result<()> {
+        let mut hsmap = match self.entries.write() {
+            Err(_) => return Err(SE::new(SEK::LockPoisoned, None)),
+            Ok(e) => e,
+        };
+
+        if let Some(se) = hsmap.get_mut(id) {
+            try!(se.release());
+        }
+        self.evict_cache(&mut hsmap);
+
+        Ok(())
+    }
+
+    /// Internal method to remove entries from the cache which are not borrowed, until there are
+    /// at most `cache_size` entries in the cache
+    ///
+    /// Borrowed entries are never evicted, as they hold the lock on their file.
+    fn evict_cache(&self, hsmap: &mut HashMap<StoreId, StoreEntry>) {
+        if hsmap.len() <= self.cache_size {
+            return;
+        }
+
+        let n = hsmap.len() - self.cache_size;
+        let evictable = hsmap.iter()
+            .filter(|&(_, se)| !se.is_borrowed())
+            .map(|(id, _)| id.clone())
+            .take(n)
+            .collect::<Vec<StoreId>>();
+
+        debug!("Evicting {} entries from the cache", evictable.len());
+        for id in evictable {
+            hsmap.remove(&id);
+        }
+    }
+
     /// Retrieve a copy of a given entry, this cannot be used to mutate
     /// the one on disk
     pub fn retrieve_copy<S: IntoStoreId>(&self, id: S) -> Result<Entry> {
@@ -571,7 +891,7 @@ impl Store {
             return Err(SE::new(SEK::IdLocked, None)).map_err_into(SEK::RetrieveCopyCallError);
         }
 
-        try!(StoreEntry::new(id)).get_entry()
+        try!(StoreEntry::new(id, &self.backend)).get_entry()
     }
 
     /// Delete an entry
@@ -584,6 +904,15 @@ impl Store {
                 .map_err_into(SEK::DeleteCallError)
         }
 
+        // The linked entries are borrowed before the entry is deleted, so the deletion fails if one
+        // of them is borrowed elsewhere, instead of leaving links to the deleted entry behind
+        let linked = if self.update_links {
+            let entry = try!(self.retrieve_copy(id.clone()).map_err_into(SEK::DeleteCallError));
+            try!(self.borrow_linked(&entry).map_err_into(SEK::DeleteCallError))
+        } else {
+            vec![]
+        };
+
         {
             let mut entries = match self.entries.write() {
                 Err(_) => return Err(SE::new(SEK::LockPoisoned, None))
@@ -592,24 +921,46 @@ impl Store {
             };
 
             // if the entry is currently modified by the user, we cannot drop it
-            match entries.get(&id) {
-                None => {
-                    return Err(SEK::FileNotFound.into_error()).map_err_into(SEK::DeleteCallError)
-                },
-                Some(e) => if e.is_borrowed() {
-                    return Err(SE::new(SEK::IdLocked, None)).map_err_into(SEK::DeleteCallError)
-                }
+            if entries.get(&id).map(|e| e.is_borrowed()).unwrap_or(false) {
+                return Err(SE::new(SEK::IdLocked, None)).map_err_into(SEK::DeleteCallError)
             }
 
-            // remove the entry first, then the file
-            entries.remove(&id);
             let pb = try!(id.clone().with_base(self.path().clone()).into_pathbuf());
-            if let Err(e) = FileAbstraction::remove_file(&pb) {
+            if !try!(self.backend.exists(&pb).map_err_into(SEK::DeleteCallError)) {
+                return Err(SEK::FileNotFound.into_error()).map_err_into(SEK::DeleteCallError)
+            }
+
+            if !entries.contains_key(&id) {
+                let se = try!(StoreEntry::new(id.clone(), &self.backend));
+                entries.insert(id.clone(), se);
+            }
+
+            // The file is locked while it is removed, so we do not remove it under the feet of
+            // another store which has borrowed it. The entry is only removed from the cache when
+            // the file is gone.
+            let borrowed = match entries.get_mut(&id) {
+                Some(se) => se.borrow(),
+                None     => Err(SEK::IdNotFound.into_error()),
+            };
+            if let Err(e) = borrowed {
+                return Err(SEK::IdLocked.into_error_with_cause(Box::new(e)))
+                    .map_err_into(SEK::DeleteCallError);
+            }
+
+            let removed = self.backend.remove_file(&pb);
+            if let Some(se) = entries.get_mut(&id) {
+                let _ = se.release();
+            }
+            if let Err(e) = removed {
                 return Err(SEK::FileError.into_error_with_cause(Box::new(e)))
                     .map_err_into(SEK::DeleteCallError);
             }
+            entries.remove(&id);
+            self.with_header_index(|index| index.remove(&id));
         }
 
+        try!(self.update_linked(linked, &id, None).map_err_into(SEK::DeleteCallError));
+
         self.execute_hooks_for_id(self.post_delete_aspects.clone(), &id)
             .map_err_into(SEK::PostHookExecuteError)
             .map_err_into(SEK::HookExecutionError)
@@ -617,14 +968,15 @@ impl Store {
     }
 
     /// Save a copy of the Entry in another place
-    /// Executes the post_move_aspects for the new id
+    /// No move hooks are executed, as the entry is not moved
     pub fn save_to(&self, entry: &FileLockEntry, new_id: StoreId) -> Result<()> {
         self.save_to_other_location(entry, new_id, false)
     }
 
     /// Save an Entry in another place
     /// Removes the original entry
-    /// Executes the post_move_aspects for the new id
+    /// Executes the pre_move_aspects and the post_move_aspects for the old and the new id
+    /// Updates the links of the linked entries as `Store::move_by_id()` does
     pub fn save_as(&self, entry: FileLockEntry, new_id: StoreId) -> Result<()> {
         self.save_to_other_location(&entry, new_id, true)
     }
@@ -633,33 +985,74 @@ impl Store {
         -> Result<()>
     {
         let new_id = new_id.with_base(self.path().clone());
-        let hsmap = try!(
-            self.entries
-                .write()
-                .map_err(|_| SEK::LockPoisoned.into_error())
-                .map_err_into(SEK::MoveCallError)
-        );
+        let old_id = entry.get_location().clone();
 
-        if hsmap.contains_key(&new_id) {
-            return Err(SEK::EntryAlreadyExists.into_error()).map_err_into(SEK::MoveCallError)
+        if remove_old {
+            let pre = self.execute_hooks_for_move(self.pre_move_aspects.clone(),
+                                                  &old_id,
+                                                  &new_id,
+                                                  &old_id);
+            if let Err(e) = pre {
+                return Err(e)
+                    .map_err_into(SEK::PreHookExecuteError)
+                    .map_err_into(SEK::HookExecutionError)
+                    .map_err_into(SEK::MoveCallError)
+            }
         }
 
-        let old_id = entry.get_location().clone();
+        // See `Store::delete()`, the entry is not moved if one of the linked entries cannot be
+        // borrowed
+        let linked = if remove_old && self.update_links {
+            try!(self.borrow_linked(&entry.entry).map_err_into(SEK::MoveCallError))
+        } else {
+            vec![]
+        };
+
+        {
+            let hsmap = try!(
+                self.entries
+                    .write()
+                    .map_err(|_| SEK::LockPoisoned.into_error())
+                    .map_err_into(SEK::MoveCallError)
+            );
+
+            if hsmap.contains_key(&new_id) {
+                return Err(SEK::EntryAlreadyExists.into_error()).map_err_into(SEK::MoveCallError)
+            }
+
+            let old_id_as_path = try!(old_id.clone().with_base(self.path().clone()).into_pathbuf());
+            let new_id_as_path = try!(new_id.clone().with_base(self.path().clone()).into_pathbuf());
+            try!(self.backend.copy(&old_id_as_path, &new_id_as_path)
+                .and_then(|_| {
+                    if remove_old {
+                        self.backend.remove_file(&old_id_as_path)
+                    } else {
+                        Ok(())
+                    }
+                })
+                .map_err_into(SEK::FileError)
+                .map_err_into(SEK::MoveCallError));
 
-        let old_id_as_path = try!(old_id.clone().with_base(self.path().clone()).into_pathbuf());
-        let new_id_as_path = try!(new_id.clone().with_base(self.path().clone()).into_pathbuf());
-        FileAbstraction::copy(&old_id_as_path, &new_id_as_path)
-            .and_then(|_| {
+            self.with_header_index(|index| {
                 if remove_old {
-                    FileAbstraction::remove_file(&old_id_as_path)
+                    index.rename(&old_id, &new_id)
                 } else {
-                    Ok(())
+                    let mut copy = entry.entry.clone();
+                    copy.location = new_id.clone();
+                    index.insert(&copy)
                 }
-            })
-            .map_err_into(SEK::FileError)
-            .and_then(|_| self.execute_hooks_for_id(self.post_move_aspects.clone(), &new_id)
-                    .map_err_into(SEK::PostHookExecuteError)
-                    .map_err_into(SEK::HookExecutionError))
+            });
+        }
+
+        if !remove_old {
+            return Ok(());
+        }
+
+        try!(self.update_linked(linked, &old_id, Some(&new_id)).map_err_into(SEK::MoveCallError));
+
+        self.execute_hooks_for_move(self.post_move_aspects.clone(), &old_id, &new_id, &new_id)
+            .map_err_into(SEK::PostHookExecuteError)
+            .map_err_into(SEK::HookExecutionError)
             .map_err_into(SEK::MoveCallError)
     }
 
@@ -699,17 +1092,34 @@ impl Store {
     ///
     /// So the link is _partly dangling_, so to say.
     ///
+    /// If `update-links` is enabled in the store configuration, the links of the linked entries
+    /// are re-pointed to the new id, so this does not happen. The entry is not moved if one of the
+    /// linked entries is borrowed.
+    ///
     pub fn move_by_id(&self, old_id: StoreId, new_id: StoreId) -> Result<()> {
         let new_id = new_id.with_base(self.path().clone());
         let old_id = old_id.with_base(self.path().clone());
 
-        if let Err(e) = self.execute_hooks_for_id(self.pre_move_aspects.clone(), &old_id) {
+        if let Err(e) = self.execute_hooks_for_move(self.pre_move_aspects.clone(),
+                                                    &old_id,
+                                                    &new_id,
+                                                    &old_id)
+        {
             return Err(e)
                 .map_err_into(SEK::PreHookExecuteError)
                 .map_err_into(SEK::HookExecutionError)
                 .map_err_into(SEK::MoveByIdCallError)
         }
 
+        // See `Store::delete()`, the move fails if one of the linked entries cannot be borrowed
+        let linked = if self.update_links {
+            let entry = try!(self.retrieve_copy(old_id.clone())
+                             .map_err_into(SEK::MoveByIdCallError));
+            try!(self.borrow_linked(&entry).map_err_into(SEK::MoveByIdCallError))
+        } else {
+            vec![]
+        };
+
         {
             let mut hsmap = match self.entries.write() {
                 Err(_) => return Err(SE::new(SEK::LockPoisoned, None)),
@@ -730,98 +1140,460 @@ impl Store {
             let old_id_pb = try!(old_id.clone().with_base(self.path().clone()).into_pathbuf());
             let new_id_pb = try!(new_id.clone().with_base(self.path().clone()).into_pathbuf());
 
-            match FileAbstraction::rename(&old_id_pb, &new_id_pb) {
+            match self.backend.rename(&old_id_pb, &new_id_pb) {
                 Err(e) => return Err(SEK::EntryRenameError.into_error_with_cause(Box::new(e))),
                 Ok(_) => {
                     debug!("Rename worked on filesystem");
+                    self.with_header_index(|index| index.rename(&old_id, &new_id));
 
+                    // The cached entry still refers to the file at the old path, so it is
+                    // replaced by a new one.
                     // assert enforced through check hsmap.contains_key(&new_id) above.
                     // Should therefor never fail
-                    assert!(hsmap
-                            .remove(&old_id)
-                            .and_then(|mut entry| {
-                                entry.id = new_id.clone();
-                                hsmap.insert(new_id.clone(), entry)
-                            }).is_none())
+                    if hsmap.remove(&old_id).is_some() {
+                        let se = try!(StoreEntry::new(new_id.clone(), &self.backend));
+                        assert!(hsmap.insert(new_id.clone(), se).is_none())
+                    }
                 }
             }
 
         }
 
-        self.execute_hooks_for_id(self.pre_move_aspects.clone(), &new_id)
+        try!(self.update_linked(linked, &old_id, Some(&new_id))
+             .map_err_into(SEK::MoveByIdCallError));
+
+        self.execute_hooks_for_move(self.post_move_aspects.clone(), &old_id, &new_id, &new_id)
             .map_err_into(SEK::PostHookExecuteError)
             .map_err_into(SEK::HookExecutionError)
             .map_err_into(SEK::MoveByIdCallError)
     }
 
-    /// Gets the path where this store is on the disk
-    pub fn path(&self) -> &PathBuf {
-        &self.location
+    /// Get the ids of the entries `entry` is linked with, as reported by the registered link
+    /// updaters
+    fn linked_ids(&self, entry: &Entry) -> Result<Vec<StoreId>> {
+        let updaters = try!(self.link_updaters
+                            .read()
+                            .map_err(|_| SEK::LockPoisoned.into_error()));
+
+        let mut ids = vec![];
+        for updater in updaters.iter() {
+            for id in try!(updater.linked(entry).map_err_into(SEK::LinkUpdateError)) {
+                let id = id.with_base(self.path().clone());
+                if id != entry.location && !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
     }
 
-    pub fn register_hook(&mut self,
-                         position: HookPosition,
-                         aspect_name: &str,
-                         mut h: Box<Hook>)
+    /// Borrow the entries `entry` is linked with, so their links can be updated with
+    /// `Store::update_linked()`
+    ///
+    /// Entries which do not exist (anymore) are skipped. Fails if one of the entries is borrowed.
+    /// The entries are not written when they are dropped, so they are left as they are if the
+    /// operation they were borrowed for fails.
+    fn borrow_linked<'a>(&'a self, entry: &Entry) -> Result<Vec<FileLockEntry<'a>>> {
+        let mut linked = vec![];
+        for id in try!(self.linked_ids(entry)) {
+            if let Some(mut entry) = try!(self.get(id).map_err_into(SEK::LinkUpdateError)) {
+                entry.write_on_drop = false;
+                linked.push(entry);
+            }
+        }
+        Ok(linked)
+    }
+
+    /// Update the links to `old` in the entries `linked` with the registered link updaters and
+    /// write them
+    fn update_linked<'a>(&'a self,
+                         linked: Vec<FileLockEntry<'a>>,
+                         old: &StoreId,
+                         new: Option<&StoreId>)
         -> Result<()>
     {
-        debug!("Registering hook: {:?}", h);
-        debug!("     in position: {:?}", position);
-        debug!("     with aspect: {:?}", aspect_name);
+        for mut entry in linked {
+            try!(self.update_links_of(&mut entry, old, new));
+            try!(self.update(entry).map_err_into(SEK::LinkUpdateError));
+        }
+        Ok(())
+    }
 
-        let guard = match position {
-                HookPosition::StoreUnload  => self.store_unload_aspects.clone(),
+    /// Re-point the links of `entry` to `old` to `new`, or remove them if `new` is `None`
+    fn update_links_of(&self, entry: &mut Entry, old: &StoreId, new: Option<&StoreId>)
+        -> Result<()>
+    {
+        debug!("Updating links to {:?} in {:?}", old, entry.get_location());
+        let updaters = try!(self.link_updaters
+                            .read()
+                            .map_err(|_| SEK::LockPoisoned.into_error()));
 
-                HookPosition::PreCreate    => self.pre_create_aspects.clone(),
-                HookPosition::PostCreate   => self.post_create_aspects.clone(),
-                HookPosition::PreRetrieve  => self.pre_retrieve_aspects.clone(),
-                HookPosition::PostRetrieve => self.post_retrieve_aspects.clone(),
-                HookPosition::PreUpdate    => self.pre_update_aspects.clone(),
-                HookPosition::PostUpdate   => self.post_update_aspects.clone(),
-                HookPosition::PreDelete    => self.pre_delete_aspects.clone(),
-                HookPosition::PostDelete   => self.post_delete_aspects.clone(),
-            };
+        for updater in updaters.iter() {
+            try!(updater.update(entry, old, new).map_err_into(SEK::LinkUpdateError));
+        }
+        Ok(())
+    }
 
-        let mut guard = match guard.deref().lock().map_err(|_| SE::new(SEK::LockError, None)) {
-            Err(e) => return Err(SEK::HookRegisterError.into_error_with_cause(Box::new(e))),
-            Ok(g) => g,
-        };
+    /// Start a new `Transaction`
+    ///
+    /// Operations which are recorded in the transaction are written atomically when the transaction
+    /// is committed.
+    pub fn transaction<'a>(&'a self) -> Transaction<'a> {
+        Transaction::new(self)
+    }
 
-        for mut aspect in guard.deref_mut() {
-            if aspect.name().clone() == aspect_name.clone() {
-                debug!("Trying to find configuration for hook: {:?}", h);
-                self.get_config_for_hook(h.name()).map(|config| h.set_config(config));
-                debug!("Trying to register hook in aspect: {:?} <- {:?}", aspect, h);
-                aspect.register_hook(h);
-                return Ok(());
-            }
+    /// Register the header schema of a module
+    ///
+    /// A schema which was registered for the same module before is replaced. The schema is
+    /// persisted in the store, so it is known to all later instances of the store, even if they
+    /// do not register it themselves.
+    pub fn register_schema(&self, schema: Schema) -> Result<()> {
+        let mut schemas = try!(self.schemas
+                               .write()
+                               .map_err(|_| SEK::LockPoisoned.into_error())
+                               .map_err_into(SEK::RegisterSchemaCallError));
+
+        debug!("Registering header schema for module '{}'", schema.module());
+        if schemas.get(schema.module()) != Some(&schema) {
+            let path = self.location
+                .join(SCHEMA_DIR_NAME)
+                .join(format!("{}.toml", schema.module()));
+
+            debug!("Writing header schema to {:?}", path);
+            let content = ::toml::encode_str(&schema.to_value());
+            try!(self.backend
+                 .new_instance(path)
+                 .write_file_content(content.as_bytes())
+                 .map_err_into(SEK::RegisterSchemaCallError));
         }
 
-        let annfe = SEK::AspectNameNotFoundError.into_error();
-        Err(SEK::HookRegisterError.into_error_with_cause(Box::new(annfe)))
+        schemas.insert(schema.module().clone(), schema);
+        Ok(())
     }
 
-    fn get_config_for_hook(&self, name: &str) -> Option<&Value> {
-        match self.configuration {
-            Some(Value::Table(ref tabl)) => {
-                debug!("Trying to head 'hooks' section from {:?}", tabl);
-                tabl.get("hooks")
-                    .map(|hook_section| {
-                        debug!("Found hook section:  {:?}", hook_section);
-                        debug!("Reading section key: {:?}", name);
-                        match *hook_section {
-                            Value::Table(ref tabl) => tabl.get(name),
-                            _ => None
-                        }
-                    })
-                    .unwrap_or(None)
-            },
-            _ => None,
+    /// Check the header of `entry` against the schema of its module
+    ///
+    /// Returns all violations of the schema. If no schema is registered for the module of the
+    /// entry, there cannot be any violations.
+    pub fn check_schema(&self, entry: &Entry) -> Result<Vec<SchemaViolation>> {
+        let schemas = try!(self.schemas
+                           .read()
+                           .map_err(|_| SEK::LockPoisoned.into_error())
+                           .map_err_into(SEK::CheckSchemaCallError));
+
+        let id = entry.get_location();
+        Ok(schemas
+           .values()
+           .filter(|schema| schema.applies_to(id))
+           .flat_map(|schema| schema.check(id, entry.get_header()))
+           .collect())
+    }
+
+    /// Get the header index
+    ///
+    /// Returns `None` if the header index is not enabled in the store configuration.
+    pub fn header_index(&self) -> Result<Option<RwLockReadGuard<Index>>> {
+        match self.index {
+            Some(ref index) => index.read().map(Some).map_err(|_| SEK::LockPoisoned.into_error()),
+            None            => Ok(None),
         }
     }
 
-    fn execute_hooks_for_id(&self,
-                            aspects: Arc<Mutex<Vec<Aspect>>>,
+    /// Rebuild the header index from the entries in the store
+    ///
+    /// This reads and parses every entry in the store, so it should only be used if the index
+    /// went stale, for example because entries were altered without using the store.
+    ///
+    /// Entries which cannot be parsed are skipped. Returns the number of indexed entries.
+    pub fn rebuild_header_index(&self) -> Result<usize> {
+        let index = try!(self.index
+                         .as_ref()
+                         .ok_or(SEK::HeaderIndexDisabled.into_error())
+                         .map_err_into(SEK::RebuildHeaderIndexCallError));
+
+        let mut index = try!(index
+                             .write()
+                             .map_err(|_| SEK::LockPoisoned.into_error())
+                             .map_err_into(SEK::RebuildHeaderIndexCallError));
+
+        let pathes = try!(self.entry_pathes().map_err_into(SEK::RebuildHeaderIndexCallError));
+
+        index.clear();
+        for path in pathes {
+            let entry = StoreId::from_full_path(&self.location, path.clone())
+                .and_then(|id| {
+                    self.backend
+                        .new_instance(path.clone())
+                        .get_file_content()
+                        .and_then(|content| Entry::from_str(id, &content[..]))
+                });
+
+            match entry {
+                Ok(entry) => index.insert(&entry),
+                Err(e)    => {
+                    trace_error(&e);
+                    warn!("Could not index {:?}, skipping it", path);
+                },
+            }
+        }
+
+        debug!("Rebuilt header index with {} entries", index.len());
+        try!(index.save(&*self.backend).map_err_into(SEK::RebuildHeaderIndexCallError));
+        Ok(index.len())
+    }
+
+    /// Register a link updater
+    ///
+    /// Link updaters are used to update the links of the entries linked with moved and deleted
+    /// entries, if `update-links` is enabled in the store configuration.
+    pub fn register_link_updater(&self, updater: Box<LinkUpdater>) -> Result<()> {
+        let mut updaters = try!(self.link_updaters
+                                .write()
+                                .map_err(|_| SEK::LockPoisoned.into_error())
+                                .map_err_into(SEK::RegisterLinkUpdaterCallError));
+
+        debug!("Registering link updater {:?}", updater);
+        updaters.push(updater);
+        Ok(())
+    }
+
+    /// Register a migration
+    ///
+    /// Migrations are applied by `Store::migrate()`.
+    pub fn register_migration(&self, migration: Box<Migration>) -> Result<()> {
+        let mut migrations = try!(self.migrations
+                                  .write()
+                                  .map_err(|_| SEK::LockPoisoned.into_error())
+                                  .map_err_into(SEK::RegisterMigrationCallError));
+
+        debug!("Registering migration to {} for {:?}", migration.version(), migration.module());
+        migrations.push(migration);
+        Ok(())
+    }
+
+    /// Migrate all entries which were written by an older version of imag
+    ///
+    /// The registered migrations are applied to the entries in order of their versions and
+    /// `imag.version` of the entries is set to the version of the running imag. If `dry_run` is
+    /// true, nothing is written, but the entries which would be changed are reported anyways.
+    ///
+    /// Returns a report for each migrated entry. If a migration fails, the entry is left untouched
+    /// and the error is returned. Entries which were migrated before stay migrated.
+    ///
+    /// Migrations to a version newer than the running imag are skipped with a warning.
+    pub fn migrate(&self, dry_run: bool) -> Result<Vec<MigrationReport>> {
+        use semver::Version;
+
+        let current = try!(Version::parse(version!())
+                           .map_err(|_| SEK::MigrationError.into_error())
+                           .map_err_into(SEK::MigrateCallError));
+
+        let migrations = try!(self.migrations
+                              .read()
+                              .map_err(|_| SEK::LockPoisoned.into_error())
+                              .map_err_into(SEK::MigrateCallError));
+
+        // Migrations to a newer version than the running one would never be applied, as the
+        // entries are bumped to the running version
+        let mut sorted : Vec<&Box<Migration>> = migrations
+            .iter()
+            .filter(|m| if m.version() > current {
+                warn!("Skipping migration to {} for {:?}, it is newer than imag {}",
+                      m.version(), m.module(), current);
+                false
+            } else {
+                true
+            })
+            .collect();
+        sorted.sort_by_key(|m| m.version());
+
+        let mut reports = vec![];
+        for path in try!(self.entry_pathes().map_err_into(SEK::MigrateCallError)) {
+            let id = try!(StoreId::from_full_path(&self.location, path)
+                          .map_err_into(SEK::MigrateCallError));
+
+            if dry_run {
+                let mut entry = try!(self.retrieve_copy(id).map_err_into(SEK::MigrateCallError));
+                let report    = try!(migrate_entry(&sorted, &mut entry, &current)
+                                     .map_err_into(SEK::MigrationError)
+                                     .map_err_into(SEK::MigrateCallError));
+
+                if let Some(report) = report {
+                    reports.push(report);
+                }
+            } else {
+                let mut fle = match try!(self.get(id).map_err_into(SEK::MigrateCallError)) {
+                    Some(fle) => fle,
+                    None      => continue,
+                };
+
+                // the entry is written here, so we can catch the error
+                fle.write_on_drop = false;
+                match migrate_entry(&sorted, &mut fle.entry, &current) {
+                    Ok(Some(report)) => {
+                        try!(self._update(&mut fle, true).map_err_into(SEK::MigrateCallError));
+                        reports.push(report);
+                    },
+                    Ok(None) => {},
+                    Err(e)   => {
+                        return Err(e)
+                            .map_err_into(SEK::MigrationError)
+                            .map_err_into(SEK::MigrateCallError);
+                    },
+                }
+            }
+        }
+
+        Ok(reports)
+    }
+
+    /// Get the pathes of all entry files in the store
+    ///
+    /// Files which are used by the store internally are not included.
+    fn entry_pathes(&self) -> Result<Vec<PathBuf>> {
+        self.entry_pathes_below(self.location.clone())
+    }
+
+    /// Get the pathes of all entries below `path`
+    ///
+    /// Hidden files and files in hidden directories (like the ".git" directory of the git hooks)
+    /// and the files the store uses internally are not entries.
+    fn entry_pathes_below(&self, path: PathBuf) -> Result<Vec<PathBuf>> {
+        let pathes = try!(self.backend.pathes_recursively(path));
+
+        let mut entry_pathes = vec![];
+        for path in pathes {
+            let is_hidden = path
+                .strip_prefix(&self.location)
+                .unwrap_or(path.as_path())
+                .components()
+                .any(|c| c.as_os_str().to_str().map(|c| c.starts_with(".")).unwrap_or(false));
+
+            let is_internal = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| {
+                    name == INDEX_FILE_NAME ||
+                        name.ends_with(TRANSACTION_TEMP_SUFFIX) ||
+                        name.ends_with(TRANSACTION_BACKUP_SUFFIX)
+                })
+                .unwrap_or(false);
+
+            if !is_hidden && !is_internal && try!(self.backend.is_file(&path)) {
+                entry_pathes.push(path);
+            }
+        }
+
+        Ok(entry_pathes)
+    }
+
+    /// Add the ids of the cached entries for which `f` returns true to `ids`, if they are not in
+    /// `ids` already
+    ///
+    /// Entries which were just created are only added to the header index when they are written,
+    /// so they have to be taken from the cache when listing ids from the index.
+    fn add_cached_ids<F>(&self, ids: &mut Vec<StoreId>, f: F) -> Result<()>
+        where F: Fn(&StoreId) -> bool
+    {
+        let hsmap = try!(self.entries.read().map_err(|_| SE::new(SEK::LockPoisoned, None)));
+        for id in hsmap.keys() {
+            if f(id) && !ids.iter().any(|known| known.local() == id.local()) {
+                ids.push(id.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `f` on the header index and write the index, if the index is enabled
+    ///
+    /// The index is only an optimization, so a poisoned lock or an index which cannot be written
+    /// is reported but not an error. Changes which could not be written are written with the next
+    /// change.
+    fn with_header_index<F: FnOnce(&mut Index)>(&self, f: F) {
+        if let Some(ref index) = self.index {
+            match index.write() {
+                Ok(mut index) => {
+                    f(&mut index);
+                    if let Err(e) = index.save(&*self.backend) {
+                        trace_error(&e);
+                        warn!("Could not write header index");
+                    }
+                },
+                Err(_) => warn!("Header index lock poisoned, index not updated"),
+            }
+        }
+    }
+
+    /// Gets the path where this store is on the disk
+    pub fn path(&self) -> &PathBuf {
+        &self.location
+    }
+
+    pub fn register_hook(&mut self,
+                         position: HookPosition,
+                         aspect_name: &str,
+                         mut h: Box<Hook>)
+        -> Result<()>
+    {
+        debug!("Registering hook: {:?}", h);
+        debug!("     in position: {:?}", position);
+        debug!("     with aspect: {:?}", aspect_name);
+
+        let guard = match position {
+                HookPosition::StoreUnload  => self.store_unload_aspects.clone(),
+
+                HookPosition::PreCreate    => self.pre_create_aspects.clone(),
+                HookPosition::PostCreate   => self.post_create_aspects.clone(),
+                HookPosition::PreRetrieve  => self.pre_retrieve_aspects.clone(),
+                HookPosition::PostRetrieve => self.post_retrieve_aspects.clone(),
+                HookPosition::PreUpdate    => self.pre_update_aspects.clone(),
+                HookPosition::PostUpdate   => self.post_update_aspects.clone(),
+                HookPosition::PreDelete    => self.pre_delete_aspects.clone(),
+                HookPosition::PostDelete   => self.post_delete_aspects.clone(),
+                HookPosition::PreMove      => self.pre_move_aspects.clone(),
+                HookPosition::PostMove     => self.post_move_aspects.clone(),
+            };
+
+        let mut guard = match guard.deref().lock().map_err(|_| SE::new(SEK::LockError, None)) {
+            Err(e) => return Err(SEK::HookRegisterError.into_error_with_cause(Box::new(e))),
+            Ok(g) => g,
+        };
+
+        for mut aspect in guard.deref_mut() {
+            if aspect.name().clone() == aspect_name.clone() {
+                debug!("Trying to find configuration for hook: {:?}", h);
+                self.get_config_for_hook(h.name()).map(|config| h.set_config(config));
+                debug!("Trying to register hook in aspect: {:?} <- {:?}", aspect, h);
+                aspect.register_hook(h);
+                return Ok(());
+            }
+        }
+
+        let annfe = SEK::AspectNameNotFoundError.into_error();
+        Err(SEK::HookRegisterError.into_error_with_cause(Box::new(annfe)))
+    }
+
+    fn get_config_for_hook(&self, name: &str) -> Option<&Value> {
+        match self.configuration {
+            Some(Value::Table(ref tabl)) => {
+                debug!("Trying to head 'hooks' section from {:?}", tabl);
+                tabl.get("hooks")
+                    .map(|hook_section| {
+                        debug!("Found hook section:  {:?}", hook_section);
+                        debug!("Reading section key: {:?}", name);
+                        match *hook_section {
+                            Value::Table(ref tabl) => tabl.get(name),
+                            _ => None
+                        }
+                    })
+                    .unwrap_or(None)
+            },
+            _ => None,
+        }
+    }
+
+    fn execute_hooks_for_id(&self,
+                            aspects: Arc<Mutex<Vec<Aspect>>>,
                             id: &StoreId)
         -> HookResult<()>
     {
@@ -835,6 +1607,23 @@ impl Store {
             .map_err(|e| HookErrorKind::HookExecutionError.into_error_with_cause(e))
     }
 
+    fn execute_hooks_for_move(&self,
+                              aspects: Arc<Mutex<Vec<Aspect>>>,
+                              old_id: &StoreId,
+                              new_id: &StoreId,
+                              current: &StoreId)
+        -> HookResult<()>
+    {
+        match aspects.lock() {
+            Err(_) => return Err(HookErrorKind::HookExecutionError.into()),
+            Ok(g) => g
+        }.iter().fold_defresult(|aspect| {
+            debug!("[Aspect][exec]: {:?}", aspect);
+            aspect.access_move(old_id, new_id, current)
+        }).map_err(Box::new)
+            .map_err(|e| HookErrorKind::HookExecutionError.into_error_with_cause(e))
+    }
+
     fn execute_hooks_for_mut_file(&self,
                                   aspects: Arc<Mutex<Vec<Aspect>>>,
                                   fle: &mut FileLockEntry)
@@ -859,6 +1648,7 @@ impl Debug for Store {
         try!(write!(fmt, "\n"));
         try!(write!(fmt, " - location               : {:?}\n", self.location));
         try!(write!(fmt, " - configuration          : {:?}\n", self.configuration));
+        try!(write!(fmt, " - backend                : {:?}\n", self.backend));
         try!(write!(fmt, " - pre_create_aspects     : {:?}\n", self.pre_create_aspects    ));
         try!(write!(fmt, " - post_create_aspects    : {:?}\n", self.post_create_aspects   ));
         try!(write!(fmt, " - pre_retrieve_aspects   : {:?}\n", self.pre_retrieve_aspects  ));
@@ -867,6 +1657,8 @@ impl Debug for Store {
         try!(write!(fmt, " - post_update_aspects    : {:?}\n", self.post_update_aspects   ));
         try!(write!(fmt, " - pre_delete_aspects     : {:?}\n", self.pre_delete_aspects    ));
         try!(write!(fmt, " - post_delete_aspects    : {:?}\n", self.post_delete_aspects   ));
+        try!(write!(fmt, " - pre_move_aspects       : {:?}\n", self.pre_move_aspects      ));
+        try!(write!(fmt, " - post_move_aspects      : {:?}\n", self.post_move_aspects     ));
         try!(write!(fmt, "\n"));
         try!(write!(fmt, "Entries:\n"));
         try!(write!(fmt, "{:?}", self.entries));
@@ -880,8 +1672,6 @@ impl Drop for Store {
 
     /**
      * Unlock all files on drop
-     *
-     * TODO: Unlock them
      */
     fn drop(&mut self) {
         match StoreId::new(Some(self.location.clone()), PathBuf::from(".")) {
@@ -898,6 +1688,29 @@ impl Drop for Store {
             },
         };
 
+        if let Some(ref index) = self.index {
+            match index.write() {
+                Ok(mut index) => if let Err(e) = index.save(&*self.backend) {
+                    trace_error(&e);
+                    warn!("Could not write header index");
+                },
+                Err(_) => warn!("Header index lock poisoned, index not written"),
+            }
+        }
+
+        match self.entries.write() {
+            Ok(mut entries) => {
+                for (id, se) in entries.iter_mut().filter(|&(_, ref se)| se.is_borrowed()) {
+                    warn!("Entry is still borrowed while dropping the store: {:?}", id);
+                    if let Err(e) = se.release() {
+                        trace_error(&e);
+                    }
+                }
+                entries.clear();
+            },
+            Err(_) => warn!("Entry cache lock poisoned, cannot release entries"),
+        }
+
         debug!("Dropping store");
     }
 
@@ -907,6 +1720,10 @@ impl Drop for Store {
 pub struct FileLockEntry<'a> {
     store: &'a Store,
     entry: Entry,
+
+    /// Whether the entry is written when the `FileLockEntry` is dropped. If not, the entry is
+    /// only released.
+    write_on_drop: bool,
 }
 
 impl<'a> FileLockEntry<'a, > {
@@ -914,6 +1731,7 @@ impl<'a> FileLockEntry<'a, > {
         FileLockEntry {
             store: store,
             entry: entry,
+            write_on_drop: true,
         }
     }
 }
@@ -943,7 +1761,11 @@ impl<'a> DerefMut for FileLockEntry<'a> {
 impl<'a> Drop for FileLockEntry<'a> {
     /// This will silently ignore errors, use `Store::update` if you want to catch the errors
     fn drop(&mut self) {
-        let _ = self.store._update(self, true);
+        if self.write_on_drop {
+            let _ = self.store._update(self, true);
+        } else {
+            let _ = self.store._release(self.get_location());
+        }
     }
 }
 
@@ -951,116 +1773,628 @@ impl<'a> Drop for FileLockEntry<'a> {
 impl<'a> Drop for FileLockEntry<'a> {
     /// This will not silently ignore errors but prints the result of the _update() call for testing
     fn drop(&mut self) {
-        let _ = self.store._update(self, true).map_err(|e| trace_error(&e));
+        if self.write_on_drop {
+            let _ = self.store._update(self, true).map_err(|e| trace_error(&e));
+        } else {
+            let _ = self.store._release(self.get_location()).map_err(|e| trace_error(&e));
+        }
     }
 }
 
+/// Suffix of the files a `Transaction` writes new entry content to before renaming them
+static TRANSACTION_TEMP_SUFFIX : &'static str = ".imag-transaction";
 
-/// `EntryContent` type
-pub type EntryContent = String;
+/// Suffix of the files a `Transaction` moves replaced or deleted entries to until it succeeded
+static TRANSACTION_BACKUP_SUFFIX : &'static str = ".imag-transaction-backup";
 
-/// `EntryHeader`
-///
-/// This is basically a wrapper around `toml::Table` which provides convenience to the user of the
-/// library.
-#[derive(Debug, Clone)]
-pub struct EntryHeader {
-    header: Value,
+/// One operation inside a `Transaction`
+enum TransactionAction<'a> {
+    Create(Entry),
+    Update(FileLockEntry<'a>),
+    Delete(StoreId),
+    Move(StoreId, StoreId),
 }
 
-pub type EntryResult<V> = RResult<V, ParserError>;
+/// A step which was done while committing a `Transaction` and which has to be reverted if a later
+/// step fails
+#[derive(Debug)]
+enum TransactionUndo {
+    /// The file at the path was created and has to be removed
+    Remove(PathBuf),
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum Token {
-    Key(String),
-    Index(usize),
+    /// The file was renamed from the first to the second path and has to be renamed back
+    Rename(PathBuf, PathBuf),
 }
 
-/**
- * Wrapper type around file header (TOML) object
- */
-impl EntryHeader {
+/// A set of create, update, delete and move operations which are written to the store atomically
+///
+/// A `Transaction` is created with `Store::transaction()`. Operations are only recorded until
+/// `Transaction::commit()` is called, which
+///
+///  * checks that none of the operations conflicts with the current state of the store,
+///  * executes the pre-hooks of all operations,
+///  * writes the new content of all entries to temporary files,
+///  * renames all files into place and
+///  * executes the post-hooks of all operations.
+///
+/// If one of the steps before executing the post-hooks fails, everything that was already done on
+/// the filesystem is reverted and the store is left as it was before. Errors from the post-hooks
+/// are returned, but the transaction has succeeded then.
+///
+/// Entries which are passed to `Transaction::update()` are not written when they are dropped, so
+/// if the transaction is dropped without being committed or if committing fails, the changes to
+/// these entries are discarded.
+///
+/// If `update-links` is enabled in the store configuration, the links of the entries linked with
+/// deleted and moved entries are updated as part of the transaction. Committing fails if one of
+/// these entries is borrowed or moved by the transaction itself.
+pub struct Transaction<'a> {
+    store: &'a Store,
+    actions: Vec<TransactionAction<'a>>,
+}
 
-    pub fn new() -> EntryHeader {
-        EntryHeader {
-            header: build_default_header()
+impl<'a> Transaction<'a> {
+
+    fn new(store: &'a Store) -> Transaction<'a> {
+        Transaction {
+            store: store,
+            actions: vec![],
         }
     }
 
-    pub fn header(&self) -> &Value {
-        &self.header
+    /// Create `entry` when the transaction is committed
+    pub fn create(&mut self, mut entry: Entry) -> &mut Transaction<'a> {
+        entry.location = entry.location.clone().with_base(self.store.path().clone());
+        self.actions.push(TransactionAction::Create(entry));
+        self
     }
 
-    fn from_table(t: Table) -> EntryHeader {
-        EntryHeader {
-            header: Value::Table(t)
-        }
+    /// Write `entry` when the transaction is committed
+    pub fn update(&mut self, mut entry: FileLockEntry<'a>) -> &mut Transaction<'a> {
+        entry.write_on_drop = false;
+        self.actions.push(TransactionAction::Update(entry));
+        self
     }
 
-    pub fn parse(s: &str) -> EntryResult<EntryHeader> {
-        use toml::Parser;
-
-        let mut parser = Parser::new(s);
-        parser.parse()
-            .ok_or(ParserErrorKind::TOMLParserErrors.into())
-            .and_then(verify_header_consistency)
-            .map(EntryHeader::from_table)
+    /// Delete the entry `id` when the transaction is committed
+    pub fn delete(&mut self, id: StoreId) -> &mut Transaction<'a> {
+        let id = id.with_base(self.store.path().clone());
+        self.actions.push(TransactionAction::Delete(id));
+        self
     }
 
-    pub fn verify(&self) -> Result<()> {
-        match self.header {
-            Value::Table(ref t) => verify_header(&t),
-            _ => Err(SE::new(SEK::HeaderTypeFailure, None)),
-        }
+    /// Move the entry `old_id` to `new_id` when the transaction is committed
+    ///
+    /// The same warnings as for `Store::move_by_id()` apply.
+    pub fn move_by_id(&mut self, old_id: StoreId, new_id: StoreId) -> &mut Transaction<'a> {
+        let old_id = old_id.with_base(self.store.path().clone());
+        let new_id = new_id.with_base(self.store.path().clone());
+        self.actions.push(TransactionAction::Move(old_id, new_id));
+        self
     }
 
-    /**
-     * Insert a header field by a string-spec
-     *
-     * ```ignore
-     *  insert("something.in.a.field", Boolean(true));
-     * ```
-     *
-     * If an array field was accessed which is _out of bounds_ of the array available, the element
-     * is appended to the array.
-     *
-     * Inserts a Boolean in the section "something" -> "in" -> "a" -> "field"
-     * A JSON equivalent would be
-     *
-     *  {
-     *      something: {
-     *          in: {
-     *              a: {
-     *                  field: true
-     *              }
-     *          }
-     *      }
-     *  }
-     *
-     * Returns true if header field was set, false if there is already a value
-     */
-    pub fn insert(&mut self, spec: &str, v: Value) -> Result<bool> {
-        self.insert_with_sep(spec, '.', v)
+    /// Write all operations of the transaction to the store
+    pub fn commit(mut self) -> Result<()> {
+        let mut actions = ::std::mem::replace(&mut self.actions, vec![]);
+        try!(self.add_link_updates(&mut actions).map_err_into(SEK::TransactionCommitCallError));
+        self.commit_actions(actions).map_err_into(SEK::TransactionCommitCallError)
     }
 
-    pub fn insert_with_sep(&mut self, spec: &str, sep: char, v: Value) -> Result<bool> {
-        let tokens = match EntryHeader::tokenize(spec, sep) {
-            Err(e) => return Err(e),
-            Ok(t) => t
-        };
+    /// Update the links of the entries linked with the deleted and moved entries, if `update-links`
+    /// is enabled
+    ///
+    /// Linked entries which are created or updated by the transaction are changed in place, the
+    /// others are added to the transaction as updates.
+    fn add_link_updates(&self, actions: &mut Vec<TransactionAction<'a>>) -> Result<()> {
+        if !self.store.update_links {
+            return Ok(());
+        }
 
-        let destination = match tokens.iter().last() {
-            None => return Err(SE::new(SEK::HeaderPathSyntaxError, None)),
-            Some(d) => d,
-        };
+        let relocated = actions.iter()
+            .filter_map(|action| match *action {
+                TransactionAction::Delete(ref id)         => Some((id.clone(), None)),
+                TransactionAction::Move(ref old, ref new) => Some((old.clone(), Some(new.clone()))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (old, new) in relocated {
+            let entry = try!(self.store.retrieve_copy(old.clone()));
+            for id in try!(self.store.linked_ids(&entry)) {
+                let pos = actions.iter().position(|action| match *action {
+                    TransactionAction::Create(ref created)  => created.location == id,
+                    TransactionAction::Update(ref fle)      => fle.location == id,
+                    TransactionAction::Delete(ref deleted)  => *deleted == id,
+                    TransactionAction::Move(ref moved, _)   => *moved == id,
+                });
 
-        let path_to_dest = tokens[..(tokens.len() - 1)].into(); // N - 1 tokens
+                match pos {
+                    Some(i) => match actions[i] {
+                        TransactionAction::Create(ref mut created) =>
+                            try!(self.store.update_links_of(created, &old, new.as_ref())),
+                        TransactionAction::Update(ref mut fle) =>
+                            try!(self.store.update_links_of(&mut fle.entry, &old, new.as_ref())),
+                        TransactionAction::Delete(_) => (),
+                        TransactionAction::Move(..) => {
+                            debug!("Linked entry {:?} is moved by the transaction as well", id);
+                            return Err(SEK::LinkUpdateError.into_error());
+                        },
+                    },
+                    None => {
+                        let linked = try!(self.store.get(id).map_err_into(SEK::LinkUpdateError));
+                        if let Some(mut fle) = linked {
+                            fle.write_on_drop = false;
+                            try!(self.store.update_links_of(&mut fle, &old, new.as_ref()));
+                            actions.push(TransactionAction::Update(fle));
+                        }
+                    },
+                }
+            }
+        }
 
-        // walk N-1 tokens
-        let value = match EntryHeader::walk_header(&mut self.header, path_to_dest) {
-            Err(e) => return Err(e),
-            Ok(v) => v
-        };
+        Ok(())
+    }
+
+    fn commit_actions(&self, mut actions: Vec<TransactionAction<'a>>) -> Result<()> {
+        try!(self.check(&actions));
+
+        debug!("Executing pre-hooks for transaction");
+        try!(self.execute_pre_hooks(&mut actions)
+             .map_err_into(SEK::PreHookExecuteError)
+             .map_err_into(SEK::HookExecutionError));
+
+        debug!("Writing temporary files for transaction");
+        let temp_files = try!(self.write_temp_files(&actions));
+
+        {
+            let mut hsmap = try!(self.store
+                                 .entries
+                                 .write()
+                                 .map_err(|_| SE::new(SEK::LockPoisoned, None)));
+
+            debug!("Moving files into place");
+            let mut undo    = vec![];
+            let mut backups = vec![];
+            let applied = actions.iter().fold(Ok(()), |acc, action| {
+                acc.and_then(|_| self.apply(action, &mut undo, &mut backups))
+            });
+
+            if let Err(e) = applied {
+                debug!("Transaction failed, rolling back: {:?}", e);
+                self.remove_files(temp_files);
+                return match self.rollback(undo) {
+                    Ok(_)   => Err(e),
+                    Err(re) => {
+                        trace_error(&e);
+                        Err(SEK::TransactionRollbackError.into_error_with_cause(Box::new(re)))
+                    },
+                };
+            }
+
+            debug!("Transaction written, removing backups");
+            self.remove_files(backups);
+
+            for action in actions.iter() {
+                match *action {
+                    TransactionAction::Create(ref entry) => {
+                        // The entry is borrowed until the post-create hooks ran, so changes they
+                        // make can be written back, as for `Store::create()`
+                        let id = entry.location.clone();
+                        let mut se = try!(StoreEntry::new(id.clone(), &self.store.backend));
+                        try!(se.borrow());
+                        hsmap.insert(id, se);
+                        self.store.with_header_index(|index| index.insert(entry));
+                    },
+                    TransactionAction::Update(ref fle) => {
+                        // The file was replaced, so the cached entry would still read and write
+                        // the old one. The new entry is released when the FileLockEntry is
+                        // dropped.
+                        hsmap.remove(&fle.location);
+                        let mut se = try!(StoreEntry::new(fle.location.clone(),
+                                                          &self.store.backend));
+                        try!(se.borrow());
+                        hsmap.insert(fle.location.clone(), se);
+                        self.store.with_header_index(|index| index.insert(&fle.entry));
+                    },
+                    TransactionAction::Delete(ref id) => {
+                        hsmap.remove(id);
+                        self.store.with_header_index(|index| index.remove(id));
+                    },
+                    TransactionAction::Move(ref old_id, ref new_id) => {
+                        self.store.with_header_index(|index| index.rename(old_id, new_id));
+                        if hsmap.remove(old_id).is_some() {
+                            let se = try!(StoreEntry::new(new_id.clone(), &self.store.backend));
+                            hsmap.insert(new_id.clone(), se);
+                        }
+                    },
+                }
+            }
+        }
+
+        debug!("Executing post-hooks for transaction");
+        self.execute_post_hooks(actions)
+            .map_err_into(SEK::PostHookExecuteError)
+            .map_err_into(SEK::HookExecutionError)
+    }
+
+    /// Check that the operations can be done on the current state of the store
+    fn check(&self, actions: &[TransactionAction<'a>]) -> Result<()> {
+        use std::collections::HashSet;
+
+        let hsmap = try!(self.store
+                         .entries
+                         .read()
+                         .map_err(|_| SE::new(SEK::LockPoisoned, None)));
+        let backend = &self.store.backend;
+        let exists = |id: &StoreId| -> Result<bool> {
+            if hsmap.contains_key(id) {
+                Ok(true)
+            } else {
+                id.clone().into_pathbuf().and_then(|pb| backend.exists(&pb))
+            }
+        };
+        let is_borrowed = |id: &StoreId| hsmap.get(id).map(|e| e.is_borrowed()).unwrap_or(false);
+
+        let mut seen = HashSet::new();
+        for action in actions {
+            let ids = match *action {
+                TransactionAction::Create(ref entry) => {
+                    if try!(exists(&entry.location)) {
+                        return Err(SEK::EntryAlreadyExists.into_error());
+                    }
+                    vec![&entry.location]
+                },
+                TransactionAction::Update(ref fle) => {
+                    if !is_borrowed(&fle.location) {
+                        return Err(SEK::IdNotFound.into_error());
+                    }
+                    vec![&fle.location]
+                },
+                TransactionAction::Delete(ref id) => {
+                    if is_borrowed(id) {
+                        return Err(SEK::IdLocked.into_error());
+                    }
+                    if !try!(exists(id)) {
+                        return Err(SEK::FileNotFound.into_error());
+                    }
+                    vec![id]
+                },
+                TransactionAction::Move(ref old_id, ref new_id) => {
+                    if is_borrowed(old_id) {
+                        return Err(SEK::EntryAlreadyBorrowed.into_error());
+                    }
+                    if try!(exists(new_id)) {
+                        return Err(SEK::EntryAlreadyExists.into_error());
+                    }
+                    vec![old_id, new_id]
+                },
+            };
+
+            for id in ids {
+                if !seen.insert(id.clone()) {
+                    debug!("Entry used more than once in transaction: {:?}", id);
+                    return Err(SEK::TransactionDuplicateId.into_error());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_pre_hooks(&self, actions: &mut [TransactionAction<'a>]) -> HookResult<()> {
+        let store = self.store;
+        actions.iter_mut().fold_defresult(|action| match *action {
+            TransactionAction::Create(ref entry) =>
+                store.execute_hooks_for_id(store.pre_create_aspects.clone(), &entry.location),
+            TransactionAction::Update(ref mut fle) =>
+                store.execute_hooks_for_mut_file(store.pre_update_aspects.clone(), fle),
+            TransactionAction::Delete(ref id) =>
+                store.execute_hooks_for_id(store.pre_delete_aspects.clone(), id),
+            TransactionAction::Move(ref old_id, ref new_id) =>
+                store.execute_hooks_for_move(store.pre_move_aspects.clone(), old_id, new_id, old_id),
+        })
+    }
+
+    /// Execute the post-hooks for all operations, even if some of them fail
+    ///
+    /// Returns the first error.
+    fn execute_post_hooks(&self, actions: Vec<TransactionAction<'a>>) -> HookResult<()> {
+        let store = self.store;
+        actions.into_iter().fold(Ok(()), |acc, action| {
+            let res = match action {
+                TransactionAction::Create(entry) => {
+                    let written = entry.to_str();
+                    let mut fle = FileLockEntry::new(store, entry);
+                    fle.write_on_drop = false;
+                    let res = store
+                        .execute_hooks_for_mut_file(store.post_create_aspects.clone(), &mut fle);
+
+                    if fle.entry.to_str() == written {
+                        res
+                    } else {
+                        debug!("Post-create hooks changed {:?}, writing it", fle.location);
+                        res.and(store._update(&mut fle, false)
+                                .map_err(Box::new)
+                                .map_err(|e| HookErrorKind::HookExecutionError
+                                         .into_error_with_cause(e)))
+                    }
+                },
+                TransactionAction::Update(mut fle) =>
+                    store.execute_hooks_for_mut_file(store.post_update_aspects.clone(), &mut fle),
+                TransactionAction::Delete(id) =>
+                    store.execute_hooks_for_id(store.post_delete_aspects.clone(), &id),
+                TransactionAction::Move(old_id, new_id) =>
+                    store.execute_hooks_for_move(store.post_move_aspects.clone(),
+                                                 &old_id,
+                                                 &new_id,
+                                                 &new_id),
+            };
+            acc.and(res)
+        })
+    }
+
+    /// Write the new content of all created and updated entries to temporary files
+    ///
+    /// Returns the pathes of the temporary files. If writing fails, the already written temporary
+    /// files are removed.
+    fn write_temp_files(&self, actions: &[TransactionAction<'a>]) -> Result<Vec<PathBuf>> {
+        let backend = &self.store.backend;
+        let mut written = vec![];
+
+        for action in actions {
+            let entry = match *action {
+                TransactionAction::Create(ref entry) => entry,
+                TransactionAction::Update(ref fle)   => &fle.entry,
+                _ => continue,
+            };
+
+            let res = entry.verify()
+                .and_then(|_| entry.location.clone().into_pathbuf())
+                .map(|pb| with_suffix(&pb, TRANSACTION_TEMP_SUFFIX))
+                .and_then(|tmp| {
+                    if try!(backend.is_file(&tmp)) {
+                        try!(backend.remove_file(&tmp));
+                    }
+                    backend.new_instance(tmp.clone())
+                        .write_file_content(entry.to_str().as_bytes())
+                        .map(|_| tmp)
+                });
+
+            match res {
+                Ok(tmp) => written.push(tmp),
+                Err(e)  => {
+                    self.remove_files(written);
+                    return Err(e).map_err_into(SEK::FileNotWritten);
+                },
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Move the files of one operation into place, recording how to revert it
+    fn apply(&self,
+             action: &TransactionAction<'a>,
+             undo: &mut Vec<TransactionUndo>,
+             backups: &mut Vec<PathBuf>)
+        -> Result<()>
+    {
+        let backend = &self.store.backend;
+
+        match *action {
+            TransactionAction::Create(ref entry) => {
+                let pb = try!(entry.location.clone().into_pathbuf());
+                try!(backend.rename(&with_suffix(&pb, TRANSACTION_TEMP_SUFFIX), &pb));
+                undo.push(TransactionUndo::Remove(pb));
+            },
+            TransactionAction::Update(ref fle) => {
+                let pb = try!(fle.location.clone().into_pathbuf());
+                if try!(backend.is_file(&pb)) {
+                    let backup = with_suffix(&pb, TRANSACTION_BACKUP_SUFFIX);
+                    try!(backend.rename(&pb, &backup));
+                    undo.push(TransactionUndo::Rename(pb.clone(), backup.clone()));
+                    backups.push(backup);
+                }
+                try!(backend.rename(&with_suffix(&pb, TRANSACTION_TEMP_SUFFIX), &pb));
+                undo.push(TransactionUndo::Remove(pb));
+            },
+            TransactionAction::Delete(ref id) => {
+                let pb     = try!(id.clone().into_pathbuf());
+                let backup = with_suffix(&pb, TRANSACTION_BACKUP_SUFFIX);
+                try!(backend.rename(&pb, &backup));
+                undo.push(TransactionUndo::Rename(pb, backup.clone()));
+                backups.push(backup);
+            },
+            TransactionAction::Move(ref old_id, ref new_id) => {
+                let old_pb = try!(old_id.clone().into_pathbuf());
+                let new_pb = try!(new_id.clone().into_pathbuf());
+                try!(backend.rename(&old_pb, &new_pb));
+                undo.push(TransactionUndo::Rename(old_pb, new_pb));
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Revert the recorded steps in reverse order
+    fn rollback(&self, undo: Vec<TransactionUndo>) -> Result<()> {
+        let backend = &self.store.backend;
+        undo.into_iter().rev().fold(Ok(()), |acc, step| {
+            debug!("Reverting: {:?}", step);
+            let res = match step {
+                TransactionUndo::Remove(ref pb)          => backend.remove_file(pb),
+                TransactionUndo::Rename(ref orig, ref to) => backend.rename(to, orig),
+            };
+            acc.and(res)
+        })
+    }
+
+    /// Remove files if they exist, ignoring (but tracing) errors
+    fn remove_files(&self, pathes: Vec<PathBuf>) {
+        let backend = &self.store.backend;
+        for path in pathes {
+            let res = backend.is_file(&path).and_then(|is_file| if is_file {
+                backend.remove_file(&path)
+            } else {
+                Ok(())
+            });
+
+            if let Err(e) = res {
+                trace_error(&e);
+            }
+        }
+    }
+
+}
+
+/// Load the schemas which were persisted by `Store::register_schema()`
+///
+/// Schema files which cannot be read are reported and ignored.
+fn load_schemas(location: &PathBuf, backend: &FileAbstraction) -> HashMap<String, Schema> {
+    use toml::Parser;
+
+    let pathes = match backend.pathes_recursively(location.join(SCHEMA_DIR_NAME)) {
+        Ok(pathes) => pathes,
+        Err(e) => {
+            trace_error(&e);
+            return HashMap::new();
+        },
+    };
+
+    let mut schemas = HashMap::new();
+    for path in pathes {
+        let schema = backend.new_instance(path.clone())
+            .get_file_content()
+            .ok()
+            .and_then(|content| Parser::new(&content[..]).parse())
+            .and_then(|table| Schema::from_value(&Value::Table(table)));
+
+        match schema {
+            Some(schema) => {
+                debug!("Loaded header schema for module '{}'", schema.module());
+                schemas.insert(schema.module().clone(), schema);
+            },
+            None => warn!("Could not load header schema from {:?}, ignoring it", path),
+        }
+    }
+
+    schemas
+}
+
+fn with_suffix(pb: &PathBuf, suffix: &str) -> PathBuf {
+    use std::ffi::OsString;
+
+    let mut name = pb.file_name().map(|n| n.to_os_string()).unwrap_or(OsString::new());
+    name.push(suffix);
+    pb.with_file_name(name)
+}
+
+
+/// `EntryContent` type
+pub type EntryContent = String;
+
+/// `EntryHeader`
+///
+/// This is basically a wrapper around `toml::Table` which provides convenience to the user of the
+/// library.
+#[derive(Debug, Clone)]
+pub struct EntryHeader {
+    header: Value,
+}
+
+pub type EntryResult<V> = RResult<V, ParserError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Key(String),
+    Index(usize),
+}
+
+/**
+ * Wrapper type around file header (TOML) object
+ */
+impl EntryHeader {
+
+    pub fn new() -> EntryHeader {
+        EntryHeader {
+            header: build_default_header()
+        }
+    }
+
+    pub fn header(&self) -> &Value {
+        &self.header
+    }
+
+    fn from_table(t: Table) -> EntryHeader {
+        EntryHeader {
+            header: Value::Table(t)
+        }
+    }
+
+    pub fn parse(s: &str) -> EntryResult<EntryHeader> {
+        use toml::Parser;
+
+        let mut parser = Parser::new(s);
+        parser.parse()
+            .ok_or(ParserErrorKind::TOMLParserErrors.into())
+            .and_then(verify_header_consistency)
+            .map(EntryHeader::from_table)
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        match self.header {
+            Value::Table(ref t) => verify_header(&t),
+            _ => Err(SE::new(SEK::HeaderTypeFailure, None)),
+        }
+    }
+
+    /**
+     * Insert a header field by a string-spec
+     *
+     * ```ignore
+     *  insert("something.in.a.field", Boolean(true));
+     * ```
+     *
+     * If an array field was accessed which is _out of bounds_ of the array available, the element
+     * is appended to the array.
+     *
+     * Inserts a Boolean in the section "something" -> "in" -> "a" -> "field"
+     * A JSON equivalent would be
+     *
+     *  {
+     *      something: {
+     *          in: {
+     *              a: {
+     *                  field: true
+     *              }
+     *          }
+     *      }
+     *  }
+     *
+     * Returns true if header field was set, false if there is already a value
+     */
+    pub fn insert(&mut self, spec: &str, v: Value) -> Result<bool> {
+        self.insert_with_sep(spec, '.', v)
+    }
+
+    pub fn insert_with_sep(&mut self, spec: &str, sep: char, v: Value) -> Result<bool> {
+        let tokens = match EntryHeader::tokenize(spec, sep) {
+            Err(e) => return Err(e),
+            Ok(t) => t
+        };
+
+        let destination = match tokens.iter().last() {
+            None => return Err(SE::new(SEK::HeaderPathSyntaxError, None)),
+            Some(d) => d,
+        };
+
+        let path_to_dest = tokens[..(tokens.len() - 1)].into(); // N - 1 tokens
+
+        // walk N-1 tokens
+        let value = match EntryHeader::walk_header(&mut self.header, path_to_dest) {
+            Err(e) => return Err(e),
+            Ok(v) => v
+        };
 
         // There is already an value at this place
         if EntryHeader::extract(value, destination).is_ok() {
@@ -1558,75 +2892,6 @@ impl Entry {
 
 }
 
-mod glob_store_iter {
-    use std::fmt::{Debug, Formatter};
-    use std::fmt::Error as FmtError;
-    use std::path::PathBuf;
-    use glob::Paths;
-    use storeid::StoreId;
-    use storeid::StoreIdIterator;
-
-    use error::StoreErrorKind as SEK;
-    use error::MapErrInto;
-
-    use libimagerror::trace::trace_error;
-
-    pub struct GlobStoreIdIterator {
-        store_path: PathBuf,
-        paths: Paths,
-    }
-
-    impl Debug for GlobStoreIdIterator {
-
-        fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
-            write!(fmt, "GlobStoreIdIterator")
-        }
-
-    }
-
-    impl Into<StoreIdIterator> for GlobStoreIdIterator {
-
-        fn into(self) -> StoreIdIterator {
-            StoreIdIterator::new(Box::new(self))
-        }
-
-    }
-
-    impl GlobStoreIdIterator {
-
-        pub fn new(paths: Paths, store_path: PathBuf) -> GlobStoreIdIterator {
-            debug!("Create a GlobStoreIdIterator(store_path = {:?}, /* ... */)", store_path);
-
-            GlobStoreIdIterator {
-                store_path: store_path,
-                paths: paths,
-            }
-        }
-
-    }
-
-    impl Iterator for GlobStoreIdIterator {
-        type Item = StoreId;
-
-        fn next(&mut self) -> Option<StoreId> {
-            self.paths
-                .next()
-                .and_then(|o| {
-                    debug!("GlobStoreIdIterator::next() => {:?}", o);
-                    o.map_err_into(SEK::StoreIdHandlingError)
-                        .and_then(|p| StoreId::from_full_path(&self.store_path, p))
-                        .map_err(|e| {
-                            debug!("GlobStoreIdIterator error: {:?}", e);
-                            trace_error(&e);
-                        }).ok()
-                })
-        }
-
-    }
-
-}
-
-
 #[cfg(test)]
 mod test {
     extern crate env_logger;
@@ -2240,249 +3505,1029 @@ mod store_tests {
     use std::path::PathBuf;
 
     use super::Store;
+    use file_abstraction::FileAbstraction;
+    use file_abstraction::InMemoryFileAbstraction;
 
     pub fn get_store() -> Store {
-        Store::new(PathBuf::from("/"), None).unwrap()
+        Store::new_in_memory(PathBuf::from("/"), None).unwrap()
+    }
+
+    #[test]
+    fn test_store_instantiation() {
+        let store = get_store();
+
+        assert_eq!(store.location, PathBuf::from("/"));
+        assert!(store.entries.read().unwrap().is_empty());
+
+        assert!(store.store_unload_aspects.lock().unwrap().is_empty());
+
+        assert!(store.pre_create_aspects.lock().unwrap().is_empty());
+        assert!(store.post_create_aspects.lock().unwrap().is_empty());
+        assert!(store.pre_retrieve_aspects.lock().unwrap().is_empty());
+        assert!(store.post_retrieve_aspects.lock().unwrap().is_empty());
+        assert!(store.pre_update_aspects.lock().unwrap().is_empty());
+        assert!(store.post_update_aspects.lock().unwrap().is_empty());
+        assert!(store.pre_delete_aspects.lock().unwrap().is_empty());
+        assert!(store.post_delete_aspects.lock().unwrap().is_empty());
+        assert!(store.pre_move_aspects.lock().unwrap().is_empty());
+        assert!(store.post_move_aspects.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_store_create() {
+        let store = get_store();
+
+        for n in 1..100 {
+            let s = format!("test-{}", n);
+            let entry = store.create(PathBuf::from(s.clone())).unwrap();
+            assert!(entry.verify().is_ok());
+            let loc = entry.get_location().clone().into_pathbuf().unwrap();
+            assert!(loc.starts_with("/"));
+            assert!(loc.ends_with(s));
+        }
+    }
+
+    #[test]
+    fn test_store_get_create_get_delete_get() {
+        let store = get_store();
+
+        for n in 1..100 {
+            let res = store.get(PathBuf::from(format!("test-{}", n)));
+            assert!(match res { Ok(None) => true, _ => false, })
+        }
+
+        for n in 1..100 {
+            let s = format!("test-{}", n);
+            let entry = store.create(PathBuf::from(s.clone())).unwrap();
+
+            assert!(entry.verify().is_ok());
+
+            let loc = entry.get_location().clone().into_pathbuf().unwrap();
+
+            assert!(loc.starts_with("/"));
+            assert!(loc.ends_with(s));
+        }
+
+        for n in 1..100 {
+            let res = store.get(PathBuf::from(format!("test-{}", n)));
+            assert!(match res { Ok(Some(_)) => true, _ => false, })
+        }
+
+        for n in 1..100 {
+            assert!(store.delete(PathBuf::from(format!("test-{}", n))).is_ok())
+        }
+
+        for n in 1..100 {
+            let res = store.get(PathBuf::from(format!("test-{}", n)));
+            assert!(match res { Ok(None) => true, _ => false, })
+        }
+    }
+
+    #[test]
+    fn test_store_create_twice() {
+        use error::StoreErrorKind as SEK;
+
+        let store = get_store();
+
+        for n in 1..100 {
+            let s = format!("test-{}", n % 50);
+            store.create(PathBuf::from(s.clone()))
+                .map_err(|e| assert!(is_match!(e.err_type(), SEK::CreateCallError) && n >= 50))
+                .ok()
+                .map(|entry| {
+                    assert!(entry.verify().is_ok());
+                    let loc = entry.get_location().clone().into_pathbuf().unwrap();
+                    assert!(loc.starts_with("/"));
+                    assert!(loc.ends_with(s));
+                });
+        }
+    }
+
+    #[test]
+    fn test_store_create_in_hm() {
+        use storeid::StoreId;
+
+        let store = get_store();
+
+        for n in 1..100 {
+            let pb = StoreId::new_baseless(PathBuf::from(format!("test-{}", n))).unwrap();
+
+            assert!(store.entries.read().unwrap().get(&pb).is_none());
+            assert!(store.create(pb.clone()).is_ok());
+
+            let pb = pb.with_base(store.path().clone());
+            assert!(store.entries.read().unwrap().get(&pb).is_some());
+        }
+    }
+
+    #[test]
+    fn test_store_retrieve_in_hm() {
+        use storeid::StoreId;
+
+        let store = get_store();
+
+        for n in 1..100 {
+            let pb = StoreId::new_baseless(PathBuf::from(format!("test-{}", n))).unwrap();
+
+            assert!(store.entries.read().unwrap().get(&pb).is_none());
+            assert!(store.retrieve(pb.clone()).is_ok());
+
+            let pb = pb.with_base(store.path().clone());
+            assert!(store.entries.read().unwrap().get(&pb).is_some());
+        }
+    }
+
+    #[test]
+    fn test_get_none() {
+        let store = get_store();
+
+        for n in 1..100 {
+            match store.get(PathBuf::from(format!("test-{}", n))) {
+                Ok(None) => assert!(true),
+                _        => assert!(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_delete_none() {
+        let store = get_store();
+
+        for n in 1..100 {
+            match store.delete(PathBuf::from(format!("test-{}", n))) {
+                Err(_) => assert!(true),
+                _      => assert!(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_retrieve_for_module() {
+        let pathes = vec![
+            "foo/1", "foo/2", "foo/3", "foo/4", "foo/5",
+            "bar/1", "bar/2", "bar/3", "bar/4", "bar/5",
+            "bla/1", "bla/2", "bla/3", "bla/4", "bla/5",
+            "boo/1", "boo/2", "boo/3", "boo/4", "boo/5",
+            "glu/1", "glu/2", "glu/3", "glu/4", "glu/5",
+        ];
+
+        fn test(store: &Store, modulename: &str) {
+            use std::path::Component;
+            use storeid::StoreId;
+
+            let retrieved = store.retrieve_for_module(modulename);
+            assert!(retrieved.is_ok());
+            let v : Vec<StoreId> = retrieved.unwrap().collect();
+            println!("v = {:?}", v);
+            assert!(v.len() == 5);
+
+            let retrieved = store.retrieve_for_module(modulename);
+            assert!(retrieved.is_ok());
+
+            assert!(retrieved.unwrap().all(|e| {
+                let first = e.components().next();
+                assert!(first.is_some());
+                match first.unwrap() {
+                    Component::Normal(s) => s == modulename,
+                    _                    => false,
+                }
+            }))
+        }
+
+        let store = get_store();
+        for path in pathes {
+            assert!(store.create(PathBuf::from(path)).is_ok());
+        }
+
+        test(&store, "foo");
+        test(&store, "bar");
+        test(&store, "bla");
+        test(&store, "boo");
+        test(&store, "glu");
+    }
+
+    #[test]
+    fn test_store_move_moves_in_hm() {
+        use storeid::StoreId;
+
+        let store = get_store();
+
+        for n in 1..100 {
+            if n % 2 == 0 { // every second
+                let id    = StoreId::new_baseless(PathBuf::from(format!("t-{}", n))).unwrap();
+                let id_mv = StoreId::new_baseless(PathBuf::from(format!("t-{}", n - 1))).unwrap();
+
+                {
+                    assert!(store.entries.read().unwrap().get(&id).is_none());
+                }
+
+                {
+                    assert!(store.create(id.clone()).is_ok());
+                }
+
+                {
+                    let id_with_base = id.clone().with_base(store.path().clone());
+                    assert!(store.entries.read().unwrap().get(&id_with_base).is_some());
+                }
+
+                let r = store.move_by_id(id.clone(), id_mv.clone());
+                assert!(r.map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+
+                {
+                    let id_mv_with_base = id_mv.clone().with_base(store.path().clone());
+                    assert!(store.entries.read().unwrap().get(&id_mv_with_base).is_some());
+                }
+
+                assert!(match store.get(id.clone()) { Ok(None) => true, _ => false },
+                        "Moved id ({:?}) is still there", id);
+                assert!(match store.get(id_mv.clone()) { Ok(Some(_)) => true, _ => false },
+                        "New id ({:?}) is not in store...", id_mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transaction_create() {
+        use super::Entry;
+        use storeid::StoreId;
+
+        let store = get_store();
+
+        {
+            let mut tx = store.transaction();
+            for n in 1..10 {
+                let id = StoreId::new_baseless(PathBuf::from(format!("tx-{}", n))).unwrap();
+                tx.create(Entry::new(id));
+            }
+            assert!(tx.commit().map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+        }
+
+        for n in 1..10 {
+            let res = store.get(PathBuf::from(format!("tx-{}", n)));
+            assert!(match res { Ok(Some(_)) => true, _ => false, })
+        }
+    }
+
+    #[test]
+    fn test_transaction_update_delete() {
+        use storeid::StoreId;
+
+        let store  = get_store();
+        let upd_id = StoreId::new_baseless(PathBuf::from("tx-update")).unwrap();
+        let del_id = StoreId::new_baseless(PathBuf::from("tx-delete")).unwrap();
+
+        assert!(store.create(upd_id.clone()).is_ok());
+        assert!(store.create(del_id.clone()).is_ok());
+
+        {
+            let mut fle = store.retrieve(upd_id.clone()).unwrap();
+            *fle.get_content_mut() = String::from("updated");
+
+            let mut tx = store.transaction();
+            tx.update(fle).delete(del_id.clone());
+            assert!(tx.commit().map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+        }
+
+        let copy = store.retrieve_copy(upd_id).unwrap();
+        assert_eq!(copy.get_content(), "updated");
+        assert!(match store.get(del_id) { Ok(None) => true, _ => false });
+    }
+
+    #[test]
+    fn test_transaction_update_fs_entry_is_reread() {
+        use std::fs::File;
+        use std::io::Read;
+        use tempdir::TempDir;
+        use storeid::StoreId;
+
+        let dir   = TempDir::new("imag-store-transaction").unwrap();
+        let store = Store::new(dir.path().to_path_buf(), None).unwrap();
+        let id    = StoreId::new_baseless(PathBuf::from("tx-update-fs")).unwrap();
+
+        assert!(store.create(id.clone()).is_ok());
+
+        {
+            let mut fle = store.retrieve(id.clone()).unwrap();
+            *fle.get_content_mut() = String::from("updated");
+
+            let mut tx = store.transaction();
+            tx.update(fle);
+            assert!(tx.commit().map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+        }
+
+        {
+            let mut fle = store.get(id.clone()).unwrap().unwrap();
+            assert_eq!(fle.get_content(), "updated");
+            *fle.get_content_mut() = String::from("updated again");
+        }
+
+        let mut content = String::new();
+        File::open(dir.path().join("tx-update-fs")).unwrap().read_to_string(&mut content).unwrap();
+        assert!(content.contains("updated again"));
+        assert_eq!(store.retrieve_copy(id).unwrap().get_content(), "updated again");
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        use super::Entry;
+        use storeid::StoreId;
+
+        let store   = get_store();
+        let created = StoreId::new_baseless(PathBuf::from("tx-created")).unwrap();
+        let missing = StoreId::new_baseless(PathBuf::from("tx-missing")).unwrap();
+        let moved   = StoreId::new_baseless(PathBuf::from("tx-moved")).unwrap();
+
+        {
+            let mut tx = store.transaction();
+            tx.create(Entry::new(created.clone())).move_by_id(missing, moved.clone());
+
+            // moving fails, as the entry does not exist, so creating is reverted
+            assert!(tx.commit().is_err());
+        }
+
+        assert!(match store.get(created) { Ok(None) => true, _ => false });
+        assert!(match store.get(moved) { Ok(None) => true, _ => false });
+    }
+
+    #[test]
+    fn test_transaction_duplicate_id() {
+        use super::Entry;
+        use storeid::StoreId;
+
+        let store = get_store();
+        let id    = StoreId::new_baseless(PathBuf::from("tx-duplicate")).unwrap();
+
+        {
+            let mut tx = store.transaction();
+            tx.create(Entry::new(id.clone())).create(Entry::new(id.clone()));
+            assert!(tx.commit().is_err());
+        }
+
+        assert!(match store.get(id) { Ok(None) => true, _ => false });
+    }
+
+    #[test]
+    fn test_check_schema() {
+        use std::collections::BTreeMap;
+
+        use schema::{FieldType, Schema};
+        use storeid::StoreId;
+        use toml::Value;
+
+        let store = get_store();
+        let schema = Schema::new("schema")
+            .required("schema.name", FieldType::String)
+            .optional("schema.value", FieldType::Integer);
+        assert!(store.register_schema(schema).is_ok());
+
+        let id = StoreId::new_baseless(PathBuf::from("schema/a")).unwrap();
+        let mut entry = store.create(id).unwrap();
+        assert_eq!(store.check_schema(&entry).unwrap().len(), 1);
+
+        {
+            let header = entry.get_header_mut();
+            header.insert("schema", Value::Table(BTreeMap::new())).unwrap();
+            header.insert("schema.name", Value::String(String::from("a"))).unwrap();
+        }
+        assert!(store.check_schema(&entry).unwrap().is_empty());
+
+        let other = StoreId::new_baseless(PathBuf::from("other/a")).unwrap();
+        let other = store.create(other).unwrap();
+        assert!(store.check_schema(&other).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_registered_schemas_are_persisted() {
+        use schema::{FieldType, Schema};
+        use storeid::StoreId;
+
+        let backend = InMemoryFileAbstraction::new();
+        backend.create_dir_all(&PathBuf::from("/")).unwrap();
+
+        {
+            let store = Store::new_with_backend(PathBuf::from("/"),
+                                                None,
+                                                Box::new(backend.clone())).unwrap();
+            let schema = Schema::new("persisted").required("persisted.name", FieldType::String);
+            assert!(store.register_schema(schema).is_ok());
+            assert!(store.create(PathBuf::from("persisted/a")).is_ok());
+        }
+
+        // A store which does not register the schema itself still knows it
+        let store = Store::new_with_backend(PathBuf::from("/"),
+                                            None,
+                                            Box::new(backend.clone())).unwrap();
+        assert_eq!(store.entries().unwrap().count(), 1);
+
+        let id    = StoreId::new_baseless(PathBuf::from("persisted/a")).unwrap();
+        let entry = store.retrieve_copy(id).unwrap();
+        assert_eq!(store.check_schema(&entry).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_migrate() {
+        use std::collections::BTreeMap;
+
+        use semver::Version;
+        use toml::Value;
+
+        use migration::Migration;
+        use store::Entry;
+        use store::Result;
+        use storeid::StoreId;
+
+        #[derive(Debug)]
+        struct TestMigration(Version);
+
+        impl Migration for TestMigration {
+            fn module(&self) -> Option<&str> {
+                Some("migrate")
+            }
+
+            fn version(&self) -> Version {
+                self.0.clone()
+            }
+
+            fn migrate(&self, entry: &mut Entry) -> Result<()> {
+                let header = entry.get_header_mut();
+                try!(header.insert("migrate", Value::Table(BTreeMap::new())));
+                header.insert("migrate.done", Value::Boolean(true)).map(|_| ())
+            }
+        }
+
+        let store   = get_store();
+        let current = Version::parse(version!()).unwrap();
+        let mut newer = current.clone();
+        newer.increment_major();
+        assert!(store.register_migration(Box::new(TestMigration(current))).is_ok());
+        assert!(store.register_migration(Box::new(TestMigration(newer))).is_ok());
+
+        let old = StoreId::new_baseless(PathBuf::from("migrate/old")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("migrate/new")).unwrap();
+        {
+            let mut entry = store.create(old.clone()).unwrap();
+            let version   = Value::String(String::from("0.1.0"));
+            entry.get_header_mut().set("imag.version", version).unwrap();
+        }
+        assert!(store.create(new.clone()).is_ok());
+
+        let reports = store.migrate(true).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].id().local(), old.local());
+        assert_eq!(reports[0].applied(), 1);
+        assert!(store.retrieve_copy(old.clone()).unwrap().get_header().read("migrate.done").unwrap().is_none());
+
+        assert_eq!(store.migrate(false).unwrap().len(), 1);
+        let entry = store.retrieve_copy(old).unwrap();
+        assert_eq!(entry.get_header().read("migrate.done").unwrap(), Some(Value::Boolean(true)));
+        assert_eq!(entry.get_header().read("imag.version").unwrap(),
+                   Some(Value::String(String::from(version!()))));
+
+        assert!(store.migrate(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_and_import() {
+        use toml::Value;
+
+        use storeid::StoreId;
+
+        let source = get_store();
+        for n in 1..4 {
+            let id = StoreId::new_baseless(PathBuf::from(format!("snapshot/{}", n))).unwrap();
+            let mut entry = source.create(id).unwrap();
+            entry.get_header_mut().insert("snapshot", Value::Integer(n)).unwrap();
+            *entry.get_content_mut() = format!("content {}", n);
+        }
+        assert!(source.create(StoreId::new_baseless(PathBuf::from("other/1")).unwrap()).is_ok());
+
+        assert_eq!(source.snapshot(None).unwrap().len(), 4);
+        let snapshot = source.snapshot(Some("snapshot")).unwrap();
+        assert_eq!(snapshot.len(), 3);
+
+        let target = get_store();
+        for entry in snapshot.iter() {
+            assert!(target.import_entry(entry.clone()).is_ok());
+        }
+
+        for n in 1..4 {
+            let id    = StoreId::new_baseless(PathBuf::from(format!("snapshot/{}", n))).unwrap();
+            let entry = target.retrieve_copy(id).unwrap();
+            assert_eq!(entry.get_header().read("snapshot").unwrap(), Some(Value::Integer(n)));
+            assert_eq!(*entry.get_content(), format!("content {}", n));
+        }
+
+        assert!(target.import_entry(snapshot[0].clone()).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip_keeps_header_types() {
+        use toml::Value;
+
+        use storeid::StoreId;
+
+        let date   = Value::Datetime(String::from("2017-01-02T10:00:00Z"));
+        let source = get_store();
+        {
+            let id = StoreId::new_baseless(PathBuf::from("roundtrip/1")).unwrap();
+            let mut entry = source.create(id).unwrap();
+            entry.get_header_mut().insert("date", date.clone()).unwrap();
+            entry.get_header_mut().insert("float", Value::Float(1.5)).unwrap();
+            *entry.get_content_mut() = String::from("content\n");
+        }
+
+        // This is what imag-store writes into and reads from its archives
+        let serialized : Vec<(StoreId, String)> = source.snapshot(None)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.get_location().clone(), e.to_str()))
+            .collect();
+
+        let target = get_store();
+        for (id, s) in serialized {
+            assert!(target.import_entry(Entry::from_str(id, &s).unwrap()).is_ok());
+        }
+
+        let id     = StoreId::new_baseless(PathBuf::from("roundtrip/1")).unwrap();
+        let copy   = target.retrieve_copy(id.clone()).unwrap();
+        let origin = source.retrieve_copy(id).unwrap();
+        assert_eq!(copy.get_header().read("date").unwrap(), Some(date));
+        assert_eq!(copy.get_header().read("float").unwrap(), Some(Value::Float(1.5)));
+        assert_eq!(copy.get_header().header(), origin.get_header().header());
+        assert_eq!(copy.get_content(), origin.get_content());
+    }
+
+    #[test]
+    fn test_two_stores_cannot_borrow_same_entry() {
+        use storeid::StoreId;
+
+        let backend = InMemoryFileAbstraction::new();
+        backend.create_dir_all(&PathBuf::from("/")).unwrap();
+        let store_a = Store::new_with_backend(PathBuf::from("/"), None, Box::new(backend.clone()))
+            .unwrap();
+        let store_b = Store::new_with_backend(PathBuf::from("/"), None, Box::new(backend))
+            .unwrap();
+
+        {
+            let _entry = store_a.create(PathBuf::from("test-locking")).unwrap();
+            assert!(store_b.retrieve(PathBuf::from("test-locking")).is_err());
+            assert!(store_b.delete(PathBuf::from("test-locking")).is_err());
+        }
+
+        {
+            let _entry = store_b.retrieve(PathBuf::from("test-locking")).unwrap();
+            assert!(store_a.retrieve(PathBuf::from("test-locking")).is_err());
+
+            // the entry stays in the cache if deleting it fails
+            let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("test-locking")).unwrap();
+            assert!(store_a.delete(id.clone()).is_err());
+            assert!(store_a.entries.read().unwrap().contains_key(&id));
+        }
+
+        assert!(store_a.retrieve(PathBuf::from("test-locking")).is_ok());
+    }
+
+    #[test]
+    fn test_two_fs_stores_cannot_borrow_same_entry() {
+        use tempdir::TempDir;
+
+        let dir     = TempDir::new("imag-store-locking").unwrap();
+        let store_a = Store::new(dir.path().to_path_buf(), None).unwrap();
+        let store_b = Store::new(dir.path().to_path_buf(), None).unwrap();
+
+        {
+            let _entry = store_a.retrieve(PathBuf::from("test-locking")).unwrap();
+            assert!(store_b.retrieve(PathBuf::from("test-locking")).is_err());
+        }
+
+        assert!(store_b.retrieve(PathBuf::from("test-locking")).is_ok());
+    }
+
+    #[test]
+    fn test_fs_store_ignores_hidden_files_and_directories() {
+        use std::fs::{File, create_dir_all};
+        use tempdir::TempDir;
+
+        let dir   = TempDir::new("imag-store-hidden").unwrap();
+        let store = Store::new(dir.path().to_path_buf(), None).unwrap();
+        assert!(store.create(PathBuf::from("module/sub/entry")).is_ok());
+
+        let objects = dir.path().join(".git").join("objects").join("ab");
+        create_dir_all(&objects).unwrap();
+        File::create(objects.join("cdef")).unwrap();
+
+        assert_eq!(store.entries().unwrap().count(), 1);
+        assert_eq!(store.retrieve_for_module("module").unwrap().count(), 1);
+        assert!(store.migrate(true).is_ok());
+    }
+
+    #[test]
+    fn test_hidden_files_are_not_entries() {
+        use file_abstraction::FileAbstractionInstance;
+
+        let backend = InMemoryFileAbstraction::new();
+        backend.create_dir_all(&PathBuf::from("/")).unwrap();
+
+        let store = Store::new_with_backend(PathBuf::from("/"),
+                                            None,
+                                            Box::new(backend.clone())).unwrap();
+        assert!(store.create(PathBuf::from("hidden/entry")).is_ok());
+
+        for path in vec!["/.hidden/file", "/hidden/.file", "/hidden/.git/objects/ab"] {
+            backend.new_instance(PathBuf::from(path)).write_file_content(b"no entry").unwrap();
+        }
+
+        assert_eq!(store.entries().unwrap().count(), 1);
+        assert_eq!(store.retrieve_for_module("hidden").unwrap().count(), 1);
+        assert_eq!(store.snapshot(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_store_drop_releases_locks() {
+        use std::mem::forget;
+
+        let backend = InMemoryFileAbstraction::new();
+        backend.create_dir_all(&PathBuf::from("/")).unwrap();
+
+        {
+            let store = Store::new_with_backend(PathBuf::from("/"),
+                                                None,
+                                                Box::new(backend.clone())).unwrap();
+            let entry = store.create(PathBuf::from("test-drop")).unwrap();
+            forget(entry);
+        }
+
+        let store = Store::new_with_backend(PathBuf::from("/"), None, Box::new(backend)).unwrap();
+        assert!(store.retrieve(PathBuf::from("test-drop")).is_ok());
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        use toml::Parser;
+
+        let cfg = Parser::new(r#"
+[store]
+cache-size = 2
+store-unload-hook-aspects  = []
+pre-create-hook-aspects    = []
+post-create-hook-aspects   = []
+pre-move-hook-aspects      = []
+post-move-hook-aspects     = []
+pre-retrieve-hook-aspects  = []
+post-retrieve-hook-aspects = []
+pre-update-hook-aspects    = []
+post-update-hook-aspects   = []
+pre-delete-hook-aspects    = []
+post-delete-hook-aspects   = []
+
+[store.aspects]
+
+[store.hooks]
+        "#).parse().unwrap();
+
+        let store = Store::new_in_memory(PathBuf::from("/"), cfg.get("store").cloned()).unwrap();
+
+        {
+            let entries = (1..5)
+                .map(|n| store.create(PathBuf::from(format!("test-cache-{}", n))).unwrap())
+                .collect::<Vec<_>>();
+
+            // borrowed entries are never evicted
+            assert_eq!(store.entries.read().unwrap().len(), 4);
+            assert_eq!(entries.len(), 4);
+        }
+        assert_eq!(store.entries.read().unwrap().len(), 2);
+
+        for n in 1..5 {
+            assert!(store.get(PathBuf::from(format!("test-cache-{}", n))).unwrap().is_some());
+        }
+        assert!(store.create(PathBuf::from("test-cache-1")).is_err());
+    }
+
+    fn get_store_with_header_index() -> Store {
+        use toml::Parser;
+
+        let cfg = Parser::new(r#"
+[store]
+header-index = true
+store-unload-hook-aspects  = []
+pre-create-hook-aspects    = []
+post-create-hook-aspects   = []
+pre-move-hook-aspects      = []
+post-move-hook-aspects     = []
+pre-retrieve-hook-aspects  = []
+post-retrieve-hook-aspects = []
+pre-update-hook-aspects    = []
+post-update-hook-aspects   = []
+pre-delete-hook-aspects    = []
+post-delete-hook-aspects   = []
+
+[store.aspects]
+
+[store.hooks]
+        "#).parse().unwrap();
+
+        Store::new_in_memory(PathBuf::from("/"), cfg.get("store").cloned()).unwrap()
+    }
+
+    #[test]
+    fn test_header_index_is_maintained() {
+        use storeid::StoreId;
+        use toml::Value;
+
+        let store = get_store_with_header_index();
+        let a     = StoreId::new_baseless(PathBuf::from("index/a")).unwrap();
+        let b     = StoreId::new_baseless(PathBuf::from("index/b")).unwrap();
+        let c     = StoreId::new_baseless(PathBuf::from("index/c")).unwrap();
+
+        for id in vec![a.clone(), b.clone()] {
+            let mut entry = store.create(id).unwrap();
+            let tags = Value::Array(vec![Value::String(String::from("foo"))]);
+            entry.get_header_mut().insert("imag.tags", tags).unwrap();
+        }
+
+        assert_eq!(store.header_index().unwrap().unwrap().ids_with_tag("foo").len(), 2);
+
+        assert!(store.move_by_id(b.clone(), c.clone()).is_ok());
+        assert!(store.delete(a.clone()).is_ok());
+
+        let index = store.header_index().unwrap().unwrap();
+        let ids   = index.ids_with_tag("foo");
+        assert_eq!(ids.len(), 1);
+        assert_eq!(ids[0].local(), c.local());
+    }
+
+    #[test]
+    fn test_header_index_answers_queries() {
+        use super::Entry;
+        use toml::Value;
+
+        let tag       = Value::String(String::from("foo"));
+        let is_tagged = |e: &Entry| match e.get_header().read("imag.tags") {
+            Ok(Some(Value::Array(ref tags))) => tags.contains(&tag),
+            _                                => false,
+        };
+
+        for store in vec![get_store_with_header_index(), get_store()] {
+            for name in vec!["index/a", "index/b", "other/c"] {
+                let mut entry = store.create(PathBuf::from(name)).unwrap();
+                if name != "index/b" {
+                    entry.get_header_mut().insert("imag.tags", Value::Array(vec![tag.clone()]))
+                        .unwrap();
+                }
+            }
+
+            assert_eq!(store.find_by_header(Some("index"), &is_tagged).unwrap().len(), 1);
+            assert_eq!(store.find_by_header(None, &is_tagged).unwrap().len(), 2);
+
+            // created, but not yet written to the index
+            let _entry = store.create(PathBuf::from("index/d")).unwrap();
+            assert_eq!(store.retrieve_for_module("index").unwrap().count(), 3);
+            assert_eq!(store.find_by_header(Some("index"), |_| true).unwrap().len(), 3);
+        }
     }
 
     #[test]
-    fn test_store_instantiation() {
-        let store = get_store();
+    fn test_header_index_rebuild() {
+        use storeid::StoreId;
 
-        assert_eq!(store.location, PathBuf::from("/"));
-        assert!(store.entries.read().unwrap().is_empty());
+        let store = get_store_with_header_index();
+        for n in 0..5 {
+            let id = StoreId::new_baseless(PathBuf::from(format!("index/{}", n))).unwrap();
+            assert!(store.create(id).is_ok());
+        }
 
-        assert!(store.store_unload_aspects.lock().unwrap().is_empty());
+        assert_eq!(store.rebuild_header_index().unwrap(), 5);
+        assert_eq!(store.header_index().unwrap().unwrap().ids_for_module("index").len(), 5);
 
-        assert!(store.pre_create_aspects.lock().unwrap().is_empty());
-        assert!(store.post_create_aspects.lock().unwrap().is_empty());
-        assert!(store.pre_retrieve_aspects.lock().unwrap().is_empty());
-        assert!(store.post_retrieve_aspects.lock().unwrap().is_empty());
-        assert!(store.pre_update_aspects.lock().unwrap().is_empty());
-        assert!(store.post_update_aspects.lock().unwrap().is_empty());
-        assert!(store.pre_delete_aspects.lock().unwrap().is_empty());
-        assert!(store.post_delete_aspects.lock().unwrap().is_empty());
-        assert!(store.pre_move_aspects.lock().unwrap().is_empty());
-        assert!(store.post_move_aspects.lock().unwrap().is_empty());
+        assert!(get_store().rebuild_header_index().is_err());
     }
 
-    #[test]
-    fn test_store_create() {
-        let store = get_store();
+    /// Updates the `imag.links` arrays of the linked entries, as libimagentrylink does it
+    #[derive(Debug)]
+    struct TestLinkUpdater;
 
-        for n in 1..100 {
-            let s = format!("test-{}", n);
-            let entry = store.create(PathBuf::from(s.clone())).unwrap();
-            assert!(entry.verify().is_ok());
-            let loc = entry.get_location().clone().into_pathbuf().unwrap();
-            assert!(loc.starts_with("/"));
-            assert!(loc.ends_with(s));
+    impl ::linkupdate::LinkUpdater for TestLinkUpdater {
+
+        fn linked(&self, entry: &super::Entry) -> super::Result<Vec<::storeid::StoreId>> {
+            use storeid::StoreId;
+
+            Ok(links_in(entry)
+               .into_iter()
+               .map(|l| StoreId::new_baseless(PathBuf::from(l)).unwrap())
+               .collect())
         }
-    }
 
-    #[test]
-    fn test_store_get_create_get_delete_get() {
-        let store = get_store();
+        fn update(&self,
+                  entry: &mut super::Entry,
+                  old: &::storeid::StoreId,
+                  new: Option<&::storeid::StoreId>)
+            -> super::Result<()>
+        {
+            use toml::Value;
 
-        for n in 1..100 {
-            let res = store.get(PathBuf::from(format!("test-{}", n)));
-            assert!(match res { Ok(None) => true, _ => false, })
+            let old = old.clone().without_base().to_str().unwrap();
+            let mut links = links_in(entry).into_iter().filter(|l| *l != old).collect::<Vec<_>>();
+            if let Some(new) = new {
+                links.push(new.clone().without_base().to_str().unwrap());
+            }
+
+            let links = links.into_iter().map(Value::String).collect();
+            entry.get_header_mut().set("imag.links", Value::Array(links)).map(|_| ())
         }
 
-        for n in 1..100 {
-            let s = format!("test-{}", n);
-            let entry = store.create(PathBuf::from(s.clone())).unwrap();
+    }
 
-            assert!(entry.verify().is_ok());
+    fn get_store_with_link_updates() -> Store {
+        use toml::Parser;
 
-            let loc = entry.get_location().clone().into_pathbuf().unwrap();
+        let cfg = Parser::new(r#"
+[store]
+update-links = true
+store-unload-hook-aspects  = []
+pre-create-hook-aspects    = []
+post-create-hook-aspects   = []
+pre-move-hook-aspects      = []
+post-move-hook-aspects     = []
+pre-retrieve-hook-aspects  = []
+post-retrieve-hook-aspects = []
+pre-update-hook-aspects    = []
+post-update-hook-aspects   = []
+pre-delete-hook-aspects    = []
+post-delete-hook-aspects   = []
+
+[store.aspects]
+
+[store.hooks]
+        "#).parse().unwrap();
+
+        let store = Store::new_in_memory(PathBuf::from("/"), cfg.get("store").cloned()).unwrap();
+        assert!(store.register_link_updater(Box::new(TestLinkUpdater)).is_ok());
+        store
+    }
 
-            assert!(loc.starts_with("/"));
-            assert!(loc.ends_with(s));
-        }
+    fn links_in(entry: &super::Entry) -> Vec<String> {
+        use toml::Value;
 
-        for n in 1..100 {
-            let res = store.get(PathBuf::from(format!("test-{}", n)));
-            assert!(match res { Ok(Some(_)) => true, _ => false, })
+        match entry.get_header().read("imag.links").unwrap() {
+            Some(Value::Array(links)) => links.into_iter()
+                .map(|l| match l { Value::String(s) => s, _ => panic!("Non-String link") })
+                .collect(),
+            _ => vec![],
         }
+    }
 
-        for n in 1..100 {
-            assert!(store.delete(PathBuf::from(format!("test-{}", n))).is_ok())
-        }
+    fn links_of(store: &Store, id: &str) -> Vec<String> {
+        use storeid::StoreId;
 
-        for n in 1..100 {
-            let res = store.get(PathBuf::from(format!("test-{}", n)));
-            assert!(match res { Ok(None) => true, _ => false, })
+        let id = StoreId::new_baseless(PathBuf::from(id)).unwrap();
+        links_in(&store.retrieve_copy(id).unwrap())
+    }
+
+    fn link_entries(store: &Store, a: &str, b: &str) {
+        use storeid::StoreId;
+        use toml::Value;
+
+        for &(from, to) in &[(a, b), (b, a)] {
+            let id        = StoreId::new_baseless(PathBuf::from(from)).unwrap();
+            let mut entry = store.retrieve(id).unwrap();
+            let mut links = match entry.get_header().read("imag.links").unwrap() {
+                Some(Value::Array(links)) => links,
+                _ => vec![],
+            };
+            links.push(Value::String(String::from(to)));
+            entry.get_header_mut().set("imag.links", Value::Array(links)).unwrap();
         }
     }
 
     #[test]
-    fn test_store_create_twice() {
-        use error::StoreErrorKind as SEK;
+    fn test_move_repoints_links() {
+        use storeid::StoreId;
 
-        let store = get_store();
+        let store = get_store_with_link_updates();
+        link_entries(&store, "links/a", "links/b");
+        link_entries(&store, "links/a", "links/c");
 
-        for n in 1..100 {
-            let s = format!("test-{}", n % 50);
-            store.create(PathBuf::from(s.clone()))
-                .map_err(|e| assert!(is_match!(e.err_type(), SEK::CreateCallError) && n >= 50))
-                .ok()
-                .map(|entry| {
-                    assert!(entry.verify().is_ok());
-                    let loc = entry.get_location().clone().into_pathbuf().unwrap();
-                    assert!(loc.starts_with("/"));
-                    assert!(loc.ends_with(s));
-                });
-        }
+        let a = StoreId::new_baseless(PathBuf::from("links/a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("links/d")).unwrap();
+        assert!(store.move_by_id(a, d).is_ok());
+
+        assert_eq!(links_of(&store, "links/b"), vec![String::from("links/d")]);
+        assert_eq!(links_of(&store, "links/c"), vec![String::from("links/d")]);
+        assert_eq!(links_of(&store, "links/d"), vec![String::from("links/b"),
+                                                     String::from("links/c")]);
     }
 
     #[test]
-    fn test_store_create_in_hm() {
+    fn test_delete_removes_links() {
         use storeid::StoreId;
 
-        let store = get_store();
-
-        for n in 1..100 {
-            let pb = StoreId::new_baseless(PathBuf::from(format!("test-{}", n))).unwrap();
+        let store = get_store_with_link_updates();
+        link_entries(&store, "links/a", "links/b");
+        link_entries(&store, "links/c", "links/b");
 
-            assert!(store.entries.read().unwrap().get(&pb).is_none());
-            assert!(store.create(pb.clone()).is_ok());
+        let b = StoreId::new_baseless(PathBuf::from("links/b")).unwrap();
+        assert!(store.delete(b).is_ok());
 
-            let pb = pb.with_base(store.path().clone());
-            assert!(store.entries.read().unwrap().get(&pb).is_some());
-        }
+        assert!(links_of(&store, "links/a").is_empty());
+        assert!(links_of(&store, "links/c").is_empty());
     }
 
     #[test]
-    fn test_store_retrieve_in_hm() {
+    fn test_save_as_repoints_links() {
         use storeid::StoreId;
 
-        let store = get_store();
-
-        for n in 1..100 {
-            let pb = StoreId::new_baseless(PathBuf::from(format!("test-{}", n))).unwrap();
+        let store = get_store_with_link_updates();
+        link_entries(&store, "links/a", "links/b");
 
-            assert!(store.entries.read().unwrap().get(&pb).is_none());
-            assert!(store.retrieve(pb.clone()).is_ok());
+        let a = store.retrieve(PathBuf::from("links/a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("links/d")).unwrap();
+        assert!(store.save_as(a, d).is_ok());
 
-            let pb = pb.with_base(store.path().clone());
-            assert!(store.entries.read().unwrap().get(&pb).is_some());
-        }
+        assert_eq!(links_of(&store, "links/b"), vec![String::from("links/d")]);
     }
 
     #[test]
-    fn test_get_none() {
-        let store = get_store();
+    fn test_transaction_updates_links() {
+        use storeid::StoreId;
 
-        for n in 1..100 {
-            match store.get(PathBuf::from(format!("test-{}", n))) {
-                Ok(None) => assert!(true),
-                _        => assert!(false),
-            }
+        let store = get_store_with_link_updates();
+        link_entries(&store, "links/a", "links/b");
+        link_entries(&store, "links/a", "links/c");
+        link_entries(&store, "links/e", "links/c");
+
+        let a = StoreId::new_baseless(PathBuf::from("links/a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("links/d")).unwrap();
+        let e = StoreId::new_baseless(PathBuf::from("links/e")).unwrap();
+        {
+            // "links/c" is linked with both, its links are updated for both operations
+            let mut b = store.retrieve(PathBuf::from("links/b")).unwrap();
+            *b.get_content_mut() = String::from("updated");
+
+            let mut tx = store.transaction();
+            tx.update(b).move_by_id(a, d).delete(e);
+            assert!(tx.commit().map_err(|e| println!("ERROR: {:?}", e)).is_ok());
         }
+
+        assert_eq!(links_of(&store, "links/b"), vec![String::from("links/d")]);
+        assert_eq!(links_of(&store, "links/c"), vec![String::from("links/d")]);
+        let b = StoreId::new_baseless(PathBuf::from("links/b")).unwrap();
+        assert_eq!(store.retrieve_copy(b).unwrap().get_content(), "updated");
     }
 
     #[test]
-    fn test_delete_none() {
-        let store = get_store();
+    fn test_transaction_fails_if_linked_entry_is_borrowed() {
+        use storeid::StoreId;
 
-        for n in 1..100 {
-            match store.delete(PathBuf::from(format!("test-{}", n))) {
-                Err(_) => assert!(true),
-                _      => assert!(false),
-            }
+        let store = get_store_with_link_updates();
+        link_entries(&store, "links/a", "links/b");
+
+        let a = StoreId::new_baseless(PathBuf::from("links/a")).unwrap();
+        {
+            let _b = store.retrieve(PathBuf::from("links/b")).unwrap();
+            let mut tx = store.transaction();
+            tx.delete(a.clone());
+            assert!(tx.commit().is_err());
         }
-    }
 
-    // Disabled because we cannot test this by now, as we rely on glob() in
-    // Store::retieve_for_module(), which accesses the filesystem and tests run in-memory, so there
-    // are no files on the filesystem in this test after Store::create().
-    //
-    // #[test]
-    // fn test_retrieve_for_module() {
-    //     let pathes = vec![
-    //         "foo/1", "foo/2", "foo/3", "foo/4", "foo/5",
-    //         "bar/1", "bar/2", "bar/3", "bar/4", "bar/5",
-    //         "bla/1", "bla/2", "bla/3", "bla/4", "bla/5",
-    //         "boo/1", "boo/2", "boo/3", "boo/4", "boo/5",
-    //         "glu/1", "glu/2", "glu/3", "glu/4", "glu/5",
-    //     ];
-
-    //     fn test(store: &Store, modulename: &str) {
-    //         use std::path::Component;
-    //         use storeid::StoreId;
-
-    //         let retrieved = store.retrieve_for_module(modulename);
-    //         assert!(retrieved.is_ok());
-    //         let v : Vec<StoreId> = retrieved.unwrap().collect();
-    //         println!("v = {:?}", v);
-    //         assert!(v.len() == 5);
-
-    //         let retrieved = store.retrieve_for_module(modulename);
-    //         assert!(retrieved.is_ok());
-
-    //         assert!(retrieved.unwrap().all(|e| {
-    //             let first = e.components().next();
-    //             assert!(first.is_some());
-    //             match first.unwrap() {
-    //                 Component::Normal(s) => s == modulename,
-    //                 _                    => false,
-    //             }
-    //         }))
-    //     }
-
-    //     let store = get_store();
-    //     for path in pathes {
-    //         assert!(store.create(PathBuf::from(path)).is_ok());
-    //     }
-
-    //     test(&store, "foo");
-    //     test(&store, "bar");
-    //     test(&store, "bla");
-    //     test(&store, "boo");
-    //     test(&store, "glu");
-    // }
+        assert!(store.get(a).unwrap().is_some());
+        assert_eq!(links_of(&store, "links/b"), vec![String::from("links/a")]);
+    }
 
     #[test]
-    fn test_store_move_moves_in_hm() {
+    fn test_borrowed_linked_entry_prevents_move_and_delete() {
         use storeid::StoreId;
 
-        let store = get_store();
-
-        for n in 1..100 {
-            if n % 2 == 0 { // every second
-                let id    = StoreId::new_baseless(PathBuf::from(format!("t-{}", n))).unwrap();
-                let id_mv = StoreId::new_baseless(PathBuf::from(format!("t-{}", n - 1))).unwrap();
+        let store = get_store_with_link_updates();
+        link_entries(&store, "links/a", "links/b");
 
-                {
-                    assert!(store.entries.read().unwrap().get(&id).is_none());
-                }
+        let a = StoreId::new_baseless(PathBuf::from("links/a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("links/d")).unwrap();
+        {
+            let _b = store.retrieve(PathBuf::from("links/b")).unwrap();
+            assert!(store.move_by_id(a.clone(), d).is_err());
+            assert!(store.delete(a.clone()).is_err());
+        }
 
-                {
-                    assert!(store.create(id.clone()).is_ok());
-                }
+        // Nothing was changed, so there are no links to entries which do not exist
+        assert!(store.get(a).unwrap().is_some());
+        assert_eq!(links_of(&store, "links/a"), vec![String::from("links/b")]);
+        assert_eq!(links_of(&store, "links/b"), vec![String::from("links/a")]);
+    }
 
-                {
-                    let id_with_base = id.clone().with_base(store.path().clone());
-                    assert!(store.entries.read().unwrap().get(&id_with_base).is_some());
-                }
+    #[test]
+    fn test_links_are_not_updated_by_default() {
+        use storeid::StoreId;
 
-                let r = store.move_by_id(id.clone(), id_mv.clone());
-                assert!(r.map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+        let store = get_store();
+        link_entries(&store, "links/a", "links/b");
 
-                {
-                    let id_mv_with_base = id_mv.clone().with_base(store.path().clone());
-                    assert!(store.entries.read().unwrap().get(&id_mv_with_base).is_some());
-                }
+        let a = StoreId::new_baseless(PathBuf::from("links/a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("links/d")).unwrap();
+        assert!(store.move_by_id(a, d).is_ok());
 
-                assert!(match store.get(id.clone()) { Ok(None) => true, _ => false },
-                        "Moved id ({:?}) is still there", id);
-                assert!(match store.get(id_mv.clone()) { Ok(Some(_)) => true, _ => false },
-                        "New id ({:?}) is not in store...", id_mv);
-            }
-        }
+        assert_eq!(links_of(&store, "links/b"), vec![String::from("links/a")]);
     }
-
 }
 
 #[cfg(test)]
@@ -2533,6 +4578,8 @@ mod store_hook_tests {
                     HP::PostRetrieve |
                     HP::PreUpdate    |
                     HP::PostUpdate   => HDA::MutableAccess(&self.accessor),
+                    HP::PreMove      |
+                    HP::PostMove     => HDA::MoveAccess(&self.accessor),
                 }
             }
 
@@ -2540,6 +4587,7 @@ mod store_hook_tests {
 
         pub mod accessor {
             use hook::result::HookResult;
+            use hook::accessor::MoveAccessor;
             use hook::accessor::MutableHookDataAccessor;
             use hook::accessor::NonMutableHookDataAccessor;
             use hook::accessor::StoreIdAccessor;
@@ -2612,6 +4660,14 @@ mod store_hook_tests {
 
             }
 
+            impl MoveAccessor for TestHookAccessor {
+
+                fn access(&self, _old: &StoreId, _new: &StoreId) -> HookResult<()> {
+                    get_result(self.succeed, self.error_aborting)
+                }
+
+            }
+
         }
 
     }
@@ -2629,7 +4685,9 @@ mod store_hook_tests {
 
         let cfg = Parser::new(mini_config()).parse().unwrap();
         println!("Config parsed: {:?}", cfg);
-        Store::new(PathBuf::from("/"), Some(cfg.get("store").cloned().unwrap())).unwrap()
+
+        Store::new_in_memory(PathBuf::from("/"), cfg.get("store").cloned())
+            .unwrap()
     }
 
     fn mini_config() -> &'static str {
@@ -2772,10 +4830,146 @@ aspect = "test"
         test_hook_execution(&[HP::PostDelete], "test_postdelete");
     }
 
+    #[test]
+    fn test_premove() {
+        test_hook_execution(&[HP::PreMove], "test_premove");
+    }
+
+    #[test]
+    fn test_postmove() {
+        test_hook_execution(&[HP::PostMove], "test_postmove");
+    }
+
+    #[test]
+    fn test_move_hooks_get_old_and_new_id() {
+        use std::sync::Arc;
+        use std::sync::Mutex;
+
+        use toml::Value;
+
+        use hook::Hook;
+        use hook::accessor::HookDataAccessor;
+        use hook::accessor::HookDataAccessorProvider;
+        use hook::accessor::MoveAccessor;
+        use hook::result::HookResult;
+
+        #[derive(Debug)]
+        struct RecordingHook(Arc<Mutex<Vec<(StoreId, StoreId)>>>);
+
+        impl Hook for RecordingHook {
+            fn name(&self) -> &'static str { "testhook_recording" }
+            fn set_config(&mut self, _: &Value) { }
+        }
+
+        impl HookDataAccessorProvider for RecordingHook {
+            fn accessor(&self) -> HookDataAccessor {
+                HookDataAccessor::MoveAccess(self)
+            }
+        }
+
+        impl MoveAccessor for RecordingHook {
+            fn access(&self, old: &StoreId, new: &StoreId) -> HookResult<()> {
+                self.0.lock().unwrap().push((old.clone(), new.clone()));
+                Ok(())
+            }
+        }
+
+        let mut store = get_store_with_config();
+        let moves     = Arc::new(Mutex::new(vec![]));
+        for pos in [HP::PreMove, HP::PostMove].iter() {
+            let hook = RecordingHook(moves.clone());
+            assert!(store.register_hook(pos.clone(), "test", Box::new(hook)).is_ok());
+        }
+
+        let old = StoreId::new_baseless(PathBuf::from("test_move_ids")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("test_move_ids-moved")).unwrap();
+        assert!(store.create(old.clone()).is_ok());
+        assert!(store.move_by_id(old.clone(), new.clone()).is_ok());
+
+        {
+            let moves = moves.lock().unwrap();
+            assert_eq!(moves.len(), 2);
+            for &(ref o, ref n) in moves.iter() {
+                assert_eq!(o.local(), old.local());
+                assert_eq!(n.local(), new.local());
+            }
+        }
+
+        // Copying an entry is not a move
+        let copy = StoreId::new_baseless(PathBuf::from("test_move_ids-copy")).unwrap();
+        {
+            let entry = store.get(new.clone()).unwrap().unwrap();
+            assert!(store.save_to(&entry, copy.clone()).is_ok());
+        }
+        assert_eq!(moves.lock().unwrap().len(), 2);
+
+        let saved = StoreId::new_baseless(PathBuf::from("test_move_ids-saved")).unwrap();
+        {
+            let entry = store.get(copy.clone()).unwrap().unwrap();
+            assert!(store.save_as(entry, saved.clone()).is_ok());
+        }
+        let moves = moves.lock().unwrap();
+        assert_eq!(moves.len(), 4);
+        for &(ref o, ref n) in moves[2..].iter() {
+            assert_eq!(o.local(), copy.local());
+            assert_eq!(n.local(), saved.local());
+        }
+    }
+
+    #[test]
+    fn test_transaction_post_create_hook_changes_are_written() {
+        use toml::Value;
+
+        use hook::Hook;
+        use hook::accessor::HookDataAccessor;
+        use hook::accessor::HookDataAccessorProvider;
+        use hook::accessor::MutableHookDataAccessor;
+        use hook::result::HookResult;
+        use store::Entry;
+        use store::FileLockEntry;
+
+        #[derive(Debug)]
+        struct ContentHook;
+
+        impl Hook for ContentHook {
+            fn name(&self) -> &'static str { "testhook_content" }
+            fn set_config(&mut self, _: &Value) { }
+        }
+
+        impl HookDataAccessorProvider for ContentHook {
+            fn accessor(&self) -> HookDataAccessor {
+                HookDataAccessor::MutableAccess(self)
+            }
+        }
+
+        impl MutableHookDataAccessor for ContentHook {
+            fn access_mut(&self, fle: &mut FileLockEntry) -> HookResult<()> {
+                *fle.get_content_mut() = String::from("set by hook");
+                Ok(())
+            }
+        }
+
+        let mut store = get_store_with_config();
+        assert!(store.register_hook(HP::PostCreate, "test", Box::new(ContentHook)).is_ok());
+
+        let id = StoreId::new_baseless(PathBuf::from("test_tx_post_create")).unwrap();
+        {
+            let mut tx = store.transaction();
+            tx.create(Entry::new(id.clone()));
+            assert!(tx.commit().map_err(|e| println!("ERROR: {:?}", e)).is_ok());
+        }
+
+        assert_eq!(store.retrieve_copy(id.clone()).unwrap().get_content(), "set by hook");
+
+        // the entry was released again
+        assert!(store.get(id).unwrap().is_some());
+    }
+
     #[test]
     fn test_multiple_same_position() {
         let positions = [ HP::StoreUnload, HP::PreCreate, HP::PostCreate, HP::PreRetrieve,
-            HP::PostRetrieve, HP::PreUpdate, HP::PostUpdate, HP::PreDelete, HP::PostDelete ];
+            HP::PostRetrieve, HP::PreUpdate, HP::PostUpdate, HP::PreDelete, HP::PostDelete,
+            HP::PreMove, HP::PostMove ];
 
         for position in positions.iter() {
             for n in 2..10 {
@@ -2830,6 +5024,18 @@ aspect = "test"
         assert!(store.update(fle).is_err());
     }
 
+    #[test]
+    fn test_pre_move_error() {
+        let old   = StoreId::new_baseless(PathBuf::from("test_pre_move_error")).unwrap();
+        let new   = StoreId::new_baseless(PathBuf::from("test_pre_move_error-moved")).unwrap();
+        let store = get_store_with_aborting_hook_at_pos(HP::PreMove);
+        assert!(store.create(old.clone()).is_ok());
+
+        assert!(store.move_by_id(old.clone(), new.clone()).is_err());
+        assert!(store.get(old).unwrap().is_some());
+        assert!(store.get(new).unwrap().is_none());
+    }
+
     #[test]
     fn test_post_create_error() {
         let store   = get_store_with_aborting_hook_at_pos(HP::PostCreate);
@@ -2837,8 +5043,10 @@ aspect = "test"
 
         assert!(store.create(pb.clone()).is_err());
 
-        // But the entry exists, as the hook fails post-create
-        assert!(store.entries.read().unwrap().get(&pb.with_base(store.path().clone())).is_some());
+        // The entry was created, but is removed again, as the hook failed
+        let pb = pb.with_base(store.path().clone());
+        assert!(store.entries.read().unwrap().get(&pb).is_none());
+        assert!(!store.backend.exists(&pb.into_pathbuf().unwrap()).unwrap());
     }
 
     #[test]
@@ -2877,6 +5085,19 @@ aspect = "test"
         assert!(store.update(fle).is_err());
     }
 
+    #[test]
+    fn test_post_move_error() {
+        let old   = StoreId::new_baseless(PathBuf::from("test_post_move_error")).unwrap();
+        let new   = StoreId::new_baseless(PathBuf::from("test_post_move_error-moved")).unwrap();
+        let store = get_store_with_aborting_hook_at_pos(HP::PostMove);
+        assert!(store.create(old.clone()).is_ok());
+
+        // the entry is moved, though the hook fails
+        assert!(store.move_by_id(old.clone(), new.clone()).is_err());
+        assert!(store.get(old).unwrap().is_none());
+        assert!(store.get(new).unwrap().is_some());
+    }
+
     fn get_store_with_allowed_error_hook_at_pos(pos: HP) -> Store {
         let mut store = get_store_with_config();
         let hook      = TestHook::new(pos.clone(), false, false);
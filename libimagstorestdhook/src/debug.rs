@@ -72,6 +72,8 @@ impl HookDataAccessorProvider for DebugHook {
             HP::PostRetrieve |
             HP::PreUpdate    |
             HP::PostUpdate   => HDA::MutableAccess(&self.accessor),
+            HP::PreMove      |
+            HP::PostMove     => HDA::MoveAccess(&self.accessor),
         }
     }
 
@@ -83,6 +85,7 @@ pub mod accessor {
     use libimagstore::storeid::StoreId;
     use libimagstore::store::FileLockEntry;
     use libimagstore::hook::result::HookResult;
+    use libimagstore::hook::accessor::MoveAccessor;
     use libimagstore::hook::accessor::MutableHookDataAccessor;
     use libimagstore::hook::accessor::NonMutableHookDataAccessor;
     use libimagstore::hook::accessor::StoreIdAccessor;
@@ -130,5 +133,14 @@ pub mod accessor {
 
     }
 
+    impl MoveAccessor for DebugHookAccessor {
+
+        fn access(&self, old: &StoreId, new: &StoreId) -> HookResult<()> {
+            debug!("[DEBUG HOOK]: {:?} -> {:?}", old, new);
+            Ok(())
+        }
+
+    }
+
 }
 
@@ -0,0 +1,599 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Hooks which run an external program
+//!
+//! External hooks are configured in the store configuration, as an array of tables:
+//!
+//! ```toml
+//! [[store.external-hooks]]
+//! position = "pre-update"
+//! aspect   = "external"
+//! command  = "/usr/local/bin/imag-validate"
+//! args     = [ "--strict" ]
+//! mutable  = false
+//! ```
+//!
+//! The program gets a JSON object on stdin, which contains the `position` and the `id` of the
+//! entry. For positions where the entry is available, the object also contains its `header` and
+//! its `content`. Hooks for moving entries get `old_id` and `new_id` instead of `id`.
+//!
+//! If the hook is `mutable`, the program may print a JSON object with a new `header` and/or
+//! `content` for the entry to stdout. Mutable hooks are only executed in aspects which allow
+//! mutable hooks.
+//!
+//! JSON has no datetime type, so datetimes in the header are passed as strings. They are not
+//! converted back: if a mutable hook prints the header it got, its datetimes become strings.
+//! TOML has no null, so a header in the output which contains `null` is rejected and the hook
+//! fails.
+//!
+//! If the program exits with a non-zero exit code, the hook fails and the store action is aborted.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+use serde_json;
+use serde_json::Value as JsonValue;
+use toml::Value;
+
+use libimagerror::into::IntoError;
+use libimagstore::hook::Hook;
+use libimagstore::hook::accessor::HookDataAccessor as HDA;
+use libimagstore::hook::accessor::HookDataAccessorProvider;
+use libimagstore::hook::accessor::MoveAccessor;
+use libimagstore::hook::accessor::MutableHookDataAccessor;
+use libimagstore::hook::accessor::NonMutableHookDataAccessor;
+use libimagstore::hook::accessor::StoreIdAccessor;
+use libimagstore::hook::error::HookErrorKind as HEK;
+use libimagstore::hook::position::HookPosition;
+use libimagstore::hook::result::HookResult;
+use libimagstore::store::EntryHeader;
+use libimagstore::store::FileLockEntry;
+use libimagstore::storeid::StoreId;
+
+/// A hook which runs an external program
+#[derive(Debug)]
+pub struct ExternalHook {
+    position: HookPosition,
+    command: String,
+    args: Vec<String>,
+    mutable: bool,
+}
+
+impl ExternalHook {
+
+    pub fn new(position: HookPosition, command: String, args: Vec<String>, mutable: bool)
+        -> ExternalHook
+    {
+        ExternalHook {
+            position: position,
+            command: command,
+            args: args,
+            mutable: mutable,
+        }
+    }
+
+    pub fn position(&self) -> &HookPosition {
+        &self.position
+    }
+
+    /// Run the program with `input` on stdin and return what it printed to stdout
+    fn run(&self, mut input: BTreeMap<String, JsonValue>) -> HookResult<String> {
+        input.insert(String::from("position"),
+                     JsonValue::String(String::from(position_name(&self.position))));
+        let input = try!(serde_json::to_string(&JsonValue::Object(input))
+            .map_err(|e| HEK::HookExecutionError.into_error_with_cause(Box::new(e))));
+
+        debug!("[EXTERNAL HOOK]: Running {:?} {:?}", self.command, self.args);
+        let mut child = try!(Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| HEK::HookExecutionError.into_error_with_cause(Box::new(e))));
+
+        // The input is written from another thread, as the program might write to stdout before
+        // it read all of its input. If we wrote all of the input first, both would wait for each
+        // other as soon as the pipes are full.
+        let writer = child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || stdin.write_all(input.as_bytes()))
+        });
+
+        let output = try!(child.wait_with_output()
+            .map_err(|e| HEK::HookExecutionError.into_error_with_cause(Box::new(e))));
+
+        if let Some(writer) = writer {
+            match writer.join() {
+                Ok(Ok(())) => {},
+                // The program exited without reading all of its input, which is fine
+                Ok(Err(ref e)) if e.kind() == ErrorKind::BrokenPipe => {
+                    debug!("[EXTERNAL HOOK]: {:?} did not read all of its input", self.command);
+                },
+                Ok(Err(e)) => {
+                    return Err(HEK::HookExecutionError.into_error_with_cause(Box::new(e)));
+                },
+                Err(_) => return Err(HEK::HookExecutionError.into_error()),
+            }
+        }
+
+        if !output.status.success() {
+            debug!("[EXTERNAL HOOK]: {:?} failed: {:?}", self.command, output.status);
+            return Err(HEK::HookExecutionError.into_error());
+        }
+
+        String::from_utf8(output.stdout)
+            .map_err(|e| HEK::HookExecutionError.into_error_with_cause(Box::new(e)))
+    }
+
+}
+
+impl Hook for ExternalHook {
+
+    fn name(&self) -> &'static str {
+        "stdhook_external"
+    }
+
+    fn set_config(&mut self, _: &Value) {
+        debug!("Ignoring configuration in external hook, it is configured in 'external-hooks'");
+    }
+
+}
+
+impl HookDataAccessorProvider for ExternalHook {
+
+    fn accessor(&self) -> HDA {
+        use libimagstore::hook::position::HookPosition as HP;
+
+        match self.position {
+            HP::StoreUnload  |
+            HP::PreCreate    |
+            HP::PreRetrieve  |
+            HP::PreDelete    |
+            HP::PostDelete   => HDA::StoreIdAccess(self),
+            HP::PostCreate   |
+            HP::PostRetrieve |
+            HP::PreUpdate    |
+            HP::PostUpdate   => if self.mutable {
+                HDA::MutableAccess(self)
+            } else {
+                HDA::NonMutableAccess(self)
+            },
+            HP::PreMove      |
+            HP::PostMove     => HDA::MoveAccess(self),
+        }
+    }
+
+}
+
+impl StoreIdAccessor for ExternalHook {
+
+    fn access(&self, id: &StoreId) -> HookResult<()> {
+        let mut input = BTreeMap::new();
+        input.insert(String::from("id"), id_to_json(id));
+        self.run(input).map(|_| ())
+    }
+
+}
+
+impl NonMutableHookDataAccessor for ExternalHook {
+
+    fn access(&self, fle: &FileLockEntry) -> HookResult<()> {
+        self.run(entry_to_json(fle)).map(|_| ())
+    }
+
+}
+
+impl MutableHookDataAccessor for ExternalHook {
+
+    fn access_mut(&self, fle: &mut FileLockEntry) -> HookResult<()> {
+        let output = try!(self.run(entry_to_json(fle)));
+        if output.trim().is_empty() {
+            debug!("[EXTERNAL HOOK]: No output, leaving entry untouched");
+            return Ok(());
+        }
+
+        let output : JsonValue = try!(serde_json::from_str(&output)
+            .map_err(|e| HEK::HookExecutionError.into_error_with_cause(Box::new(e))));
+
+        if let Some(header) = output.find("header") {
+            match json_to_toml(header) {
+                Some(Value::Table(t)) => *fle.get_header_mut() = EntryHeader::from(t),
+                _ => {
+                    debug!("[EXTERNAL HOOK]: Header in output is not an object or contains null");
+                    return Err(HEK::HookExecutionError.into_error());
+                },
+            }
+        }
+
+        if let Some(content) = output.find("content") {
+            match content.as_str() {
+                Some(s) => *fle.get_content_mut() = String::from(s),
+                None    => {
+                    debug!("[EXTERNAL HOOK]: Content in output is not a string");
+                    return Err(HEK::HookExecutionError.into_error());
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+impl MoveAccessor for ExternalHook {
+
+    fn access(&self, old: &StoreId, new: &StoreId) -> HookResult<()> {
+        let mut input = BTreeMap::new();
+        input.insert(String::from("old_id"), id_to_json(old));
+        input.insert(String::from("new_id"), id_to_json(new));
+        self.run(input).map(|_| ())
+    }
+
+}
+
+/// Build the external hooks from the `external-hooks` array of the store configuration
+///
+/// Returns the hooks together with the names of the aspects they have to be registered in.
+/// Malformed hook configurations are reported and skipped.
+pub fn external_hooks_from_config(store_config: Option<&Value>) -> Vec<(ExternalHook, String)> {
+    let hooks = match store_config.and_then(|c| c.lookup("external-hooks")) {
+        Some(&Value::Array(ref a)) => a,
+        Some(_) => {
+            warn!("Key 'external-hooks' in store configuration is not an array");
+            return vec![];
+        },
+        None => return vec![],
+    };
+
+    hooks.iter()
+        .filter_map(|cfg| {
+            let hook = external_hook_from_config(cfg);
+            if hook.is_none() {
+                warn!("Ignoring malformed external hook configuration: {:?}", cfg);
+            }
+            hook
+        })
+        .collect()
+}
+
+fn external_hook_from_config(cfg: &Value) -> Option<(ExternalHook, String)> {
+    let get_str = |key: &str| match cfg.lookup(key) {
+        Some(&Value::String(ref s)) => Some(s.clone()),
+        _ => None,
+    };
+
+    let position = match get_str("position").and_then(|p| position_from_name(&p)) {
+        Some(p) => p,
+        None    => return None,
+    };
+    let aspect   = match get_str("aspect") {
+        Some(a) => a,
+        None    => return None,
+    };
+    let command  = match get_str("command") {
+        Some(c) => c,
+        None    => return None,
+    };
+    let args = match cfg.lookup("args") {
+        Some(&Value::Array(ref a)) => {
+            let args = a.iter()
+                .filter_map(|v| match *v {
+                    Value::String(ref s) => Some(s.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<String>>();
+            if args.len() != a.len() {
+                return None;
+            }
+            args
+        },
+        Some(_) => return None,
+        None    => vec![],
+    };
+    let mutable = match cfg.lookup("mutable") {
+        Some(&Value::Boolean(b)) => b,
+        Some(_) => return None,
+        None    => false,
+    };
+
+    Some((ExternalHook::new(position, command, args, mutable), aspect))
+}
+
+fn position_from_name(name: &str) -> Option<HookPosition> {
+    use libimagstore::hook::position::HookPosition as HP;
+
+    match name {
+        "store-unload"  => Some(HP::StoreUnload),
+        "pre-create"    => Some(HP::PreCreate),
+        "post-create"   => Some(HP::PostCreate),
+        "pre-retrieve"  => Some(HP::PreRetrieve),
+        "post-retrieve" => Some(HP::PostRetrieve),
+        "pre-update"    => Some(HP::PreUpdate),
+        "post-update"   => Some(HP::PostUpdate),
+        "pre-delete"    => Some(HP::PreDelete),
+        "post-delete"   => Some(HP::PostDelete),
+        "pre-move"      => Some(HP::PreMove),
+        "post-move"     => Some(HP::PostMove),
+        _               => None,
+    }
+}
+
+fn position_name(position: &HookPosition) -> &'static str {
+    use libimagstore::hook::position::HookPosition as HP;
+
+    match *position {
+        HP::StoreUnload  => "store-unload",
+        HP::PreCreate    => "pre-create",
+        HP::PostCreate   => "post-create",
+        HP::PreRetrieve  => "pre-retrieve",
+        HP::PostRetrieve => "post-retrieve",
+        HP::PreUpdate    => "pre-update",
+        HP::PostUpdate   => "post-update",
+        HP::PreDelete    => "pre-delete",
+        HP::PostDelete   => "post-delete",
+        HP::PreMove      => "pre-move",
+        HP::PostMove     => "post-move",
+    }
+}
+
+fn id_to_json(id: &StoreId) -> JsonValue {
+    JsonValue::String(format!("{}", id.local().display()))
+}
+
+fn entry_to_json(fle: &FileLockEntry) -> BTreeMap<String, JsonValue> {
+    let mut map = BTreeMap::new();
+    map.insert(String::from("id"), id_to_json(fle.get_location()));
+    map.insert(String::from("header"), toml_to_json(fle.get_header().header()));
+    map.insert(String::from("content"), JsonValue::String(fle.get_content().clone()));
+    map
+}
+
+fn toml_to_json(v: &Value) -> JsonValue {
+    match *v {
+        Value::String(ref s)   => JsonValue::String(s.clone()),
+        Value::Integer(i)      => JsonValue::I64(i),
+        Value::Float(f)        => JsonValue::F64(f),
+        Value::Boolean(b)      => JsonValue::Bool(b),
+        Value::Datetime(ref s) => JsonValue::String(s.clone()),
+        Value::Array(ref a)    => JsonValue::Array(a.iter().map(toml_to_json).collect()),
+        Value::Table(ref t)    => JsonValue::Object(t.iter()
+                                                     .map(|(k, v)| (k.clone(), toml_to_json(v)))
+                                                     .collect()),
+    }
+}
+
+/// Convert a JSON value to TOML
+///
+/// Returns `None` if the value is or contains `null`, which cannot be represented in TOML.
+fn json_to_toml(v: &JsonValue) -> Option<Value> {
+    match *v {
+        JsonValue::Null          => None,
+        JsonValue::Bool(b)       => Some(Value::Boolean(b)),
+        JsonValue::I64(i)        => Some(Value::Integer(i)),
+        JsonValue::U64(u)        => Some(Value::Integer(u as i64)),
+        JsonValue::F64(f)        => Some(Value::Float(f)),
+        JsonValue::String(ref s) => Some(Value::String(s.clone())),
+        JsonValue::Array(ref a)  => a.iter()
+            .map(json_to_toml)
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::Array),
+        JsonValue::Object(ref o) => o.iter()
+            .map(|(k, v)| json_to_toml(v).map(|v| (k.clone(), v)))
+            .collect::<Option<BTreeMap<String, Value>>>()
+            .map(Value::Table),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use serde_json;
+    use serde_json::Value as JsonValue;
+    use tempdir::TempDir;
+    use toml::Parser;
+    use toml::Value;
+
+    use libimagstore::hook::position::HookPosition as HP;
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+
+    use super::ExternalHook;
+    use super::external_hooks_from_config;
+
+    fn store_config(s: &str) -> Value {
+        Parser::new(s).parse().unwrap().get("store").cloned().unwrap()
+    }
+
+    fn get_store() -> Store {
+        let cfg = store_config(r#"
+[store]
+store-unload-hook-aspects  = [ ]
+pre-create-hook-aspects    = [ "external" ]
+post-create-hook-aspects   = [ ]
+pre-move-hook-aspects      = [ ]
+post-move-hook-aspects     = [ ]
+pre-retrieve-hook-aspects  = [ ]
+post-retrieve-hook-aspects = [ ]
+pre-update-hook-aspects    = [ "external" ]
+post-update-hook-aspects   = [ ]
+pre-delete-hook-aspects    = [ ]
+post-delete-hook-aspects   = [ ]
+
+[store.aspects.external]
+parallel = false
+mutable_hooks = true
+
+[store.hooks.stdhook_external]
+aspect = "external"
+        "#);
+
+        Store::new_in_memory(PathBuf::from("/"), Some(cfg)).unwrap()
+    }
+
+    /// A hook which runs `script` with `sh -c`, the arguments are passed as `$1`, `$2`, ...
+    fn sh_hook(position: HP, script: &str, args: &[&str], mutable: bool) -> ExternalHook {
+        let mut sh_args = vec![String::from("-c"), String::from(script), String::from("sh")];
+        sh_args.extend(args.iter().map(|a| String::from(*a)));
+        ExternalHook::new(position, String::from("sh"), sh_args, mutable)
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    #[test]
+    fn test_external_hooks_from_config() {
+        let cfg = store_config(r#"
+[store]
+[[store.external-hooks]]
+position = "pre-update"
+aspect   = "external"
+command  = "/bin/true"
+args     = [ "a", "b" ]
+mutable  = true
+
+[[store.external-hooks]]
+position = "post-move"
+aspect   = "external"
+command  = "/bin/false"
+        "#);
+
+        let hooks = external_hooks_from_config(Some(&cfg));
+        assert_eq!(hooks.len(), 2);
+
+        assert!(match *hooks[0].0.position() { HP::PreUpdate => true, _ => false });
+        assert_eq!(hooks[0].0.args, vec![String::from("a"), String::from("b")]);
+        assert!(hooks[0].0.mutable);
+        assert_eq!(hooks[0].1, "external");
+
+        assert!(match *hooks[1].0.position() { HP::PostMove => true, _ => false });
+        assert!(hooks[1].0.args.is_empty());
+        assert!(!hooks[1].0.mutable);
+    }
+
+    #[test]
+    fn test_malformed_external_hooks_are_skipped() {
+        let cfg = store_config(r#"
+[store]
+[[store.external-hooks]]
+position = "sometime"
+aspect   = "external"
+command  = "/bin/true"
+
+[[store.external-hooks]]
+position = "pre-create"
+command  = "/bin/true"
+        "#);
+
+        assert!(external_hooks_from_config(Some(&cfg)).is_empty());
+        assert!(external_hooks_from_config(None).is_empty());
+    }
+
+    #[test]
+    fn test_entry_is_sent_on_stdin() {
+        let dir       = TempDir::new("imag-external-hook").unwrap();
+        let out       = dir.path().join("input.json");
+        let mut store = get_store();
+        let hook      = sh_hook(HP::PreUpdate, "cat > \"$1\"", &[out.to_str().unwrap()], false);
+        assert!(store.register_hook(HP::PreUpdate, "external", Box::new(hook)).is_ok());
+
+        let mut fle = store.create(id("external/a")).unwrap();
+        fle.get_header_mut().insert("value", Value::Integer(1)).unwrap();
+        *fle.get_content_mut() = String::from("content");
+        assert!(store.update(fle).is_ok());
+
+        let mut input = String::new();
+        File::open(&out).unwrap().read_to_string(&mut input).unwrap();
+        let input : JsonValue = serde_json::from_str(&input).unwrap();
+
+        assert_eq!(input.find("position").and_then(|v| v.as_str()), Some("pre-update"));
+        assert_eq!(input.find("id").and_then(|v| v.as_str()), Some("external/a"));
+        assert_eq!(input.lookup("header.value").and_then(|v| v.as_i64()), Some(1));
+        assert_eq!(input.find("content").and_then(|v| v.as_str()), Some("content"));
+    }
+
+    #[test]
+    fn test_output_of_mutable_hook_is_applied() {
+        let output = format!(r#"{{
+            "header": {{ "imag": {{ "version": "{}", "links": [] }}, "value": 2 }},
+            "content": "changed"
+        }}"#, env!("CARGO_PKG_VERSION"));
+
+        let mut store = get_store();
+        let hook      = sh_hook(HP::PreUpdate, "cat > /dev/null; echo \"$1\"", &[&output], true);
+        assert!(store.register_hook(HP::PreUpdate, "external", Box::new(hook)).is_ok());
+
+        let mut fle = store.create(id("external/b")).unwrap();
+        fle.get_header_mut().insert("value", Value::Integer(1)).unwrap();
+        *fle.get_content_mut() = String::from("content");
+        assert!(store.update(fle).is_ok());
+
+        let entry = store.retrieve_copy(id("external/b")).unwrap();
+        assert_eq!(entry.get_header().read("value").unwrap(), Some(Value::Integer(2)));
+        assert_eq!(entry.get_content(), "changed");
+    }
+
+    #[test]
+    fn test_large_entry_does_not_block() {
+        let mut store = get_store();
+        let hook      = sh_hook(HP::PreUpdate, "cat", &[], true);
+        assert!(store.register_hook(HP::PreUpdate, "external", Box::new(hook)).is_ok());
+
+        // more than fits into a pipe, so the output is written while the input is still read
+        let content = ::std::iter::repeat("a").take(1024 * 1024).collect::<String>();
+
+        let mut fle = store.create(id("external/large")).unwrap();
+        *fle.get_content_mut() = content.clone();
+        assert!(store.update(fle).is_ok());
+
+        assert_eq!(store.retrieve_copy(id("external/large")).unwrap().get_content(), &content);
+    }
+
+    #[test]
+    fn test_null_in_output_is_rejected() {
+        let output = format!(r#"{{
+            "header": {{ "imag": {{ "version": "{}", "links": [] }}, "value": null }}
+        }}"#, env!("CARGO_PKG_VERSION"));
+
+        let mut store = get_store();
+        let hook      = sh_hook(HP::PreUpdate, "cat > /dev/null; echo \"$1\"", &[&output], true);
+        assert!(store.register_hook(HP::PreUpdate, "external", Box::new(hook)).is_ok());
+
+        let fle = store.create(id("external/null")).unwrap();
+        assert!(store.update(fle).is_err());
+    }
+
+    #[test]
+    fn test_failing_program_aborts() {
+        let mut store = get_store();
+        let hook      = sh_hook(HP::PreCreate, "cat > /dev/null; exit 1", &[], false);
+        assert!(store.register_hook(HP::PreCreate, "external", Box::new(hook)).is_ok());
+
+        assert!(store.create(id("external/c")).is_err());
+        assert!(store.get(id("external/c")).unwrap().is_none());
+    }
+
+}
@@ -37,6 +37,8 @@
 extern crate toml;
 extern crate fs2;
 extern crate git2;
+extern crate serde_json;
+#[cfg(test)] extern crate tempdir;
 
 extern crate libimagstore;
 extern crate libimagentrylink;
@@ -46,6 +48,7 @@ extern crate libimagutil;
 
 pub mod debug;
 pub mod denylinkeddelete;
+pub mod external;
 pub mod flock;
 pub mod linkverify;
 pub mod vcs;
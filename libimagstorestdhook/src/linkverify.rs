@@ -24,15 +24,24 @@ use toml::Value;
 use libimagstore::hook::Hook;
 use libimagstore::hook::accessor::HookDataAccessor as HDA;
 use libimagstore::hook::accessor::HookDataAccessorProvider;
+use libimagstore::hook::accessor::MutableHookDataAccessor;
 use libimagstore::hook::accessor::NonMutableHookDataAccessor;
 use libimagstore::hook::result::HookResult;
 use libimagstore::store::FileLockEntry;
+use libimagentrylink::check::remove_dangling_link;
 use libimagentrylink::internal::InternalLinker;
 use libimagerror::trace::trace_error;
 
+/// Hook which checks whether the entries an entry links to exist
+///
+/// Links to entries which do not exist are logged. If the hook is configured with
+/// `repair = true`, they are removed from the entry as `imag link check --repair` would do it.
+/// Repairing needs mutable access to the entry, so the hook has to be registered for a position
+/// which grants mutable access then.
 #[derive(Debug, Clone)]
 pub struct LinkedEntriesExistHook {
     store_location: PathBuf,
+    repair: bool,
 }
 
 impl LinkedEntriesExistHook {
@@ -40,6 +49,7 @@ impl LinkedEntriesExistHook {
     pub fn new(store_location: PathBuf) -> LinkedEntriesExistHook {
         LinkedEntriesExistHook {
             store_location: store_location,
+            repair: false,
         }
     }
 
@@ -51,8 +61,17 @@ impl Hook for LinkedEntriesExistHook {
         "stdhook_linked_entries_exist"
     }
 
-    fn set_config(&mut self, _: &Value) {
-        () // We are not configurable here.
+    fn set_config(&mut self, config: &Value) {
+        self.repair = match config.lookup("repair") {
+            Some(&Value::Boolean(b)) => b,
+            Some(_) => {
+                warn!("Configuration error,");
+                warn!("\t'store.hooks.stdhook_linked_entries_exist.repair' must be a Boolean.");
+                warn!("Defaulting to repair = false");
+                false
+            },
+            None => false,
+        };
     }
 
 }
@@ -60,7 +79,11 @@ impl Hook for LinkedEntriesExistHook {
 impl HookDataAccessorProvider for LinkedEntriesExistHook {
 
     fn accessor(&self) -> HDA {
-        HDA::NonMutableAccess(self)
+        if self.repair {
+            HDA::MutableAccess(self)
+        } else {
+            HDA::NonMutableAccess(self)
+        }
     }
 
 }
@@ -72,7 +95,7 @@ impl NonMutableHookDataAccessor for LinkedEntriesExistHook {
         let _ = fle.get_internal_links()
             .map(|links| {
                 for link in links {
-                    if !link.exists() {
+                    if !link.clone().with_base(self.store_location.clone()).exists() {
                         warn!("File link does not exist: {:?} -> {:?}", fle.get_location(), link);
                     }
                 }
@@ -86,3 +109,99 @@ impl NonMutableHookDataAccessor for LinkedEntriesExistHook {
 
 }
 
+impl MutableHookDataAccessor for LinkedEntriesExistHook {
+
+    fn access_mut(&self, fle: &mut FileLockEntry) -> HookResult<()> {
+        debug!("[LINKVERIFY HOOK][repair] {:?}", fle.get_location());
+        let dangling = match fle.get_internal_links() {
+            Ok(links) => links
+                .filter(|link| !link.clone().with_base(self.store_location.clone()).exists())
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("Couldn't execute Link-Verify hook");
+                trace_error(&e);
+                return Ok(());
+            },
+        };
+
+        for link in dangling {
+            warn!("Removing link which does not exist: {:?} -> {:?}", fle.get_location(), link);
+            if let Err(e) = remove_dangling_link(fle, &link) {
+                warn!("Couldn't remove link to {:?}", link);
+                trace_error(&e);
+            }
+        }
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+    use toml::Value;
+
+    use libimagstore::hook::Hook;
+    use libimagstore::hook::accessor::HookDataAccessor as HDA;
+    use libimagstore::hook::accessor::HookDataAccessorProvider;
+    use libimagstore::hook::accessor::MutableHookDataAccessor;
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+    use libimagentrylink::internal::InternalLinker;
+
+    use super::LinkedEntriesExistHook;
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    fn repair_config(repair: bool) -> Value {
+        let mut cfg = BTreeMap::new();
+        cfg.insert(String::from("repair"), Value::Boolean(repair));
+        Value::Table(cfg)
+    }
+
+    #[test]
+    fn test_repair_is_configurable() {
+        fn is_mutable(hook: &LinkedEntriesExistHook) -> bool {
+            match hook.accessor() {
+                HDA::MutableAccess(_) => true,
+                _                     => false,
+            }
+        }
+
+        let mut hook = LinkedEntriesExistHook::new(PathBuf::from("/"));
+        assert!(!is_mutable(&hook));
+
+        hook.set_config(&repair_config(true));
+        assert!(is_mutable(&hook));
+
+        hook.set_config(&repair_config(false));
+        assert!(!is_mutable(&hook));
+    }
+
+    #[test]
+    fn test_repair_removes_dangling_links() {
+        let dir   = TempDir::new("imag-linkverify").unwrap();
+        let store = Store::new(dir.path().to_path_buf(), None).unwrap();
+        {
+            let mut a = store.create(id("a")).unwrap();
+            let mut b = store.create(id("b")).unwrap();
+            assert!(a.add_internal_link(&mut b).is_ok());
+        }
+
+        let mut a = store.retrieve(id("a")).unwrap();
+        let links = vec![Value::String(String::from("b")), Value::String(String::from("gone"))];
+        a.get_header_mut().set("imag.links", Value::Array(links)).unwrap();
+
+        let mut hook = LinkedEntriesExistHook::new(dir.path().to_path_buf());
+        hook.set_config(&repair_config(true));
+        assert!(hook.access_mut(&mut a).is_ok());
+
+        assert_eq!(a.get_internal_links().unwrap().collect::<Vec<_>>(), vec![id("b")]);
+    }
+
+}
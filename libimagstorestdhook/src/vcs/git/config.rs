@@ -28,7 +28,7 @@ use vcs::git::result::Result;
 
 use vcs::git::action::StoreAction;
 
-use git2::Repository;
+use git2::{Repository, Commit, Tree};
 
 /// Check the configuration whether we should commit interactively
 pub fn commit_interactive(config: &Value, action: &StoreAction) -> bool {
@@ -87,31 +87,65 @@ fn commit_default_msg<'a>(config: &'a Value, action: &'a StoreAction) -> &'a str
     }
 }
 
-/// Get the commit template
+/// Get the commit message template, if one is configured
 ///
-/// TODO: Implement good template string
-fn commit_template(action: &StoreAction, id: &StoreId) -> String {
-    format!(r#"
-# Please commit your changes and remove these lines.
+/// See `vcs::git::message` for the placeholders which can be used in the template.
+fn commit_template<'a>(config: &'a Value, action: &StoreAction) -> Option<&'a str> {
+    match config.lookup("commit.template") {
+        Some(&Value::String(ref s)) => Some(s),
+        Some(_) => {
+            warn!("Configuration error, 'store.hooks.stdhook_git_{}.commit.template' must be a String.",
+                  action);
+            warn!("Ignoring commit.template");
+            None
+        }
+        None => None,
+    }
+}
+
+/// Get the text which is put into the editor when committing with the editor
+fn editor_text(action: &StoreAction, id: &StoreId, message: &str) -> String {
+    format!(r#"{message}
+
+# Please edit the commit message above. Lines starting with '#' are ignored.
 #
 # You're about to commit changes via the {action} Hook
 #
 #   Altered file: {id}
 #
-    "#,
+"#,
+    message = message,
     action = action,
     id = id.local().display())
 }
 
 /// Generate a commit message
 ///
-/// Uses the functions `commit_interactive()` and `commit_with_editor()`
-/// or reads one from the commandline or uses the `commit_default_msg()` string to create a commit
-/// message.
-pub fn commit_message(repo: &Repository, config: &Value, action: StoreAction, id: &StoreId) -> Result<String> {
+/// If there is a `commit.template` configured, it is rendered with the information about the
+/// commit (see `vcs::git::message`), otherwise the `commit_default_msg()` string is used.
+/// If `commit_interactive()` is enabled, this message is used as default for the message read
+/// from the commandline or, if `commit_with_editor()` is enabled, put into the editor.
+///
+/// `old_id` is the id the entry had before the action, if the action changed it (moving).
+pub fn commit_message(repo: &Repository,
+                      config: &Value,
+                      action: StoreAction,
+                      id: &StoreId,
+                      old_id: Option<&StoreId>,
+                      parents: &[&Commit],
+                      tree: &Tree)
+    -> Result<String>
+{
     use libimaginteraction::ask::ask_string;
     use libimagutil::edit::edit_in_tmpfile_with_command;
     use std::process::Command;
+    use vcs::git::message::CommitInfo;
+
+    let message = match commit_template(config, &action) {
+        Some(template) => try!(CommitInfo::collect(repo, &action, id, old_id, parents, tree))
+            .render(template),
+        None => String::from(commit_default_msg(config, &action)),
+    };
 
     if commit_interactive(config, &action) {
         if commit_with_editor(config, &action) {
@@ -121,18 +155,29 @@ pub fn commit_message(repo: &Repository, config: &Value, action: StoreAction, id
                 .map_err_into(GHEK::ConfigError)
                 .map(Command::new)
                 .and_then(|cmd| {
-                    let mut s = commit_template(&action, id);
-                    edit_in_tmpfile_with_command(cmd, &mut s).map(|_| s)
+                    let mut s = editor_text(&action, id, &message);
+                    edit_in_tmpfile_with_command(cmd, &mut s)
+                        .map(|_| strip_comments(&s))
                         .map_err_into(GHEK::EditorError)
                 })
         } else {
-            Ok(ask_string("Commit Message", None, false, false, None, "> "))
+            Ok(ask_string("Commit Message", Some(message), false, false, None, "> "))
         }
     } else {
-        Ok(String::from(commit_default_msg(config, &action)))
+        Ok(message)
     }
 }
 
+/// Remove the lines starting with '#' from a commit message
+fn strip_comments(s: &str) -> String {
+    s.lines()
+        .filter(|line| !line.starts_with("#"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
 /// Check whether the hook should abort if the repository cannot be initialized
 pub fn abort_on_repo_init_err(cfg: &Value) -> bool {
     get_bool_cfg(Some(cfg), "abort_on_repo_init_failure", true, true)
@@ -216,3 +261,39 @@ pub fn add_wt_changes_before_committing(cfg: &Value) -> bool {
     get_bool_cfg(Some(cfg), "commit.add_wt_changes", true, true)
 }
 
+/// Check whether the repository should be synchronized with a remote. Disabled by default.
+pub fn sync_is_enabled(cfg: &Value) -> bool {
+    get_bool_cfg(Some(cfg), "sync.enabled", false, false)
+}
+
+/// Get the name of the remote to synchronize with. Defaults to "origin".
+pub fn sync_remote(cfg: &Value) -> String {
+    match cfg.lookup("sync.remote") {
+        Some(&Value::String(ref s)) => s.clone(),
+        Some(_) => {
+            warn!("Configuration error, 'sync.remote' must be a String.");
+            warn!("Defaulting to sync.remote = 'origin'");
+            String::from("origin")
+        },
+        None => {
+            debug!("No key 'sync.remote' - Defaulting to 'origin'");
+            String::from("origin")
+        },
+    }
+}
+
+/// Get the (short) name of the branch to synchronize, if configured.
+///
+/// If this is `None`, the currently checked out branch is synchronized.
+pub fn sync_branch(cfg: &Value) -> Option<String> {
+    match cfg.lookup("sync.branch") {
+        Some(&Value::String(ref s)) => Some(s.clone()),
+        Some(_) => {
+            warn!("Configuration error, 'sync.branch' must be a String.");
+            warn!("Using the current branch");
+            None
+        },
+        None => None,
+    }
+}
+
@@ -135,7 +135,7 @@ impl StoreIdAccessor for DeleteHook {
 
         let _         = try!(self.runtime.ensure_cfg_branch_is_checked_out(&action));
         let repo      = try!(self.runtime.repository(&action));
-        let mut index = try!(fetch_index(repo, &action));
+        let mut index = try!(fetch_index(&repo, &action));
 
         let signature = try!(
             repo.signature()
@@ -218,7 +218,7 @@ impl StoreIdAccessor for DeleteHook {
                 .map_into_hook_error()
         );
 
-        let message = try!(commit_message(&repo, cfg, action, &id)
+        let message = try!(commit_message(&repo, cfg, action, &id, None, &parents, &tree)
                 .map_dbg_err_str("Failed to get commit message")
                 .map_dbg_str("[GIT DELETE HOOK]: Got commit message"));
 
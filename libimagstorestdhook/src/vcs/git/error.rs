@@ -31,6 +31,7 @@ generate_error_module!(
 
         RepositoryError                   => "Error while interacting with git repository",
         RepositoryInitError               => "Error while loading the git repository",
+        RepositoryLockError               => "Error while locking the git repository",
         RepositoryBackendError            => "Error in the git library",
         RepositoryBranchError             => "Error while interacting with git branch(es)",
         RepositoryBranchNameFetchingError => "Error while fetching branch name",
@@ -38,9 +39,23 @@ generate_error_module!(
         RepositoryIndexFetchingError      => "Error while fetching Repository Index",
         RepositoryIndexWritingError       => "Error while writing Repository Index",
         RepositoryPathAddingError         => "Error while adding Path to Index",
+        RepositoryPathRemovingError       => "Error while removing Path from Index",
         RepositoryCommittingError         => "Error while committing",
         RepositoryParentFetchingError     => "Error while fetching parent of commit",
         RepositoryStatusFetchError        => "Error while fetching repository status",
+        RepositoryCheckoutError           => "Error while checking out branch",
+        RepositoryRemoteError             => "Error while looking up remote",
+        RepositoryFetchError              => "Error while fetching from remote",
+        RepositoryPushError               => "Error while pushing to remote",
+        RepositoryRebaseError             => "Error while rebasing onto remote branch",
+        RepositoryRebaseConflict          => "Conflict while rebasing onto remote branch",
+        RepositorySyncError               => "Error while synchronizing with remote",
+        RepositoryDirty                   => "Working tree has uncommitted changes",
+        RepositoryDiffError               => "Error while computing diff",
+        RepositoryHistoryError            => "Error while reading history",
+        RevisionNotFound                  => "Revision not found",
+        EntryNotInRevision                => "Entry does not exist in revision",
+        EntryParseError                   => "Error while parsing entry",
 
         HeadFetchError                    => "Error while getting HEAD",
         NotOnBranch                       => "No Branch is checked out",
@@ -0,0 +1,346 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Reading the history of entries from the git repository of the store
+//!
+//! The git hooks write the history of the store into a git repository. This module provides
+//! functionality to read it back: listing the revisions which touched an entry, loading an entry
+//! as it was at a revision and comparing two versions of an entry.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use git2::{Repository, Commit, ObjectType, Oid, Tree, SORT_TIME};
+use toml::Value;
+
+use libimagerror::into::IntoError;
+use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+
+use vcs::git::error::GitHookErrorKind as GHEK;
+use vcs::git::error::MapErrInto;
+use vcs::git::message::flatten;
+use vcs::git::result::Result;
+
+/// A commit which touched an entry
+#[derive(Debug, Clone)]
+pub struct Revision {
+    id: String,
+    summary: String,
+    author: String,
+    time: i64,
+}
+
+impl Revision {
+
+    fn from_commit(commit: &Commit) -> Revision {
+        Revision {
+            id: commit.id().to_string(),
+            summary: String::from(commit.summary().unwrap_or("")),
+            author: String::from(commit.author().name().unwrap_or("")),
+            time: commit.time().seconds(),
+        }
+    }
+
+    /// The id (hash) of the commit
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    /// The first line of the commit message
+    pub fn summary(&self) -> &String {
+        &self.summary
+    }
+
+    /// The name of the author of the commit
+    pub fn author(&self) -> &String {
+        &self.author
+    }
+
+    /// The time of the commit, in seconds since the epoch
+    pub fn time(&self) -> i64 {
+        self.time
+    }
+
+}
+
+/// A difference in the header of two versions of an entry
+///
+/// Keys are in "a.b.c" notation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderChange {
+    Added(String, Value),
+    Removed(String, Value),
+    Changed(String, Value, Value),
+}
+
+/// A line of the difference between the content of two versions of an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineChange {
+    Unchanged(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Get the revisions which touched the entry `id`, newest first.
+///
+/// A revision touched an entry if it created, altered or deleted it.
+pub fn history(store_path: &Path, id: &StoreId) -> Result<Vec<Revision>> {
+    let repo = try!(Repository::open(store_path).map_err_into(GHEK::RepositoryInitError));
+    let head = try!(repo.refname_to_id("HEAD").map_err_into(GHEK::HeadFetchError));
+
+    let mut walk = try!(repo.revwalk().map_err_into(GHEK::RepositoryHistoryError));
+    walk.set_sorting(SORT_TIME);
+    try!(walk.push(head).map_err_into(GHEK::RepositoryHistoryError));
+
+    let mut revisions = vec![];
+    for oid in walk {
+        let commit = try!(oid
+                          .and_then(|oid| repo.find_commit(oid))
+                          .map_err_into(GHEK::RepositoryHistoryError));
+
+        let tree   = try!(commit.tree().map_err_into(GHEK::RepositoryHistoryError));
+        let own    = blob_id(&tree, id);
+        let parent = match commit.parent(0) {
+            Ok(parent) => {
+                let tree = try!(parent.tree().map_err_into(GHEK::RepositoryHistoryError));
+                blob_id(&tree, id)
+            },
+            Err(_) => None,
+        };
+
+        if own != parent {
+            revisions.push(Revision::from_commit(&commit));
+        }
+    }
+
+    Ok(revisions)
+}
+
+/// Load the entry `id` as it was at the revision `rev`.
+///
+/// `rev` can be anything git understands as a revision, for example a commit hash, "HEAD~2" or
+/// the name of a tag.
+pub fn entry_at_revision(store_path: &Path, id: &StoreId, rev: &str) -> Result<Entry> {
+    let repo   = try!(Repository::open(store_path).map_err_into(GHEK::RepositoryInitError));
+    let oid    = try!(repo.revparse_single(rev)
+                      .and_then(|object| object.peel(ObjectType::Commit))
+                      .map(|commit| commit.id())
+                      .map_err_into(GHEK::RevisionNotFound));
+    let commit = try!(repo.find_commit(oid).map_err_into(GHEK::RevisionNotFound));
+    let tree   = try!(commit.tree().map_err_into(GHEK::RepositoryHistoryError));
+
+    let blob_id = try!(blob_id(&tree, id).ok_or(GHEK::EntryNotInRevision.into_error()));
+    let blob    = try!(repo.find_blob(blob_id).map_err_into(GHEK::RepositoryHistoryError));
+    let content = try!(String::from_utf8(blob.content().to_vec())
+                       .map_err_into(GHEK::EntryParseError));
+
+    Entry::from_str(id.clone(), &content).map_err_into(GHEK::EntryParseError)
+}
+
+/// Compare the headers of two versions of an entry
+pub fn diff_headers(old: &Entry, new: &Entry) -> Vec<HeaderChange> {
+    let mut old_values = BTreeMap::new();
+    let mut new_values = BTreeMap::new();
+    flatten(String::new(), old.get_header().header(), &mut old_values);
+    flatten(String::new(), new.get_header().header(), &mut new_values);
+
+    let mut changes = vec![];
+    for (key, old_value) in old_values.iter() {
+        match new_values.get(key) {
+            None => changes.push(HeaderChange::Removed(key.clone(), old_value.clone())),
+            Some(new_value) => if new_value != old_value {
+                changes.push(HeaderChange::Changed(key.clone(), old_value.clone(), new_value.clone()))
+            },
+        }
+    }
+
+    for (key, new_value) in new_values.iter() {
+        if !old_values.contains_key(key) {
+            changes.push(HeaderChange::Added(key.clone(), new_value.clone()));
+        }
+    }
+
+    changes
+}
+
+/// Compare the content of two versions of an entry line by line
+pub fn diff_content(old: &Entry, new: &Entry) -> Vec<LineChange> {
+    let old : Vec<&str> = old.get_content().lines().collect();
+    let new : Vec<&str> = new.get_content().lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(LineChange::Unchanged(String::from(old[i])));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            changes.push(LineChange::Removed(String::from(old[i])));
+            i += 1;
+        } else {
+            changes.push(LineChange::Added(String::from(new[j])));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|l| LineChange::Removed(String::from(*l))));
+    changes.extend(new[j..].iter().map(|l| LineChange::Added(String::from(*l))));
+
+    changes
+}
+
+fn blob_id(tree: &Tree, id: &StoreId) -> Option<Oid> {
+    tree.get_path(id.local()).ok().map(|entry| entry.id())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{File, create_dir_all};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use git2::{Repository, Signature, Time};
+    use tempdir::TempDir;
+    use toml::Value;
+
+    use libimagstore::store::Entry;
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+
+    use super::{history, entry_at_revision};
+    use super::{diff_headers, diff_content, HeaderChange, LineChange};
+
+    /// Commit the entry `path` with `content` as content. Each commit gets its own `time`, so the
+    /// order of the history is well-defined.
+    fn commit_entry(repo: &Repository, path: &str, content: &str, time: i64) {
+        let file = repo.workdir().unwrap().join(path);
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(&file)
+            .unwrap()
+            .write_all(format!("---\n[imag]\nversion = \"0.2.0\"\nlinks = []\n---\n{}\n", content)
+                       .as_bytes())
+            .unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+
+        let tree    = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig     = Signature::new("imag", "imag@example.com", &Time::new(time, 0)).unwrap();
+        let parents = repo.head()
+            .ok()
+            .and_then(|head| head.target())
+            .map(|oid| repo.find_commit(oid).unwrap());
+        let parents = parents.iter().collect::<Vec<_>>();
+        let message = format!("{}: {}", path, content);
+
+        repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &parents).unwrap();
+    }
+
+    fn entry(header: &str, content: &str) -> Entry {
+        let id = StoreId::new_baseless(PathBuf::from("test")).unwrap();
+        let s  = format!("---\n[imag]\nversion = \"0.2.0\"\n{}\n---\n{}", header, content);
+        Entry::from_str(id, &s).unwrap()
+    }
+
+    #[test]
+    fn test_diff_headers() {
+        let old = entry("[note]\nname = \"a\"\nold = 1", "");
+        let new = entry("[note]\nname = \"b\"\nnew = 2", "");
+
+        let changes = diff_headers(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&HeaderChange::Changed(String::from("note.name"),
+                                                        Value::String(String::from("a")),
+                                                        Value::String(String::from("b")))));
+        assert!(changes.contains(&HeaderChange::Removed(String::from("note.old"), Value::Integer(1))));
+        assert!(changes.contains(&HeaderChange::Added(String::from("note.new"), Value::Integer(2))));
+    }
+
+    #[test]
+    fn test_diff_content() {
+        let old = entry("", "a\nb\nc");
+        let new = entry("", "a\nc\nd");
+
+        assert_eq!(diff_content(&old, &new), vec![
+            LineChange::Unchanged(String::from("a")),
+            LineChange::Removed(String::from("b")),
+            LineChange::Unchanged(String::from("c")),
+            LineChange::Added(String::from("d")),
+        ]);
+    }
+
+    #[test]
+    fn test_history_and_restore() {
+        let dir  = TempDir::new("imag-git-history").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let id   = StoreId::new_baseless(PathBuf::from("notes/foo")).unwrap();
+
+        commit_entry(&repo, "notes/foo", "first", 1000);
+        commit_entry(&repo, "notes/bar", "other", 1001);
+        commit_entry(&repo, "notes/foo", "second", 1002);
+
+        let revisions = history(dir.path(), &id).unwrap();
+        let summaries = revisions.iter().map(|r| r.summary().clone()).collect::<Vec<_>>();
+        assert_eq!(summaries, vec!["notes/foo: second", "notes/foo: first"]);
+        assert_eq!(revisions[0].author(), "imag");
+        assert_eq!(revisions[1].time(), 1000);
+
+        let first = entry_at_revision(dir.path(), &id, revisions[1].id()).unwrap();
+        assert_eq!(first.get_content(), "first\n");
+        assert_eq!(entry_at_revision(dir.path(), &id, "HEAD~1").unwrap().get_content(), "first\n");
+        assert!(entry_at_revision(dir.path(), &id, "no-such-revision").is_err());
+
+        // An annotated tag is a revision as well
+        let sig    = Signature::new("imag", "imag@example.com", &Time::new(3000, 0)).unwrap();
+        let target = repo.revparse_single(revisions[1].id()).unwrap();
+        repo.tag("first", &target, &sig, "The first revision", false).unwrap();
+        assert_eq!(entry_at_revision(dir.path(), &id, "first").unwrap().get_content(), "first\n");
+
+        let bar = StoreId::new_baseless(PathBuf::from("notes/bar")).unwrap();
+        assert!(entry_at_revision(dir.path(), &bar, "HEAD~2").is_err());
+
+        // Restoring, as `imag store restore` does it
+        let store = Store::new(dir.path().to_path_buf(), None).unwrap();
+        {
+            let mut entry = store.retrieve(id.clone()).unwrap();
+            assert_eq!(entry.get_content(), "second\n");
+            *entry.get_header_mut()  = first.get_header().clone();
+            *entry.get_content_mut() = first.get_content().clone();
+            assert!(store.update(entry).is_ok());
+        }
+
+        let restored = store.retrieve_copy(id).unwrap();
+        assert_eq!(restored.get_content(), "first\n");
+        assert!(diff_headers(&first, &restored).is_empty());
+    }
+
+}
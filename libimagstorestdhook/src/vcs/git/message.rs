@@ -0,0 +1,383 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Commit message templates for the git hooks
+//!
+//! A template is a string which may contain the following placeholders:
+//!
+//! * `{action}`: The store action which caused the commit, e.g. "Update"
+//! * `{id}`: The id of the entry
+//! * `{module}`: The module the entry belongs to
+//! * `{header_keys}`: The header keys which were changed, comma separated
+//! * `{files}`: The changed files, comma separated
+//! * `{binary}`: The name of the imag binary which triggered the commit
+//! * `{diff}`: A short summary of the changes, e.g. "1 file(s) changed, 2 insertion(s), 1 deletion(s)"
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+
+use git2::{Repository, Commit, Tree};
+use toml::Value;
+
+use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+
+use vcs::git::action::StoreAction;
+use vcs::git::error::GitHookErrorKind as GHEK;
+use vcs::git::error::MapErrInto;
+use vcs::git::result::Result;
+
+/// The data which can be put into a commit message template
+#[derive(Debug)]
+pub struct CommitInfo {
+    action: String,
+    id: String,
+    module: String,
+    header_keys: Vec<String>,
+    files: Vec<String>,
+    binary: String,
+    diff: String,
+}
+
+impl CommitInfo {
+
+    /// Collect the information about the commit which is about to be made from `parents` and
+    /// `tree`.
+    ///
+    /// If the action changed the id of the entry (moving), `old_id` is the id it had before, which
+    /// is used to look up the old version of the header.
+    pub fn collect(repo: &Repository,
+                   action: &StoreAction,
+                   id: &StoreId,
+                   old_id: Option<&StoreId>,
+                   parents: &[&Commit],
+                   tree: &Tree)
+        -> Result<CommitInfo>
+    {
+        let old_tree = match parents.first() {
+            Some(parent) => Some(try!(parent.tree().map_err_into(GHEK::RepositoryDiffError))),
+            None         => None,
+        };
+
+        let diff  = try!(repo.diff_tree_to_tree(old_tree.as_ref(), Some(tree), None)
+                         .map_err_into(GHEK::RepositoryDiffError));
+        let stats = try!(diff.stats().map_err_into(GHEK::RepositoryDiffError));
+
+        let files = diff.deltas()
+            .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+            .map(|path| path.display().to_string())
+            .collect();
+
+        let old_id = old_id.unwrap_or(id);
+        let module = id.local()
+            .components()
+            .next()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .unwrap_or_else(String::new);
+
+        Ok(CommitInfo {
+            action: String::from(action.as_commit_message()),
+            id: id.local().display().to_string(),
+            module: module,
+            header_keys: changed_header_keys(repo, old_id, old_tree.as_ref(), id, tree),
+            files: files,
+            binary: binary_name(),
+            diff: format!("{} file(s) changed, {} insertion(s), {} deletion(s)",
+                          stats.files_changed(),
+                          stats.insertions(),
+                          stats.deletions()),
+        })
+    }
+
+    /// Replace the placeholders in `template` with the collected information
+    ///
+    /// The template is rendered in a single pass, so placeholders in the inserted values (for
+    /// example in the id of an entry) are not replaced. Unknown placeholders are left as they are.
+    pub fn render(&self, template: &str) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest     = template;
+
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let value = rest.find('}').and_then(|end| {
+                self.placeholder_value(&rest[1..end]).map(|value| (value, end + 1))
+            });
+
+            match value {
+                Some((value, len)) => {
+                    rendered.push_str(&value);
+                    rest = &rest[len..];
+                },
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                },
+            }
+        }
+
+        rendered.push_str(rest);
+        rendered
+    }
+
+    fn placeholder_value(&self, name: &str) -> Option<String> {
+        match name {
+            "action"      => Some(self.action.clone()),
+            "id"          => Some(self.id.clone()),
+            "module"      => Some(self.module.clone()),
+            "header_keys" => Some(self.header_keys.join(", ")),
+            "files"       => Some(self.files.join(", ")),
+            "binary"      => Some(self.binary.clone()),
+            "diff"        => Some(self.diff.clone()),
+            _             => None,
+        }
+    }
+
+}
+
+/// Get the name of the binary which is running, e.g. "imag-notes"
+fn binary_name() -> String {
+    env::args()
+        .next()
+        .and_then(|arg| Path::new(&arg).file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| String::from("imag"))
+}
+
+/// Get the keys (in "a.b.c" notation) of the header values which differ between the version of
+/// the entry `old_id` in `old` and the version of the entry `new_id` in `new`.
+fn changed_header_keys(repo: &Repository,
+                       old_id: &StoreId,
+                       old: Option<&Tree>,
+                       new_id: &StoreId,
+                       new: &Tree)
+    -> Vec<String>
+{
+    let old = old
+        .and_then(|tree| header_in_tree(repo, tree, old_id))
+        .unwrap_or_else(BTreeMap::new);
+    let new = header_in_tree(repo, new, new_id).unwrap_or_else(BTreeMap::new);
+
+    let mut keys : Vec<String> = old.keys()
+        .chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Read the header of the entry `id` from `tree`, flattened to a map from keys in "a.b.c"
+/// notation to values.
+fn header_in_tree(repo: &Repository, tree: &Tree, id: &StoreId) -> Option<BTreeMap<String, Value>> {
+    tree.get_path(id.local())
+        .ok()
+        .and_then(|tree_entry| repo.find_blob(tree_entry.id()).ok())
+        .and_then(|blob| String::from_utf8(blob.content().to_vec()).ok())
+        .and_then(|content| Entry::from_str(id.clone(), &content).ok())
+        .map(|entry| {
+            let mut map = BTreeMap::new();
+            flatten(String::new(), entry.get_header().header(), &mut map);
+            map
+        })
+}
+
+/// Flatten a header value into a map from keys in "a.b.c" notation to values
+pub fn flatten(prefix: String, value: &Value, map: &mut BTreeMap<String, Value>) {
+    match *value {
+        Value::Table(ref table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(key, value, map);
+            }
+        },
+        _ => {
+            map.insert(prefix, value.clone());
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::fs::{File, create_dir_all};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use git2::{Repository, Signature, Oid, Tree};
+    use tempdir::TempDir;
+    use toml::Parser;
+    use toml::Value;
+
+    use libimagstore::storeid::StoreId;
+
+    use vcs::git::action::StoreAction;
+
+    use super::CommitInfo;
+    use super::changed_header_keys;
+    use super::flatten;
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    /// Write a note entry to `path` in the working tree and add it to the index
+    fn add_note(repo: &Repository, path: &str, header: &str) {
+        let file = repo.workdir().unwrap().join(path);
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(&file)
+            .unwrap()
+            .write_all(format!("---\n[imag]\nversion = \"0.2.0\"\nlinks = []\n\n[note]\n{}\n---\n\
+                               content\n", header).as_bytes())
+            .unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+    }
+
+    fn remove_note(repo: &Repository, path: &str) {
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+    }
+
+    fn index_tree(repo: &Repository) -> Tree {
+        let oid = repo.index().unwrap().write_tree().unwrap();
+        repo.find_tree(oid).unwrap()
+    }
+
+    fn commit_index(repo: &Repository) -> Oid {
+        let tree = index_tree(repo);
+        let sig  = Signature::now("imag", "imag@example.com").unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "commit", &tree, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_render_template() {
+        let info = CommitInfo {
+            action: String::from("Update"),
+            id: String::from("notes/foo"),
+            module: String::from("notes"),
+            header_keys: vec![String::from("imag.links"), String::from("note.name")],
+            files: vec![String::from("notes/foo")],
+            binary: String::from("imag-notes"),
+            diff: String::from("1 file(s) changed, 2 insertion(s), 1 deletion(s)"),
+        };
+
+        let msg = info.render("[{module}] {action} {id} ({header_keys}) via {binary}: {diff}");
+        assert_eq!(msg, "[notes] Update notes/foo (imag.links, note.name) via imag-notes: \
+                         1 file(s) changed, 2 insertion(s), 1 deletion(s)");
+    }
+
+    #[test]
+    fn test_render_does_not_expand_placeholders_in_values() {
+        let info = CommitInfo {
+            action: String::from("Update"),
+            id: String::from("notes/{diff}"),
+            module: String::from("notes"),
+            header_keys: vec![],
+            files: vec![String::from("notes/{diff}")],
+            binary: String::from("imag-notes"),
+            diff: String::from("1 file(s) changed"),
+        };
+
+        let msg = info.render("{action} {id}: {diff} {unknown} {");
+        assert_eq!(msg, "Update notes/{diff}: 1 file(s) changed {unknown} {");
+    }
+
+    #[test]
+    fn test_flatten_header() {
+        let header = Parser::new("[imag]\nversion = \"0.2.0\"\n[note]\nname = \"foo\"").parse().unwrap();
+        let mut map = BTreeMap::new();
+        flatten(String::new(), &Value::Table(header), &mut map);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("imag.version"), Some(&Value::String(String::from("0.2.0"))));
+        assert_eq!(map.get("note.name"), Some(&Value::String(String::from("foo"))));
+    }
+
+    #[test]
+    fn test_collect_update() {
+        let dir  = TempDir::new("imag-git-message").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        add_note(&repo, "notes/foo", "name = \"foo\"");
+        let parent = repo.find_commit(commit_index(&repo)).unwrap();
+
+        add_note(&repo, "notes/foo", "name = \"bar\"\ntag = \"baz\"");
+        let tree = index_tree(&repo);
+
+        let info = CommitInfo::collect(&repo, &StoreAction::Update, &id("notes/foo"), None,
+                                       &[&parent], &tree).unwrap();
+
+        assert_eq!(info.action, "Update");
+        assert_eq!(info.id, "notes/foo");
+        assert_eq!(info.module, "notes");
+        assert_eq!(info.header_keys, vec![String::from("note.name"), String::from("note.tag")]);
+        assert_eq!(info.files, vec![String::from("notes/foo")]);
+        assert_eq!(info.diff, "1 file(s) changed, 2 insertion(s), 1 deletion(s)");
+    }
+
+    #[test]
+    fn test_collect_create() {
+        let dir  = TempDir::new("imag-git-message").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        add_note(&repo, "notes/foo", "name = \"foo\"");
+        let tree = index_tree(&repo);
+
+        let info = CommitInfo::collect(&repo, &StoreAction::Create, &id("notes/foo"), None,
+                                       &[], &tree).unwrap();
+
+        let keys = vec!["imag.links", "imag.version", "note.name"];
+        assert_eq!(info.header_keys, keys.into_iter().map(String::from).collect::<Vec<_>>());
+        assert_eq!(info.files, vec![String::from("notes/foo")]);
+    }
+
+    #[test]
+    fn test_changed_header_keys_of_moved_entry() {
+        let dir  = TempDir::new("imag-git-message").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        add_note(&repo, "notes/foo", "name = \"foo\"");
+        let parent   = repo.find_commit(commit_index(&repo)).unwrap();
+        let old_tree = parent.tree().unwrap();
+
+        remove_note(&repo, "notes/foo");
+        add_note(&repo, "notes/bar", "name = \"bar\"");
+        let new_tree = index_tree(&repo);
+
+        let (old, new) = (id("notes/foo"), id("notes/bar"));
+        assert_eq!(changed_header_keys(&repo, &old, Some(&old_tree), &new, &new_tree),
+                   vec![String::from("note.name")]);
+
+        let info = CommitInfo::collect(&repo, &StoreAction::Move, &new, Some(&old),
+                                       &[&parent], &new_tree).unwrap();
+        assert_eq!(info.id, "notes/bar");
+        assert_eq!(info.header_keys, vec![String::from("note.name")]);
+        assert_eq!(info.files.len(), 2);
+    }
+
+}
@@ -20,10 +20,14 @@
 mod action;
 mod config;
 pub mod delete;
-mod error;
-mod result;
+pub mod error;
+pub mod history;
+mod message;
+pub mod mv;
+pub mod result;
 mod runtime;
 pub mod store_unload;
+mod sync;
 pub mod update;
 pub mod util;
 
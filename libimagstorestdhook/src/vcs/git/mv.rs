@@ -0,0 +1,319 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::path::PathBuf;
+use std::fmt::{Debug, Formatter, Error as FmtError};
+use std::result::Result as RResult;
+
+use toml::Value;
+
+use libimagerror::trace::trace_error;
+use libimagstore::hook::Hook;
+use libimagstore::hook::accessor::MoveAccessor;
+use libimagstore::hook::accessor::{HookDataAccessor, HookDataAccessorProvider};
+use libimagstore::hook::position::HookPosition;
+use libimagstore::hook::result::HookResult;
+use libimagstore::storeid::StoreId;
+use libimagutil::debug_result::*;
+
+use vcs::git::error::GitHookErrorKind as GHEK;
+use vcs::git::error::MapErrInto;
+use vcs::git::runtime::Runtime as GRuntime;
+
+/// The `MoveHook` type
+///
+/// Represents a hook which is executed whenever a entry in the store is moved.
+///
+/// # Time of execution
+///
+/// This hook is executed _after_ the store operation succeeded, so _after_ the file is renamed.
+/// It records the rename in the index like `git mv` does, instead of a deletion and an addition.
+pub struct MoveHook {
+    storepath: PathBuf,
+
+    runtime: GRuntime,
+
+    position: HookPosition,
+}
+
+impl MoveHook {
+
+    pub fn new(storepath: PathBuf, p: HookPosition) -> MoveHook {
+        MoveHook {
+            runtime: GRuntime::new(&storepath),
+            storepath: storepath,
+            position: p,
+        }
+    }
+
+}
+
+impl Debug for MoveHook {
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FmtError> {
+        write!(fmt, "MoveHook(storepath={:?}, repository={}, pos={:?}, cfg={:?})",
+               self.storepath,
+               (if self.runtime.has_repository() { "Some(_)" } else { "None" }),
+               self.position,
+               self.runtime.has_config())
+    }
+}
+
+impl Hook for MoveHook {
+
+    fn name(&self) -> &'static str {
+        "stdhook_git_move"
+    }
+
+    /// Set the configuration of the hook. See
+    /// `libimagstorestdhook::vcs::git::runtime::Runtime::set_config()`.
+    ///
+    /// This function traces the error (using `trace_error()`) that
+    /// `libimagstorestdhook::vcs::git::runtime::Runtime::set_config()`
+    /// returns, if any.
+    fn set_config(&mut self, config: &Value) {
+        if let Err(e) = self.runtime.set_config(config) {
+            trace_error(&e);
+        }
+    }
+
+}
+
+impl HookDataAccessorProvider for MoveHook {
+
+    fn accessor(&self) -> HookDataAccessor {
+        HookDataAccessor::MoveAccess(self)
+    }
+}
+
+impl MoveAccessor for MoveHook {
+
+    /// The implementation of the MoveHook
+    ///
+    /// # Scope
+    ///
+    /// This hook removes the old path from the git index and adds the new one, so git sees a
+    /// rename. Afterwards, the index is committed, if committing is enabled.
+    ///
+    fn access(&self, old: &StoreId, new: &StoreId) -> HookResult<()> {
+        use libimagerror::into::IntoError;
+        use vcs::git::action::StoreAction;
+        use vcs::git::config::commit_message;
+        use vcs::git::error::MapIntoHookError;
+        use vcs::git::util::fetch_index;
+        use vcs::git::config::abort_on_repo_init_err;
+        use vcs::git::config::is_enabled;
+        use vcs::git::config::committing_is_enabled;
+
+        debug!("[GIT MOVE HOOK]: {:?} -> {:?}", old, new);
+
+        let action = StoreAction::Move;
+        let cfg    = try!(self.runtime.config_value_or_err(&action));
+
+        if !is_enabled(cfg) {
+            return Ok(())
+        }
+
+        if !self.runtime.has_repository() {
+            debug!("[GIT MOVE HOOK]: Runtime has no repository...");
+            if try!(self.runtime.config_value_or_err(&action).map(|c| abort_on_repo_init_err(c))) {
+                // Abort on repo init failure
+                debug!("[GIT MOVE HOOK]: Config says we should abort if we have no repository");
+                debug!("[GIT MOVE HOOK]: Returing Err(_)");
+                return Err(GHEK::RepositoryInitError.into_error())
+                    .map_err_into(GHEK::RepositoryError)
+                    .map_into_hook_error()
+            } else {
+                debug!("[GIT MOVE HOOK]: Config says it is okay to not have a repository");
+                debug!("[GIT MOVE HOOK]: Returing Ok(())");
+                return Ok(())
+            }
+        }
+
+        let _         = try!(self.runtime.ensure_cfg_branch_is_checked_out(&action));
+        let repo      = try!(self.runtime.repository(&action));
+        let mut index = try!(fetch_index(&repo, &action));
+
+        let signature = try!(
+            repo.signature()
+                .map_err_into(GHEK::MkSignature)
+                .map_dbg_err_str("Failed to fetch signature")
+                .map_dbg_str("[GIT MOVE HOOK]: Fetched signature object")
+                .map_into_hook_error()
+        );
+
+        let head = try!(
+            repo.head()
+                .map_err_into(GHEK::HeadFetchError)
+                .map_dbg_err_str("Failed to fetch HEAD")
+                .map_dbg_str("[GIT MOVE HOOK]: Fetched HEAD")
+                .map_into_hook_error()
+        );
+
+        try!(
+            index.remove_path(old.local())
+                .map_err_into(GHEK::RepositoryPathRemovingError)
+                .map_dbg_err_str("Failed to remove old path from index")
+                .map_dbg(|_| format!("[GIT MOVE HOOK]: Removed id ({:?}) from index", old))
+                .map_into_hook_error()
+        );
+
+        try!(
+            index.add_path(new.local())
+                .map_err_into(GHEK::RepositoryPathAddingError)
+                .map_dbg_err_str("Failed to add new path to index")
+                .map_dbg(|_| format!("[GIT MOVE HOOK]: Added id ({:?}) to index", new))
+                .map_into_hook_error()
+        );
+
+        let tree_id = try!(
+            index.write_tree()
+                .map_err_into(GHEK::RepositoryIndexWritingError)
+                .map_dbg_err_str("Failed to write tree")
+                .map_dbg_str("[GIT MOVE HOOK]: Wrote index tree")
+                .map_into_hook_error()
+        );
+
+        if !try!(committing_is_enabled(cfg)) {
+            debug!("Committing not enabled. This is fine, writing index and returning now...");
+            return index.write()
+                .map_err_into(GHEK::RepositoryIndexWritingError)
+                .map_dbg_err_str("Failed to write index")
+                .map_into_hook_error()
+                .map(|_| ())
+        }
+
+        let mut parents = Vec::new();
+        {
+            let commit = try!(
+                head.target()
+                    .ok_or(GHEK::HeadFetchError.into_error())
+                    .and_then(|oid| {
+                        repo.find_commit(oid).map_err_into(GHEK::RepositoryParentFetchingError)
+                    })
+                    .map_dbg_err_str("Failed to find commit HEAD")
+                    .map_dbg_str("[GIT MOVE HOOK]: Found commit HEAD")
+                    .map_into_hook_error()
+            );
+            parents.push(commit);
+        }
+
+        // for converting from Vec<Commit> to Vec<&Commit>
+        let parents = parents.iter().collect::<Vec<_>>();
+
+        let tree = try!(
+            repo.find_tree(tree_id)
+                .map_err_into(GHEK::RepositoryParentFetchingError)
+                .map_dbg_err_str("Failed to find tree")
+                .map_dbg_str("[GIT MOVE HOOK]: Found tree for index")
+                .map_into_hook_error()
+        );
+
+        let message = try!(commit_message(&repo, cfg, action, &new, Some(old), &parents, &tree)
+                .map_dbg_err_str("Failed to get commit message")
+                .map_dbg_str("[GIT MOVE HOOK]: Got commit message"));
+
+        try!(repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &parents)
+            .map_dbg_str("Committed")
+            .map_dbg_err_str("Failed to commit")
+            .map_dbg_str("[GIT MOVE HOOK]: Committed")
+            .map_err_into(GHEK::RepositoryCommittingError)
+            .map_into_hook_error()
+        );
+
+        index.write()
+            .map_err_into(GHEK::RepositoryIndexWritingError)
+            .map_dbg_err_str("Failed to write tree")
+            .map_dbg_str("[GIT MOVE HOOK]: Wrote index")
+            .map_into_hook_error()
+            .map(|_| ())
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::fs::{File, rename};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use git2::{Repository, Signature};
+    use tempdir::TempDir;
+    use toml::Value;
+
+    use libimagstore::hook::Hook;
+    use libimagstore::hook::accessor::MoveAccessor;
+    use libimagstore::hook::position::HookPosition;
+    use libimagstore::storeid::StoreId;
+
+    use super::MoveHook;
+
+    fn config() -> Value {
+        let mut commit = BTreeMap::new();
+        commit.insert(String::from("enabled"), Value::Boolean(true));
+        commit.insert(String::from("interactive"), Value::Boolean(false));
+        commit.insert(String::from("message"), Value::String(String::from("moved")));
+
+        let mut cfg = BTreeMap::new();
+        cfg.insert(String::from("enabled"), Value::Boolean(true));
+        cfg.insert(String::from("commit"), Value::Table(commit));
+        Value::Table(cfg)
+    }
+
+    #[test]
+    fn test_move_is_committed() {
+        let dir  = TempDir::new("imag-git-move").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "imag").unwrap();
+            config.set_str("user.email", "imag@example.com").unwrap();
+        }
+
+        File::create(dir.path().join("a")).unwrap().write_all(b"content").unwrap();
+        {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a")).unwrap();
+            index.write().unwrap();
+
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let sig  = Signature::now("imag", "imag@example.com").unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[]).unwrap();
+        }
+
+        // The hook runs after the store renamed the file
+        rename(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        let mut hook = MoveHook::new(dir.path().to_path_buf(), HookPosition::PostMove);
+        hook.set_config(&config());
+
+        let old = StoreId::new_baseless(PathBuf::from("a")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("b")).unwrap();
+        assert!(hook.access(&old, &new).is_ok());
+
+        let head = repo.find_commit(repo.head().unwrap().target().unwrap()).unwrap();
+        assert_eq!(head.message(), Some("moved"));
+        assert_eq!(head.parent_count(), 1);
+
+        let tree = head.tree().unwrap();
+        assert!(tree.get_path(Path::new("a")).is_err());
+        assert!(tree.get_path(Path::new("b")).is_ok());
+    }
+
+}
@@ -18,6 +18,7 @@
 //
 
 use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
 
 use git2::Repository;
 use toml::Value;
@@ -36,8 +37,10 @@ use vcs::git::error::{MapErrInto, GitHookErrorKind as GHEK};
 /// Runtime object for git hook implementations.
 ///
 /// Contains some utility functionality to hold the repository and the configuration for the hooks.
+///
+/// The `Repository` is guarded by a `Mutex`, as hooks might be executed concurrently.
 pub struct Runtime {
-    repository: Option<Repository>,
+    repository: Option<Mutex<Repository>>,
     config: Option<Value>,
 }
 
@@ -50,7 +53,7 @@ impl Runtime {
     /// returns a `Runtime` object that does _not_ contain a `Repository`.
     pub fn new(storepath: &PathBuf) -> Runtime {
         Runtime {
-            repository: Repository::open(storepath).map_err_trace().ok(),
+            repository: Repository::open(storepath).map_err_trace().ok().map(Mutex::new),
             config: None,
         }
     }
@@ -92,15 +95,18 @@ impl Runtime {
     /// Get the `Repository` object from the `Runtime` or an `Err()` that can be returned to the
     /// callee of the Hook.
     ///
+    /// The `Repository` stays locked until the returned guard is dropped.
+    ///
     /// The `action` Argument is required in case of `Err()` so the error message can be build
     /// correctly.
-    pub fn repository(&self, action: &StoreAction) -> HookResult<&Repository> {
+    pub fn repository(&self, action: &StoreAction) -> HookResult<MutexGuard<Repository>> {
         use vcs::git::error::MapIntoHookError;
 
         debug!("[GIT {} HOOK]: Getting repository", action.uppercase());
         self.repository
             .as_ref()
             .ok_or(GHEK::MkRepo.into_error())
+            .and_then(|r| r.lock().map_err(|_| GHEK::RepositoryLockError.into_error()))
             .map_err_into(GHEK::RepositoryError)
             .map_into_hook_error()
             .map_dbg_err(|_| format!("[GIT {} HOOK]: Couldn't fetch Repository", action.uppercase()))
@@ -111,18 +117,16 @@ impl Runtime {
     pub fn ensure_cfg_branch_is_checked_out(&self, action: &StoreAction) -> HookResult<()> {
         use vcs::git::config::ensure_branch;
         use vcs::git::config::do_checkout_ensure_branch;
+        use vcs::git::error::MapIntoHookError;
 
         debug!("[GIT {} HOOK]: Ensuring branch checkout", action.uppercase());
-        let head = try!(self
-                        .repository(action)
-                        .and_then(|r| {
-                            debug!("[GIT {} HOOK]: Repository fetched, getting head", action.uppercase());
-                            r.head()
-                                .map_dbg_err_str("Couldn't fetch HEAD")
-                                .map_dbg_err(|e| format!("\tbecause = {:?}", e))
-                                .map_err_into(GHEK::HeadFetchError)
-                                .map_err(|e| e.into())
-                        }));
+        let repo = try!(self.repository(action));
+        debug!("[GIT {} HOOK]: Repository fetched, getting head", action.uppercase());
+        let head = try!(repo.head()
+                        .map_dbg_err_str("Couldn't fetch HEAD")
+                        .map_dbg_err(|e| format!("\tbecause = {:?}", e))
+                        .map_err_into(GHEK::HeadFetchError)
+                        .map_into_hook_error());
         debug!("[GIT {} HOOK]: HEAD fetched", action.uppercase());
 
         // TODO: Fail if not on branch? hmmh... I'm not sure
@@ -151,8 +155,10 @@ impl Runtime {
                                 Err(GHEK::RepositoryWrongBranchError.into_error())
                                     .map_err_into(GHEK::RepositoryError)
                             } else {
-                                // Else try to check out the branch...
-                                unimplemented!()
+                                checkout_branch(&repo, &s)
+                                    .map_dbg(|_| {
+                                        format!("[GIT {} HOOK]: Checked out {}", action.uppercase(), s)
+                                    })
                             }
                         }
                     },
@@ -176,3 +182,122 @@ impl Runtime {
 
 }
 
+/// Check out the branch `refname` (for example "refs/heads/master").
+///
+/// Fails if the checkout would overwrite changes in the working tree.
+fn checkout_branch(repo: &Repository, refname: &str) -> Result<()> {
+    use git2::build::CheckoutBuilder;
+
+    let reference = try!(repo.find_reference(refname).map_err_into(GHEK::RepositoryBranchError));
+    if !reference.is_branch() {
+        return Err(GHEK::RepositoryBranchError.into_error());
+    }
+
+    let target = try!(reference
+                      .target()
+                      .ok_or(GHEK::RepositoryBranchError.into_error())
+                      .and_then(|oid| repo.find_object(oid, None).map_err_into(GHEK::RepositoryBranchError)));
+
+    try!(repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))
+         .map_err_into(GHEK::RepositoryCheckoutError));
+
+    repo.set_head(refname).map_err_into(GHEK::RepositoryCheckoutError)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::fs::{File, remove_file};
+    use std::io::Write;
+    use std::path::Path;
+
+    use git2::{Repository, Signature};
+    use tempdir::TempDir;
+    use toml::Value;
+
+    use vcs::git::action::StoreAction;
+
+    use super::Runtime;
+    use super::checkout_branch;
+
+    /// Create a repository with a commit on "master" and a commit with the file "other" on the
+    /// branch "other", with "master" checked out
+    fn repo_with_branches(dir: &Path) -> Repository {
+        let repo = Repository::init(dir).unwrap();
+        {
+            let sig = Signature::now("imag", "imag@example.com").unwrap();
+            let commit_file = |name: &str, refname: &str| {
+                File::create(dir.join(name)).unwrap().write_all(b"content").unwrap();
+                let mut index = repo.index().unwrap();
+                index.add_path(Path::new(name)).unwrap();
+                index.write().unwrap();
+
+                let tree    = repo.find_tree(index.write_tree().unwrap()).unwrap();
+                let parents = repo.head()
+                    .ok()
+                    .and_then(|head| head.target())
+                    .map(|oid| repo.find_commit(oid).unwrap());
+                let parents = parents.iter().collect::<Vec<_>>();
+                repo.commit(Some(refname), &sig, &sig, name, &tree, &parents).unwrap();
+            };
+
+            commit_file("master", "HEAD");
+            commit_file("other", "refs/heads/other");
+        }
+
+        // The working tree and the index have to match "master" again
+        remove_file(dir.join("other")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("other")).unwrap();
+        index.write().unwrap();
+        repo
+    }
+
+    fn head_name(repo: &Repository) -> String {
+        String::from(repo.head().unwrap().name().unwrap())
+    }
+
+    #[test]
+    fn test_checkout_branch() {
+        let dir  = TempDir::new("imag-git-checkout").unwrap();
+        let repo = repo_with_branches(dir.path());
+        assert!(!dir.path().join("other").exists());
+
+        assert!(checkout_branch(&repo, "refs/heads/other").is_ok());
+        assert_eq!(head_name(&repo), "refs/heads/other");
+        assert!(dir.path().join("other").exists());
+    }
+
+    #[test]
+    fn test_checkout_nonexistent_branch() {
+        let dir  = TempDir::new("imag-git-checkout").unwrap();
+        let repo = repo_with_branches(dir.path());
+
+        assert!(checkout_branch(&repo, "refs/heads/nonexistent").is_err());
+        assert_eq!(head_name(&repo), "refs/heads/master");
+    }
+
+    #[test]
+    fn test_configured_branch_is_checked_out() {
+        let dir  = TempDir::new("imag-git-checkout").unwrap();
+        let repo = repo_with_branches(dir.path());
+
+        let runtime_with_branch = |branch: &str| {
+            let mut cfg = BTreeMap::new();
+            cfg.insert(String::from("ensure_branch"), Value::String(String::from(branch)));
+            let mut runtime = Runtime::new(&dir.path().to_path_buf());
+            runtime.set_config(&Value::Table(cfg)).unwrap();
+            runtime
+        };
+
+        let runtime = runtime_with_branch("refs/heads/nonexistent");
+        assert!(runtime.ensure_cfg_branch_is_checked_out(&StoreAction::Update).is_err());
+        assert_eq!(head_name(&repo), "refs/heads/master");
+
+        let runtime = runtime_with_branch("refs/heads/other");
+        assert!(runtime.ensure_cfg_branch_is_checked_out(&StoreAction::Update).is_ok());
+        assert_eq!(head_name(&repo), "refs/heads/other");
+        assert!(dir.path().join("other").exists());
+    }
+
+}
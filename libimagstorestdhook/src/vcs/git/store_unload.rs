@@ -21,6 +21,7 @@ use std::path::PathBuf;
 use std::fmt::{Debug, Formatter, Error as FmtError};
 use std::result::Result as RResult;
 
+use git2::Repository;
 use toml::Value;
 
 use libimagerror::trace::trace_error;
@@ -50,6 +51,35 @@ impl StoreUnloadHook {
         }
     }
 
+    /// Synchronize the repository with the configured remote, if this is enabled
+    fn sync_with_remote(&self, repo: &Repository, cfg: &Value) -> HookResult<()> {
+        use libimagerror::into::IntoError;
+        use vcs::git::config::{sync_is_enabled, sync_remote, sync_branch};
+        use vcs::git::error::MapIntoHookError;
+        use vcs::git::sync::sync;
+
+        if !sync_is_enabled(cfg) {
+            debug!("[GIT STORE UNLOAD HOOK]: Synchronizing not enabled");
+            return Ok(());
+        }
+
+        let current = || repo.head().ok().and_then(|head| head.shorthand().map(String::from));
+        let branch  = match sync_branch(cfg).or_else(current) {
+            Some(branch) => branch,
+            None => return Err(GHEK::NotOnBranch.into_error())
+                .map_err_into(GHEK::RepositorySyncError)
+                .map_into_hook_error(),
+        };
+        let remote = sync_remote(cfg);
+
+        debug!("[GIT STORE UNLOAD HOOK]: Synchronizing '{}' with '{}'", branch, remote);
+        sync(repo, &remote, &branch)
+            .map_dbg_err_str("Failed to synchronize with remote")
+            .map_dbg_str("[GIT STORE UNLOAD HOOK]: Synchronized with remote")
+            .map_err_into(GHEK::RepositorySyncError)
+            .map_into_hook_error()
+    }
+
 }
 
 impl Debug for StoreUnloadHook {
@@ -140,7 +170,7 @@ impl StoreIdAccessor for StoreUnloadHook {
 
         let _         = try!(self.runtime.ensure_cfg_branch_is_checked_out(&action));
         let repo      = try!(self.runtime.repository(&action));
-        let mut index = try!(fetch_index(repo, &action));
+        let mut index = try!(fetch_index(&repo, &action));
 
         let check_dirty = |show: STShow, new: Status, modif: Status, del: Status, ren: Status| {
             let mut status_options = StatusOptions::new();
@@ -184,7 +214,7 @@ impl StoreIdAccessor for StoreUnloadHook {
             debug!("INDEX DIRTY!");
         } else {
             debug!("INDEX CLEAN... not continuing!");
-            return Ok(());
+            return self.sync_with_remote(&repo, cfg);
         }
 
         let signature = try!(
@@ -213,7 +243,7 @@ impl StoreIdAccessor for StoreUnloadHook {
 
         if !try!(committing_is_enabled(cfg)) {
             debug!("Committing not enabled. This is fine, returning now...");
-            return Ok(())
+            return self.sync_with_remote(&repo, cfg);
         }
 
         let mut parents = Vec::new();
@@ -239,7 +269,7 @@ impl StoreIdAccessor for StoreUnloadHook {
                 .map_into_hook_error()
         );
 
-        let message = try!(commit_message(&repo, cfg, action, &id)
+        let message = try!(commit_message(&repo, cfg, action, &id, None, &parents, &tree)
                 .map_dbg_err_str("Failed to get commit message")
                 .map_dbg_str("[GIT STORE UNLOAD HOOK]: Got commit message"));
 
@@ -256,7 +286,7 @@ impl StoreIdAccessor for StoreUnloadHook {
             .map_dbg_err_str("Failed to write tree")
             .map_dbg_str("[GIT STORE UNLOAD HOOK]: Wrote index")
             .map_into_hook_error()
-            .map(|_| ())
+            .and_then(|_| self.sync_with_remote(&repo, cfg))
     }
 
 }
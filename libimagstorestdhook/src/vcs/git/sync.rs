@@ -0,0 +1,292 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015, 2016 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+//! Synchronizing the store repository with a remote repository
+//!
+//! The local commits are rebased onto the branch of the remote before the branch is pushed, so
+//! the history stays linear. Repositories with uncommitted changes are not synchronized, as the
+//! rebased branch could not be checked out without losing them.
+
+use git2::{Repository, Oid, Cred, RemoteCallbacks, FetchOptions, PushOptions};
+
+use libimagerror::into::IntoError;
+
+use vcs::git::error::GitHookErrorKind as GHEK;
+use vcs::git::error::MapErrInto;
+use vcs::git::result::Result;
+
+/// Fetch `branch` from the remote `remote_name`, rebase the local branch onto it and push the
+/// result to the remote.
+///
+/// If the remote does not have the branch yet, it is pushed without rebasing. Fails with
+/// `RepositoryDirty` if the working tree or the index have uncommitted changes.
+pub fn sync(repo: &Repository, remote_name: &str, branch: &str) -> Result<()> {
+    if try!(has_uncommitted_changes(repo)) {
+        warn!("The store has uncommitted changes, not synchronizing with '{}'", remote_name);
+        return Err(GHEK::RepositoryDirty.into_error());
+    }
+
+    let mut remote = try!(repo.find_remote(remote_name).map_err_into(GHEK::RepositoryRemoteError));
+    let local_ref  = format!("refs/heads/{}", branch);
+    let remote_ref = format!("refs/remotes/{}/{}", remote_name, branch);
+
+    debug!("Fetching '{}' from '{}'", branch, remote_name);
+    let fetchspec = format!("+{}:{}", local_ref, remote_ref);
+    try!(remote.fetch(&[&fetchspec[..]], Some(&mut fetch_options()), None)
+         .map_err_into(GHEK::RepositoryFetchError));
+
+    match repo.refname_to_id(&remote_ref) {
+        Ok(upstream) => try!(rebase_onto(repo, &local_ref, upstream)),
+        Err(_)       => debug!("Remote has no branch '{}' yet, not rebasing", branch),
+    }
+
+    debug!("Pushing '{}' to '{}'", branch, remote_name);
+    let pushspec = format!("{}:{}", local_ref, local_ref);
+    remote.push(&[&pushspec[..]], Some(&mut push_options()))
+        .map_err_into(GHEK::RepositoryPushError)
+}
+
+/// Rebase the commits of the branch `local_ref` which are not reachable from `upstream` onto
+/// `upstream` and check out the result if the branch is checked out.
+fn rebase_onto(repo: &Repository, local_ref: &str, upstream: Oid) -> Result<()> {
+    use git2::build::CheckoutBuilder;
+
+    let local = try!(repo.refname_to_id(local_ref).map_err_into(GHEK::RepositoryBranchError));
+    let base  = try!(repo.merge_base(local, upstream).map_err_into(GHEK::RepositoryRebaseError));
+
+    if base == upstream {
+        debug!("Local branch already contains the remote branch, nothing to rebase");
+        return Ok(());
+    }
+
+    let new_tip = if base == local {
+        debug!("Fast-forwarding to the remote branch");
+        upstream
+    } else {
+        debug!("Local and remote branch diverged, rebasing");
+        try!(replay_commits(repo, local, upstream))
+    };
+
+    try!(repo.reference(local_ref, new_tip, true, "imag: rebase onto remote branch")
+         .map_err_into(GHEK::RepositoryRebaseError));
+
+    let head_is_branch = repo.head()
+        .ok()
+        .and_then(|head| head.name().map(|name| name == local_ref))
+        .unwrap_or(false);
+
+    if head_is_branch {
+        // The working tree is clean (see `sync()`), but files which are not tracked must not be
+        // overwritten either
+        repo.checkout_head(Some(CheckoutBuilder::new().safe()))
+            .map_err_into(GHEK::RepositoryCheckoutError)
+    } else {
+        Ok(())
+    }
+}
+
+/// Check whether the working tree or the index of `repo` differ from HEAD
+///
+/// Untracked files do not count, the checkout after rebasing does not overwrite them.
+fn has_uncommitted_changes(repo: &Repository) -> Result<bool> {
+    use git2::StatusOptions;
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+
+    repo.statuses(Some(&mut options))
+        .map(|statuses| statuses.iter().next().is_some())
+        .map_err_into(GHEK::RepositoryStatusFetchError)
+}
+
+/// Re-apply the commits reachable from `local` but not from `upstream` on top of `upstream`,
+/// oldest first. Returns the new tip.
+fn replay_commits(repo: &Repository, local: Oid, upstream: Oid) -> Result<Oid> {
+    use git2::{SORT_TOPOLOGICAL, SORT_REVERSE};
+
+    let mut walk = try!(repo.revwalk().map_err_into(GHEK::RepositoryRebaseError));
+    walk.set_sorting(SORT_TOPOLOGICAL | SORT_REVERSE);
+    try!(walk.push(local).map_err_into(GHEK::RepositoryRebaseError));
+    try!(walk.hide(upstream).map_err_into(GHEK::RepositoryRebaseError));
+
+    let mut tip = upstream;
+    for oid in walk {
+        let commit = try!(oid
+                          .and_then(|oid| repo.find_commit(oid))
+                          .map_err_into(GHEK::RepositoryRebaseError));
+
+        if commit.parent_count() != 1 {
+            warn!("Cannot rebase merge commit {}", commit.id());
+            return Err(GHEK::RepositoryRebaseError.into_error());
+        }
+
+        let onto        = try!(repo.find_commit(tip).map_err_into(GHEK::RepositoryRebaseError));
+        let parent_tree = try!(commit.parent(0)
+                               .and_then(|p| p.tree())
+                               .map_err_into(GHEK::RepositoryRebaseError));
+        let onto_tree   = try!(onto.tree().map_err_into(GHEK::RepositoryRebaseError));
+        let own_tree    = try!(commit.tree().map_err_into(GHEK::RepositoryRebaseError));
+
+        let mut index = try!(repo.merge_trees(&parent_tree, &onto_tree, &own_tree, None)
+                             .map_err_into(GHEK::RepositoryRebaseError));
+
+        if index.has_conflicts() {
+            warn!("Rebasing commit {} onto the remote branch results in conflicts", commit.id());
+            return Err(GHEK::RepositoryRebaseConflict.into_error());
+        }
+
+        let tree = try!(index
+                        .write_tree_to(repo)
+                        .and_then(|id| repo.find_tree(id))
+                        .map_err_into(GHEK::RepositoryRebaseError));
+
+        tip = try!(repo.commit(None,
+                               &commit.author(),
+                               &commit.committer(),
+                               commit.message().unwrap_or(""),
+                               &tree,
+                               &[&onto])
+                   .map_err_into(GHEK::RepositoryCommittingError));
+        debug!("Rebased {} as {}", commit.id(), tip);
+    }
+
+    Ok(tip)
+}
+
+/// Callbacks for talking to remotes, using the ssh agent for authentication
+fn callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|_url, username, _allowed| {
+        Cred::ssh_key_from_agent(username.unwrap_or("git"))
+    });
+    callbacks
+}
+
+fn fetch_options<'a>() -> FetchOptions<'a> {
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks());
+    options
+}
+
+fn push_options<'a>() -> PushOptions<'a> {
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks());
+    options
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::path::Path;
+
+    use git2::{Repository, Signature, Oid};
+    use tempdir::TempDir;
+
+    use super::sync;
+
+    fn commit_file(repo: &Repository, name: &str, content: &str) -> Oid {
+        let path = repo.workdir().unwrap().join(name);
+        File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+
+        let tree    = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig     = Signature::now("imag", "imag@example.com").unwrap();
+        let parents = repo.head()
+            .ok()
+            .and_then(|head| head.target())
+            .map(|oid| repo.find_commit(oid).unwrap());
+        let parents = parents.iter().collect::<Vec<_>>();
+
+        repo.commit(Some("HEAD"), &sig, &sig, name, &tree, &parents).unwrap()
+    }
+
+    #[test]
+    fn test_sync_pushes_to_empty_remote() {
+        let dir    = TempDir::new("imag-git-sync").unwrap();
+        let bare   = Repository::init_bare(dir.path().join("remote")).unwrap();
+        let local  = Repository::init(dir.path().join("local")).unwrap();
+        local.remote("origin", dir.path().join("remote").to_str().unwrap()).unwrap();
+
+        let oid = commit_file(&local, "a", "a");
+        assert!(sync(&local, "origin", "master").is_ok());
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), oid);
+    }
+
+    #[test]
+    fn test_sync_rebases_onto_remote() {
+        let dir    = TempDir::new("imag-git-sync").unwrap();
+        let remote = dir.path().join("remote");
+        let bare   = Repository::init_bare(&remote).unwrap();
+
+        let first = Repository::init(dir.path().join("first")).unwrap();
+        first.remote("origin", remote.to_str().unwrap()).unwrap();
+        commit_file(&first, "a", "a");
+        assert!(sync(&first, "origin", "master").is_ok());
+
+        let second = Repository::clone(remote.to_str().unwrap(), dir.path().join("second")).unwrap();
+
+        let remote_tip = commit_file(&first, "b", "b");
+        assert!(sync(&first, "origin", "master").is_ok());
+
+        commit_file(&second, "c", "c");
+        assert!(sync(&second, "origin", "master").is_ok());
+
+        let tip = second.refname_to_id("refs/heads/master").unwrap();
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), tip);
+
+        let tip = second.find_commit(tip).unwrap();
+        assert_eq!(tip.parent_id(0).unwrap(), remote_tip);
+        assert!(second.workdir().unwrap().join("b").exists());
+        assert!(second.workdir().unwrap().join("c").exists());
+    }
+
+    #[test]
+    fn test_sync_refuses_dirty_working_tree() {
+        let dir    = TempDir::new("imag-git-sync").unwrap();
+        let remote = dir.path().join("remote");
+        let bare   = Repository::init_bare(&remote).unwrap();
+
+        let first = Repository::init(dir.path().join("first")).unwrap();
+        first.remote("origin", remote.to_str().unwrap()).unwrap();
+        commit_file(&first, "a", "a");
+        assert!(sync(&first, "origin", "master").is_ok());
+
+        let second = Repository::clone(remote.to_str().unwrap(), dir.path().join("second")).unwrap();
+        let local_tip = second.refname_to_id("refs/heads/master").unwrap();
+
+        let remote_tip = commit_file(&first, "a", "changed remotely");
+        assert!(sync(&first, "origin", "master").is_ok());
+
+        // Like an entry which was changed while committing is disabled
+        let path = second.workdir().unwrap().join("a");
+        File::create(&path).unwrap().write_all(b"changed locally").unwrap();
+
+        assert!(sync(&second, "origin", "master").is_err());
+
+        let mut content = String::new();
+        File::open(&path).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "changed locally");
+        assert_eq!(second.refname_to_id("refs/heads/master").unwrap(), local_tip);
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), remote_tip);
+    }
+
+}
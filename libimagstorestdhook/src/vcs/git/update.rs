@@ -169,7 +169,7 @@ impl StoreIdAccessor for UpdateHook {
             return Ok(())
         }
 
-        let mut index = try!(fetch_index(repo, &action));
+        let mut index = try!(fetch_index(&repo, &action));
 
         let signature = try!(
             repo.signature()
@@ -241,7 +241,7 @@ impl StoreIdAccessor for UpdateHook {
                 .map_into_hook_error()
         );
 
-        let message = try!(commit_message(&repo, cfg, StoreAction::Update, &id)
+        let message = try!(commit_message(&repo, cfg, action, &id, None, &parents, &tree)
                 .map_dbg_err_str("Failed to get commit message")
                 .map_dbg_str("[GIT UPDATE HOOK]: Fetched commit message"));
 